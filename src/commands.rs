@@ -1,56 +1,325 @@
-use crate::programs::arch::{Arch, ARCH};
-use crate::MakeImage;
+use crate::error::{Error, Result};
+use crate::programs::{find_program, PROGRAMS};
 use clap::{Arg, ArgMatches, Command};
-use image::ImageFormat;
-
-use std::path::Path;
+use image::{DynamicImage, ImageError, ImageResult};
+use ray_tracer::canvas::{aov, layers, Layers, AOVS};
+use ray_tracer::color::color_from_hex;
+use ray_tracer::denoise::{denoise, denoiser_from_name, guides, DENOISERS};
+use ray_tracer::output::{
+    displayable_aov, encode_animation, encode_layers, expand_template, format_from_extension,
+    format_from_path, is_floating_point, supports_animation, OutputFormat, EXTENSIONS,
+};
+use std::fmt::Display;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
 
 pub const FILENAME: &str = "out";
+pub const STDOUT: &str = "-";
+pub const LIST: &str = "list";
+pub const OUTPUT: &str = "output";
+pub const NO_CLOBBER: &str = "no-clobber";
 pub const WIDTH: &str = "width";
 pub const HEIGHT: &str = "height";
 pub const EXTENSION: &str = "ext";
+pub const DENOISE: &str = "denoise";
+pub const AOV: &str = "aov";
+pub const FRAMES: &str = "frames";
+pub const FPS: &str = "fps";
+pub const DEFAULT_EXTENSION: &str = "ppm";
+pub const DEFAULT_DENOISER: &str = "none";
 pub const E: char = 'e';
 pub const W: char = 'w';
 pub const H: char = 'h';
+pub const O: char = 'o';
+
+pub fn run(matches: ArgMatches) -> Result<()> {
+    if let Some((LIST, _)) = matches.subcommand() {
+        list_programs();
+        return Ok(());
+    }
+
+    let output = matches.value_of(OUTPUT).unwrap_or(FILENAME);
+    let explicit_ext = matches.occurrences_of(EXTENSION) > 0;
+    let ext = matches.value_of(EXTENSION).unwrap_or(DEFAULT_EXTENSION);
+    let format = format_from_extension(ext).ok_or_else(|| {
+        Error::invalid_argument(
+            EXTENSION,
+            ext,
+            &format!("expected one of {}", EXTENSIONS.join(", ")),
+        )
+    })?;
+    let denoiser_name = matches.value_of(DENOISE).unwrap_or(DEFAULT_DENOISER);
+    let denoiser = denoiser_from_name(denoiser_name).ok_or_else(|| {
+        Error::invalid_argument(
+            DENOISE,
+            denoiser_name,
+            &format!("expected one of {}", DENOISERS.join(", ")),
+        )
+    })?;
+
+    let (scene, sub_matches) = matches.subcommand().unwrap();
+    let (w, h) = width_and_height(&matches)?;
+    let frames: u32 = parse_arg(&matches, FRAMES)?;
+    let fps: u32 = parse_arg(&matches, FPS)?;
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
 
-pub fn run(matches: ArgMatches) {
-    let ext = matches.value_of(EXTENSION).unwrap();
-
-    match ext {
-        "jpg" => {}
-        "png" => {}
-        "ppm" => {}
-        _ => {
-            eprintln!(
-                "bad file extension: '{}' is not one of:\n1. jpg\n2. ppm\n3. png",
-                ext
-            );
-            std::process::exit(1);
+    let program = find_program(scene).unwrap();
+    let expand = |template: &str, frame: u32| {
+        PathBuf::from(expand_template(
+            template,
+            &[
+                ("scene", scene.to_string()),
+                ("width", w.to_string()),
+                ("height", h.to_string()),
+                ("frame", format!("{:04}", frame)),
+                ("timestamp", timestamp.to_string()),
+            ],
+        ))
+    };
+
+    let to_stdout = output == STDOUT;
+    let aovs: Vec<&str> = matches
+        .values_of(AOV)
+        .map(Iterator::collect)
+        .unwrap_or_default();
+    if let Some(name) = aovs.first().filter(|_| to_stdout) {
+        return Err(Error::invalid_argument(
+            AOV,
+            name,
+            "buffers are saved beside --output, which can't be stdout",
+        ));
+    }
+    // several frames either fill in {frame} to make a numbered sequence, or go into one file
+    let sequence = frames > 1 && !to_stdout && output.contains("{frame}");
+    let resolve = |frame: u32| {
+        if to_stdout {
+            return Ok((PathBuf::from(STDOUT), format));
+        }
+        let path = expand(output, frame);
+        match format_from_path(&path) {
+            Some(inferred) if !explicit_ext || inferred == format => Ok((path, inferred)),
+            // writing one format under another's extension would leave a file nothing can open
+            Some(_) => Err(Error::invalid_argument(
+                EXTENSION,
+                ext,
+                &format!("conflicts with the extension of {}", path.display()),
+            )),
+            // an extension we can't write is part of the name, so ours goes after it
+            None => Ok((append_extension(path, ext), format)),
+        }
+    };
+    // frames are numbered from 1 in file names
+    let outputs: Vec<(PathBuf, OutputFormat)> = if sequence {
+        (1..=frames).map(resolve).collect::<Result<_>>()?
+    } else {
+        vec![resolve(1)?]
+    };
+
+    if frames > 1 && !sequence && !supports_animation(outputs[0].1) {
+        return Err(Error::invalid_argument(
+            FRAMES,
+            &frames.to_string(),
+            "several frames need {frame} in --output for a numbered sequence, \
+            or a gif or png output to animate",
+        ));
+    }
+    // extra outputs such as data files are written once per run, so they're the first frame's
+    let extras: Vec<PathBuf> = program
+        .extra_outputs(sub_matches)?
+        .iter()
+        .map(|template| expand(template, 1))
+        .collect();
+    if matches.is_present(NO_CLOBBER) {
+        let images = outputs
+            .iter()
+            .map(|(path, _)| path.clone())
+            .filter(|_| !to_stdout);
+        let buffers = outputs
+            .iter()
+            .flat_map(|(path, _)| aovs.iter().map(|name| aov_path(path, name)));
+        let mut all = images.chain(buffers).chain(extras.iter().cloned());
+        if let Some(path) = all.find(|path| path.exists()) {
+            return Err(Error::OutputExists(path));
         }
     }
 
-    let filename = format!("{}.{}", FILENAME, ext);
+    // numbered frames are written as soon as they're rendered; a single file needs them all
+    let mut images = vec![];
+    let mut sequence_outputs = outputs.iter();
+    let contents = program.make_frames(sub_matches, frames, &mut |mut image| {
+        if let Some(name) = aovs.iter().find(|name| aov(&image, name).is_none()) {
+            return Err(Error::invalid_argument(
+                AOV,
+                name,
+                &format!("{} doesn't render this buffer", scene),
+            ));
+        }
+        image.color = denoise(&image.color, guides(&image), denoiser)?;
+        match sequence_outputs.next().filter(|_| sequence) {
+            Some((path, format)) => {
+                let frame = std::slice::from_ref(&image);
+                write_image(path, frame, *format, fps)?;
+                write_aovs(path, frame, &aovs, *format, fps)
+            }
+            None => {
+                images.push(image);
+                Ok(())
+            }
+        }
+    })?;
 
-    let image = match matches.subcommand() {
-        Some((ARCH, sub_matches)) => Arch::make(sub_matches),
-        _ => unreachable!(),
+    if to_stdout {
+        io::stdout().write_all(&encode_frames(&images, format, fps)?)?;
+    } else if !sequence {
+        let (path, format) = &outputs[0];
+        write_image(path, &images, *format, fps)?;
+        write_aovs(path, &images, &aovs, *format, fps)?;
+    }
+
+    for (path, contents) in extras.iter().zip(contents) {
+        write_file(path, contents.as_bytes())?;
+    }
+    Ok(())
+}
+
+///
+/// Adds an extension after any the path already has, e.g. "my.render" becomes "my.render.ppm"
+///
+fn append_extension(path: PathBuf, ext: &str) -> PathBuf {
+    let mut name = path.into_os_string();
+    name.push(".");
+    name.push(ext);
+    PathBuf::from(name)
+}
+
+///
+/// Where an auxiliary buffer is saved beside an image, with its name before the extension,
+/// e.g. "out.png" becomes "out.depth.png"
+///
+fn aov_path(path: &Path, name: &str) -> PathBuf {
+    match path.extension() {
+        Some(ext) => path.with_extension(format!("{}.{}", name, ext.to_string_lossy())),
+        None => append_extension(path.to_path_buf(), name),
+    }
+}
+
+///
+/// Saves each of `aovs` from the frames beside the image at `path`, in the same format.
+/// Formats that clip get the buffers brought into range, see `displayable_aov`
+///
+fn write_aovs(
+    path: &Path,
+    images: &[Layers],
+    aovs: &[&str],
+    format: OutputFormat,
+    fps: u32,
+) -> Result<()> {
+    for name in aovs {
+        let buffers: Vec<Layers> = images
+            .iter()
+            .filter_map(|image| aov(image, name))
+            .map(|buffer| {
+                if is_floating_point(format) {
+                    layers(buffer.clone())
+                } else {
+                    layers(displayable_aov(name, buffer))
+                }
+            })
+            .collect();
+        write_image(&aov_path(path, name), &buffers, format, fps)?;
+    }
+    Ok(())
+}
+
+///
+/// Encodes frames into one image file, see `encode_frames`
+///
+fn write_image(path: &Path, images: &[Layers], format: OutputFormat, fps: u32) -> Result<()> {
+    let encoded = encode_frames(images, format, fps).map_err(|source| Error::Write {
+        path: path.to_path_buf(),
+        source,
+    })?;
+    write_file(path, &encoded)
+}
+
+///
+/// Writes a file, creating the directories it goes in
+///
+fn write_file(path: &Path, contents: &[u8]) -> Result<()> {
+    let write = || {
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, contents)
     };
+    write().map_err(|e| Error::Write {
+        path: path.to_path_buf(),
+        source: ImageError::IoError(e),
+    })
+}
 
-    image.save(filename).expect("error saving image");
+///
+/// Encodes a single frame as a still, with all of its layers where the format has room for them,
+/// and several as an animation
+///
+fn encode_frames(images: &[Layers], format: OutputFormat, fps: u32) -> ImageResult<Vec<u8>> {
+    if images.len() > 1 {
+        let frames: Vec<_> = images
+            .iter()
+            .map(|image| DynamicImage::ImageRgb32F(image.color.clone()))
+            .collect();
+        encode_animation(&frames, format, fps)
+    } else {
+        encode_layers(&images[0], format)
+    }
 }
 
 pub fn get_subcommands() -> Vec<Command<'static>> {
-    vec![Arch::subcommand()]
+    let mut subcommands: Vec<_> = PROGRAMS.iter().map(|p| p.subcommand()).collect();
+    subcommands.push(Command::new(LIST).about("Lists the available programs"));
+    subcommands
+}
+
+///
+/// Prints each program's name and description, one per line
+///
+pub fn list_programs() {
+    let width = PROGRAMS.iter().map(|p| p.name().len()).max().unwrap_or(0);
+    for p in PROGRAMS {
+        println!("{:width$}  {}", p.name(), p.about(), width = width);
+    }
 }
 
 pub fn global_args() -> Vec<Arg<'static>> {
     vec![
+        Arg::new(OUTPUT)
+            .long(OUTPUT)
+            .short(O)
+            .help(
+                "The path to save to, or - for stdout. The format follows the extension, which \
+                --ext must agree with. {scene}, {width}, {height}, {frame} and {timestamp} are filled in",
+            )
+            .global(true)
+            .required(false)
+            .default_value(FILENAME),
+        Arg::new(NO_CLOBBER)
+            .long(NO_CLOBBER)
+            .help("Fail instead of overwriting an existing file")
+            .global(true)
+            .required(false),
         Arg::new(WIDTH)
             .long(WIDTH)
             .short(W)
             .help("The width of the canvas")
             .global(true)
             .required(false)
+            .validator(validate_positive_u32)
             .default_value("1000"),
         Arg::new(HEIGHT)
             .long(HEIGHT)
@@ -58,19 +327,510 @@ pub fn global_args() -> Vec<Arg<'static>> {
             .help("The height of the canvas")
             .global(true)
             .required(false)
+            .validator(validate_positive_u32)
             .default_value("1000"),
         Arg::new(EXTENSION)
             .long(EXTENSION)
             .short(E)
-            .help("The file extension to save (png, ppm, jpeg)")
+            .help("The format to save")
+            .global(true)
+            .required(false)
+            .possible_values(EXTENSIONS)
+            .ignore_case(true)
+            .default_value(DEFAULT_EXTENSION),
+        Arg::new(FRAMES)
+            .long(FRAMES)
+            .help(
+                "The number of frames to render. They're numbered into a sequence if --output \
+                has {frame}, otherwise animated in a gif or png",
+            )
             .global(true)
             .required(false)
-            .default_value("ppm"),
+            .validator(validate_positive_u32)
+            .default_value("1"),
+        Arg::new(FPS)
+            .long(FPS)
+            .help("The frames per second of an animation")
+            .global(true)
+            .required(false)
+            .validator(validate_positive_u32)
+            .default_value("24"),
+        Arg::new(DENOISE)
+            .long(DENOISE)
+            .help("The filter to denoise the image with before saving")
+            .global(true)
+            .required(false)
+            .possible_values(DENOISERS)
+            .default_value(DEFAULT_DENOISER),
+        Arg::new(AOV)
+            .long(AOV)
+            .help(
+                "Also saves an auxiliary buffer of the render beside --output, e.g. out.depth.png \
+                for depth. May be repeated",
+            )
+            .global(true)
+            .required(false)
+            .multiple_occurrences(true)
+            .possible_values(AOVS),
     ]
 }
 
-pub fn width_and_height(matches: &ArgMatches) -> (u32, u32) {
-    let w = matches.value_of(WIDTH).unwrap().parse::<u32>().unwrap();
-    let h = matches.value_of(HEIGHT).unwrap().parse::<u32>().unwrap();
-    (w, h)
+pub fn width_and_height(matches: &ArgMatches) -> Result<(u32, u32)> {
+    let w = parse_arg(matches, WIDTH)?;
+    let h = parse_arg(matches, HEIGHT)?;
+    Ok((w, h))
+}
+
+///
+/// Parses the value of an argument, reporting which argument was bad if it doesn't parse
+///
+pub fn parse_arg<T>(matches: &ArgMatches, name: &str) -> Result<T>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    let value = matches
+        .value_of(name)
+        .ok_or_else(|| Error::invalid_argument(name, "", "a value is required"))?;
+    value
+        .parse::<T>()
+        .map_err(|e| Error::invalid_argument(name, value, &e.to_string()))
+}
+
+///
+/// clap validator for positive integers, such as canvas dimensions
+///
+pub fn validate_positive_u32(v: &str) -> std::result::Result<(), String> {
+    match v.parse::<u32>() {
+        Ok(0) => Err("must be greater than zero".to_string()),
+        Ok(_) => Ok(()),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+///
+/// clap validator for finite floating point numbers
+///
+pub fn validate_f64(v: &str) -> std::result::Result<(), String> {
+    match v.parse::<f64>() {
+        Ok(f) if f.is_finite() => Ok(()),
+        Ok(_) => Err("must be a finite number".to_string()),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+///
+/// clap validator for finite floating point numbers greater than zero, such as step sizes
+///
+pub fn validate_positive_f64(v: &str) -> std::result::Result<(), String> {
+    validate_f64(v)?;
+    match v.parse::<f64>() {
+        Ok(f) if f <= 0.0 => Err("must be greater than zero".to_string()),
+        _ => Ok(()),
+    }
+}
+
+///
+/// clap validator for finite floating point numbers that can't be negative, such as coefficients
+///
+pub fn validate_non_negative_f64(v: &str) -> std::result::Result<(), String> {
+    validate_f64(v)?;
+    match v.parse::<f64>() {
+        Ok(f) if f < 0.0 => Err("must not be negative".to_string()),
+        _ => Ok(()),
+    }
+}
+
+///
+/// Three numbers given on the command line as "x,y,z"
+///
+pub type Triple = (f64, f64, f64);
+
+///
+/// clap validator for comma separated triples like "0,0,-5"
+///
+pub fn validate_triple(v: &str) -> std::result::Result<(), String> {
+    parse_triple_str(v).map(|_| ())
+}
+
+///
+/// Parses an argument holding a comma separated triple like "0,0,-5"
+///
+pub fn parse_triple(matches: &ArgMatches, name: &str) -> Result<Triple> {
+    let value = matches
+        .value_of(name)
+        .ok_or_else(|| Error::invalid_argument(name, "", "a value is required"))?;
+    parse_triple_str(value).map_err(|e| Error::invalid_argument(name, value, &e))
+}
+
+///
+/// Parses every occurrence of an argument holding a comma separated triple
+///
+pub fn parse_triples(matches: &ArgMatches, name: &str) -> Result<Vec<Triple>> {
+    matches
+        .values_of(name)
+        .into_iter()
+        .flatten()
+        .map(|value| parse_triple_str(value).map_err(|e| Error::invalid_argument(name, value, &e)))
+        .collect()
+}
+
+fn parse_triple_str(v: &str) -> std::result::Result<Triple, String> {
+    let parts = v
+        .split(',')
+        .map(|p| p.trim().parse::<f64>().map_err(|e| e.to_string()))
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+    match parts[..] {
+        [x, y, z] if x.is_finite() && y.is_finite() && z.is_finite() => Ok((x, y, z)),
+        [_, _, _] => Err("must be finite numbers".to_string()),
+        _ => Err("expected three comma separated numbers like 0,0,-5".to_string()),
+    }
+}
+
+///
+/// clap validator for a number or an inclusive range like "15:75:15" (start:end:step)
+///
+pub fn validate_range(v: &str) -> std::result::Result<(), String> {
+    parse_range_str(v).map(|_| ())
+}
+
+///
+/// Parses an argument holding a number or an inclusive range like "15:75:15" into its values
+///
+pub fn parse_range(matches: &ArgMatches, name: &str) -> Result<Vec<f64>> {
+    let value = matches
+        .value_of(name)
+        .ok_or_else(|| Error::invalid_argument(name, "", "a value is required"))?;
+    parse_range_str(value).map_err(|e| Error::invalid_argument(name, value, &e))
+}
+
+///
+/// The most values a range may expand to
+///
+pub const MAX_RANGE_LEN: usize = 1000;
+
+fn parse_range_str(v: &str) -> std::result::Result<Vec<f64>, String> {
+    let parts = v
+        .split(':')
+        .map(|p| match p.trim().parse::<f64>() {
+            Ok(f) if f.is_finite() => Ok(f),
+            Ok(_) => Err("must be finite numbers".to_string()),
+            Err(e) => Err(e.to_string()),
+        })
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+    let (start, end, step) = match parts[..] {
+        [single] => return Ok(vec![single]),
+        [start, end, step] => (start, end, step),
+        _ => return Err("expected a number or start:end:step like 15:75:15".to_string()),
+    };
+    if step <= 0.0 {
+        return Err("the step must be greater than zero".to_string());
+    }
+    if end < start {
+        return Err("the end must not be before the start".to_string());
+    }
+    // a little slack so that e.g. 0:1:0.1 includes 1 despite rounding. Counted in floating
+    // point so huge ranges are rejected before they could overflow an integer
+    let count = ((end - start) / step + 1e-9).floor() + 1.0;
+    if count > MAX_RANGE_LEN as f64 {
+        return Err(format!("expands to more than {} values", MAX_RANGE_LEN));
+    }
+    Ok((0..count as usize)
+        .map(|i| start + i as f64 * step)
+        .collect())
+}
+
+///
+/// clap validator for hex colors like "#1a80ff"
+///
+pub fn validate_color(v: &str) -> std::result::Result<(), String> {
+    color_from_hex(v)
+        .map(|_| ())
+        .ok_or_else(|| "expected a hex color like #1a80ff".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{cli, temp_path, try_matches};
+
+    #[test]
+    fn dimensions_must_be_positive_integers() {
+        assert!(validate_positive_u32("640").is_ok());
+        assert!(validate_positive_u32("0").is_err());
+        assert!(validate_positive_u32("-3").is_err());
+        assert!(validate_positive_u32("abc").is_err());
+    }
+
+    #[test]
+    fn triples_need_three_finite_numbers() {
+        assert_eq!(parse_triple_str("0, 1.5,-5"), Ok((0.0, 1.5, -5.0)));
+        assert!(validate_triple("1,2").is_err());
+        assert!(validate_triple("1,2,3,4").is_err());
+        assert!(validate_triple("1,x,3").is_err());
+        assert!(validate_triple("1,inf,3").is_err());
+    }
+
+    #[test]
+    fn ranges_expand_inclusively() {
+        assert_eq!(parse_range_str("-2.5"), Ok(vec![-2.5]));
+        assert_eq!(parse_range_str("15:45:15"), Ok(vec![15.0, 30.0, 45.0]));
+        assert_eq!(parse_range_str("0:1:0.1").unwrap().len(), 11);
+        assert!(validate_range("1:2").is_err());
+        assert!(validate_range("1:2:0").is_err());
+        assert!(validate_range("5:1:1").is_err());
+        assert!(validate_range("0:1e9:1").is_err());
+        assert!(validate_range("0:1e300:1e-300").is_err());
+        assert!(validate_range("-1e308:1e308:1").is_err());
+    }
+
+    #[test]
+    fn colors_must_be_hex() {
+        assert!(validate_color("#00ff7f").is_ok());
+        assert!(validate_color("green").is_err());
+    }
+
+    #[test]
+    fn floats_must_be_finite() {
+        assert!(validate_f64("-2.5").is_ok());
+        assert!(validate_f64("inf").is_err());
+        assert!(validate_f64("NaN").is_err());
+        assert!(validate_f64("1.2.3").is_err());
+        assert!(validate_non_negative_f64("0").is_ok());
+        assert!(validate_non_negative_f64("-0.1").is_err());
+        assert!(validate_non_negative_f64("inf").is_err());
+        assert!(validate_positive_f64("0.01").is_ok());
+        assert!(validate_positive_f64("0").is_err());
+    }
+
+    #[test]
+    fn bad_canvas_sizes_are_rejected_by_clap() {
+        assert!(try_matches(&["test", "-w", "abc", "arch"]).is_err());
+        assert!(try_matches(&["test", "-h", "0", "arch"]).is_err());
+        assert!(try_matches(&["test", "-w", "20", "arch"]).is_ok());
+    }
+
+    #[test]
+    fn usage_errors_share_an_exit_code_with_invalid_arguments() {
+        let exit_code = |args: &[&str]| {
+            let err = cli()
+                .version("0.1.0")
+                .try_get_matches_from(args)
+                .unwrap_err();
+            Error::from(err).exit_code()
+        };
+        assert_eq!(
+            exit_code(&["test", "-w", "abc", "arch"]),
+            crate::error::EXIT_USAGE
+        );
+        assert_eq!(
+            exit_code(&["test", "--nope", "arch"]),
+            crate::error::EXIT_USAGE
+        );
+        assert_eq!(
+            exit_code(&["test", "arch", "--dt", "x"]),
+            crate::error::EXIT_USAGE
+        );
+        assert_eq!(exit_code(&["test", "--help"]), 0);
+        assert_eq!(exit_code(&["test", "--version"]), 0);
+    }
+
+    fn run_args(args: &[&str]) -> Result<()> {
+        run(cli().get_matches_from(args))
+    }
+
+    #[test]
+    fn frames_become_sequences_or_animations() {
+        let dir = temp_path("frames");
+        let sequence = dir.join("frame_{frame}.png");
+        let gif = dir.join("arch.gif");
+        let jpg = dir.join("arch.jpg");
+        let base = ["test", "-w", "40", "-h", "40", "--frames", "3", "-o"];
+
+        run_args(&[&base[..], &[sequence.to_str().unwrap(), "arch"]].concat()).unwrap();
+        let names: Vec<_> = (1..=3)
+            .map(|i| dir.join(format!("frame_{:04}.png", i)))
+            .collect();
+        assert!(names.iter().all(|p| p.exists()));
+        assert!(!dir.join("frame_0004.png").exists());
+
+        run_args(&[&base[..], &[gif.to_str().unwrap(), "arch"]].concat()).unwrap();
+        assert!(fs::metadata(&gif).unwrap().len() > 0);
+
+        let err = run_args(&[&base[..], &[jpg.to_str().unwrap(), "arch"]].concat()).unwrap_err();
+        assert_eq!(err.exit_code(), crate::error::EXIT_USAGE);
+        assert!(!jpg.exists());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn unknown_extensions_are_kept_in_the_name() {
+        assert_eq!(
+            append_extension(PathBuf::from("renders/my.render"), "ppm"),
+            PathBuf::from("renders/my.render.ppm")
+        );
+        assert_eq!(
+            append_extension(PathBuf::from("out"), "png"),
+            PathBuf::from("out.png")
+        );
+
+        let dir = temp_path("names");
+        let output = dir.join("v1.2_{frame}");
+        let base = ["test", "-w", "20", "-h", "20", "--frames", "2", "-e", "png"];
+        run_args(&[&base[..], &["-o", output.to_str().unwrap(), "clock"]].concat()).unwrap();
+        assert!(dir.join("v1.2_0001.png").exists());
+        assert!(dir.join("v1.2_0002.png").exists());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn ext_must_agree_with_the_output_extension() {
+        let dir = temp_path("ext");
+        let (jpg, jpeg) = (dir.join("arch.jpg"), dir.join("arch.jpeg"));
+        let base = ["test", "-w", "20", "-h", "20"];
+
+        let args = [
+            &base[..],
+            &["-e", "png", "-o", jpg.to_str().unwrap(), "arch"],
+        ]
+        .concat();
+        let err = run_args(&args).unwrap_err();
+        assert_eq!(err.exit_code(), crate::error::EXIT_USAGE);
+        assert!(err.to_string().contains("arch.jpg"));
+        assert!(!jpg.exists());
+
+        // different spellings of the same format agree
+        let args = [
+            &base[..],
+            &["-e", "jpg", "-o", jpeg.to_str().unwrap(), "arch"],
+        ]
+        .concat();
+        run_args(&args).unwrap();
+        assert_eq!(image::open(&jpeg).unwrap().width(), 20);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn renders_are_denoised_with_their_own_guides() {
+        let dir = temp_path("denoise");
+        let output = dir.join("sphere.exr");
+        let args = ["test", "-w", "24", "-h", "24", "--denoise", "atrous", "-o"];
+        run_args(&[&args[..], &[output.to_str().unwrap(), "sphere"]].concat()).unwrap();
+        let denoised = image::open(&output).unwrap();
+        assert_eq!((denoised.width(), denoised.height()), (24, 24));
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn data_files_follow_the_output_rules() {
+        let dir = temp_path("data");
+        let image = dir.join("arch.png");
+        let data = dir.join("{scene}_{width}.json");
+        let args = [
+            "test",
+            "-w",
+            "40",
+            "-h",
+            "40",
+            "--no-clobber",
+            "-o",
+            image.to_str().unwrap(),
+            "arch",
+            "--data-out",
+            data.to_str().unwrap(),
+        ];
+
+        run_args(&args).unwrap();
+        assert!(image.exists());
+        assert!(dir.join("arch_40.json").exists());
+
+        // an existing data file stops the run before anything is written
+        fs::remove_file(&image).unwrap();
+        let err = run_args(&args).unwrap_err();
+        assert!(matches!(err, Error::OutputExists(path) if path == dir.join("arch_40.json")));
+        assert!(!image.exists());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn aovs_are_saved_beside_the_output() {
+        assert_eq!(
+            aov_path(Path::new("renders/my.render.exr"), "uv"),
+            PathBuf::from("renders/my.render.uv.exr")
+        );
+        let dir = temp_path("aovs");
+        let output = dir.join("sphere.png");
+        let mut args = vec![
+            "test",
+            "-w",
+            "25",
+            "-h",
+            "25",
+            "-o",
+            output.to_str().unwrap(),
+        ];
+        for name in ["normal", "depth", "shadow", "object-id"] {
+            args.extend(["--aov", name]);
+        }
+        run_args(&[&args[..], &["sphere"]].concat()).unwrap();
+        let open = |name: &str| image::open(dir.join(name)).unwrap().into_rgb8();
+        // the middle of the sphere faces straight back, mapped from -1..1
+        assert_eq!(open("sphere.normal.png").get_pixel(12, 12).0, [128, 128, 0]);
+        // depths are scaled so the farthest hit is white
+        let depth = open("sphere.depth.png");
+        let nearest = depth.get_pixel(12, 12).0[0];
+        assert!(nearest > 0 && nearest < 255, "{}", nearest);
+        assert_eq!(depth.pixels().map(|p| p.0[0]).max(), Some(255));
+        // the light is up and to the left, so the lower right of the sphere faces away from it
+        let shadow = open("sphere.shadow.png");
+        assert_eq!(shadow.get_pixel(12, 12).0, [0, 0, 0]);
+        assert!(shadow.pixels().any(|p| p.0 == [255, 255, 255]));
+        assert_eq!(open("sphere.object-id.png").get_pixel(12, 12).0, [255; 3]);
+        assert!(!dir.join("sphere.uv.png").exists());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn floating_point_aovs_keep_their_values() {
+        let dir = temp_path("aovs-exr");
+        let output = dir.join("sphere_{frame}.exr");
+        let args = [
+            "test", "-w", "25", "-h", "25", "--frames", "2", "--aov", "depth",
+        ];
+        run_args(&[&args[..], &["-o", output.to_str().unwrap(), "sphere"]].concat()).unwrap();
+        for frame in ["0001", "0002"] {
+            let name = format!("sphere_{}.depth.exr", frame);
+            let depth = image::open(dir.join(name)).unwrap().into_rgb32f();
+            // the sphere's surface is 4 units in front of the origin
+            assert!((depth.get_pixel(12, 12).0[0] - 4.0).abs() < 0.01);
+        }
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn aovs_must_be_rendered_and_named() {
+        let dir = temp_path("aovs-missing");
+        let output = dir.join("clock.png");
+        let args = ["test", "-w", "20", "-h", "20", "--aov", "depth", "-o"];
+        let err = run_args(&[&args[..], &[output.to_str().unwrap(), "clock"]].concat());
+        assert_eq!(err.unwrap_err().exit_code(), crate::error::EXIT_USAGE);
+        assert!(!dir.exists());
+
+        let args = [
+            "test", "-w", "20", "-h", "20", "--aov", "uv", "-o", "-", "sphere",
+        ];
+        let err = run_args(&args).unwrap_err();
+        assert_eq!(err.exit_code(), crate::error::EXIT_USAGE);
+        assert!(try_matches(&["test", "--aov", "beauty", "sphere"]).is_err());
+    }
+
+    #[test]
+    fn parse_arg_names_the_bad_argument() {
+        let matches = Command::new("test")
+            .arg(Arg::new(WIDTH).long(WIDTH).takes_value(true))
+            .get_matches_from(["test", "--width", "wide"]);
+        let err = parse_arg::<u32>(&matches, WIDTH).unwrap_err();
+        assert_eq!(err.exit_code(), crate::error::EXIT_USAGE);
+        assert!(err.to_string().contains("--width"));
+    }
 }
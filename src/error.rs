@@ -0,0 +1,141 @@
+use image::ImageError;
+use ray_tracer::denoise::GuideMismatch;
+use ray_tracer::stereo::EyeMismatch;
+use std::fmt::{Display, Formatter};
+use std::io;
+use std::path::PathBuf;
+
+///
+/// Exit codes, following the BSD sysexits.h conventions
+///
+pub const EXIT_USAGE: i32 = 64;
+pub const EXIT_SOFTWARE: i32 = 70;
+pub const EXIT_CANT_CREATE: i32 = 73;
+pub const EXIT_IO: i32 = 74;
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+#[derive(Debug)]
+pub enum Error {
+    /// The command line couldn't be parsed, or it asked for the help or version
+    Usage(clap::Error),
+    /// An argument that clap accepted but that can't be used as given
+    InvalidArgument {
+        name: String,
+        value: String,
+        reason: String,
+    },
+    /// --no-clobber was given and the output already exists
+    OutputExists(PathBuf),
+    /// Creating or writing the output file failed
+    Write {
+        path: PathBuf,
+        source: ImageError,
+    },
+    Io(io::Error),
+    Image(ImageError),
+    /// A program produced guide layers that don't match its image
+    Denoise(GuideMismatch),
+    /// A program rendered left and right eyes that can't be packed together
+    Stereo(EyeMismatch),
+}
+
+impl Error {
+    pub fn invalid_argument(name: &str, value: &str, reason: &str) -> Error {
+        Error::InvalidArgument {
+            name: name.to_string(),
+            value: value.to_string(),
+            reason: reason.to_string(),
+        }
+    }
+
+    ///
+    /// The process exit code to report this error with
+    ///
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            // help and version are printed to stdout and aren't failures
+            Error::Usage(e) if !e.use_stderr() => 0,
+            Error::Usage(_) => EXIT_USAGE,
+            Error::InvalidArgument { .. } => EXIT_USAGE,
+            Error::OutputExists(_) => EXIT_CANT_CREATE,
+            Error::Write {
+                source: ImageError::IoError(_),
+                ..
+            } => EXIT_IO,
+            Error::Write { .. } => EXIT_SOFTWARE,
+            Error::Io(_) => EXIT_IO,
+            Error::Image(ImageError::IoError(_)) => EXIT_IO,
+            Error::Image(_) => EXIT_SOFTWARE,
+            Error::Denoise(_) => EXIT_SOFTWARE,
+            Error::Stereo(_) => EXIT_SOFTWARE,
+        }
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Usage(e) => write!(f, "{}", e),
+            Error::InvalidArgument {
+                name,
+                value,
+                reason,
+            } => write!(f, "invalid value '{}' for --{}: {}", value, name, reason),
+            Error::OutputExists(path) => {
+                write!(f, "refusing to overwrite '{}'", path.display())
+            }
+            Error::Write { path, source } => {
+                write!(f, "could not write '{}': {}", path.display(), source)
+            }
+            Error::Io(e) => write!(f, "{}", e),
+            Error::Image(e) => write!(f, "{}", e),
+            Error::Denoise(e) => write!(f, "could not denoise: {}", e),
+            Error::Stereo(e) => write!(f, "could not compose the eyes: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Usage(e) => Some(e),
+            Error::Write { source, .. } => Some(source),
+            Error::Io(e) => Some(e),
+            Error::Image(e) => Some(e),
+            Error::Denoise(e) => Some(e),
+            Error::Stereo(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<clap::Error> for Error {
+    fn from(e: clap::Error) -> Self {
+        Error::Usage(e)
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+impl From<GuideMismatch> for Error {
+    fn from(e: GuideMismatch) -> Self {
+        Error::Denoise(e)
+    }
+}
+
+impl From<EyeMismatch> for Error {
+    fn from(e: EyeMismatch) -> Self {
+        Error::Stereo(e)
+    }
+}
+
+impl From<ImageError> for Error {
+    fn from(e: ImageError) -> Self {
+        Error::Image(e)
+    }
+}
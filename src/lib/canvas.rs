@@ -1,21 +1,99 @@
-use image::{ImageBuffer, Rgb};
+use image::{DynamicImage, ImageBuffer, Rgb};
+
+///
+/// A floating-point canvas, for values that must not be clipped to 0..1 before output
+///
+pub type HdrImage = ImageBuffer<Rgb<f32>, Vec<f32>>;
+
+///
+/// The names of the auxiliary buffers, in the order they're listed to users
+///
+pub const AOVS: [&str; 7] = [
+    "albedo",
+    "normal",
+    "depth",
+    "shadow",
+    "object-id",
+    "material-id",
+    "uv",
+];
+
+///
+/// A rendered image along with the auxiliary buffers (AOVs) a renderer can fill in beside it:
+/// the unlit surface color, the surface normal, and the distance along the ray in every channel.
+/// Each buffer has the dimensions of `color`
+///
+#[derive(Clone, Debug, PartialEq)]
+pub struct Layers {
+    pub color: HdrImage,
+    pub albedo: Option<HdrImage>,
+    pub normal: Option<HdrImage>,
+    pub depth: Option<HdrImage>,
+    /// 1 in every channel where the light is blocked, 0 where it gets through
+    pub shadow: Option<HdrImage>,
+    /// Which object each pixel sees, counting from 1 with 0 for none, in every channel
+    pub object_id: Option<HdrImage>,
+    /// Which material each pixel sees, numbered like `object_id`
+    pub material_id: Option<HdrImage>,
+    /// The texture coordinates of the surface in red and green
+    pub uv: Option<HdrImage>,
+}
+
+///
+/// Creates Layers holding only a color image
+///
+pub fn layers(color: HdrImage) -> Layers {
+    Layers {
+        color,
+        albedo: None,
+        normal: None,
+        depth: None,
+        shadow: None,
+        object_id: None,
+        material_id: None,
+        uv: None,
+    }
+}
+
+///
+/// The auxiliary buffer named by one of `AOVS`, if it was rendered
+///
+pub fn aov<'a>(layers: &'a Layers, name: &str) -> Option<&'a HdrImage> {
+    match name {
+        "albedo" => layers.albedo.as_ref(),
+        "normal" => layers.normal.as_ref(),
+        "depth" => layers.depth.as_ref(),
+        "shadow" => layers.shadow.as_ref(),
+        "object-id" => layers.object_id.as_ref(),
+        "material-id" => layers.material_id.as_ref(),
+        "uv" => layers.uv.as_ref(),
+        _ => None,
+    }
+}
 
 pub fn new_image_buffer(w: u32, h: u32) -> ImageBuffer<Rgb<u8>, Vec<u8>> {
     ImageBuffer::new(w, h)
 }
 
 ///
-/// Determines if the point (x, y) resides in the rectangle from point (0..width, 0..height)
+/// Widens an 8-bit canvas to floating point, mapping 0..255 onto 0..1
 ///
-pub fn point_is_in_rectangle(x: i32, y: i32, width: i32, height: i32) -> bool {
-    x >= 0 && y >= 0 && x < width && y < height
+pub fn hdr_from_rgb8(image: ImageBuffer<Rgb<u8>, Vec<u8>>) -> HdrImage {
+    DynamicImage::ImageRgb8(image).into_rgb32f()
 }
 
 ///
-/// image.rs library uses top-down coords, so we switch them to bottom-up here
+/// Quantizes a floating-point canvas to 8 bits, clipping values outside of 0..1
 ///
-pub fn convert_to_bottom_up_coordinates(x: i32, y: i32, _w: i32, h: i32) -> (i32, i32) {
-    (x, h - y)
+pub fn quantize(image: &HdrImage) -> ImageBuffer<Rgb<u8>, Vec<u8>> {
+    DynamicImage::ImageRgb32F(image.clone()).into_rgb8()
+}
+
+///
+/// Determines if the point (x, y) resides in the rectangle from point (0..width, 0..height)
+///
+pub fn point_is_in_rectangle(x: i32, y: i32, width: i32, height: i32) -> bool {
+    x >= 0 && y >= 0 && x < width && y < height
 }
 
 ///
@@ -23,8 +101,8 @@ pub fn convert_to_bottom_up_coordinates(x: i32, y: i32, _w: i32, h: i32) -> (i32
 ///
 pub fn neighbors(x: i32, y: i32, w: i32, h: i32, n: i32) -> Vec<(i32, i32)> {
     let mut v = vec![];
-    for i in -n..n {
-        for j in -n..n {
+    for i in -n..=n {
+        for j in -n..=n {
             let (mx, my) = (x + i, y + j);
             if point_is_in_rectangle(mx, my, w, h) {
                 v.push((mx, my))
@@ -33,3 +111,54 @@ pub fn neighbors(x: i32, y: i32, w: i32, h: i32, n: i32) -> Vec<(i32, i32)> {
     }
     v
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn neighbors_are_a_square_around_the_point() {
+        let square = neighbors(5, 5, 10, 10, 2);
+        assert_eq!(square.len(), 25);
+        assert!(square.contains(&(7, 7)) && square.contains(&(3, 3)));
+    }
+
+    #[test]
+    fn neighbors_are_clipped_to_the_canvas() {
+        assert_eq!(neighbors(0, 0, 10, 10, 1).len(), 4);
+    }
+
+    #[test]
+    fn quantizing_clips_and_rounds() {
+        let hdr = ImageBuffer::from_pixel(1, 1, Rgb([4.0, 0.5, -1.0]));
+        assert_eq!(quantize(&hdr).get_pixel(0, 0).0, [255, 128, 0]);
+    }
+
+    #[test]
+    fn every_aov_can_be_looked_up() {
+        let pass = |v: f32| Some(ImageBuffer::from_pixel(1, 1, Rgb([v; 3])));
+        let rendered = Layers {
+            albedo: pass(1.0),
+            normal: pass(2.0),
+            depth: pass(3.0),
+            shadow: pass(4.0),
+            object_id: pass(5.0),
+            material_id: pass(6.0),
+            uv: pass(7.0),
+            ..layers(ImageBuffer::new(1, 1))
+        };
+        for (i, name) in AOVS.iter().enumerate() {
+            let found = aov(&rendered, name).unwrap_or_else(|| panic!("no {} pass", name));
+            assert_eq!(found.get_pixel(0, 0).0[0], i as f32 + 1.0);
+        }
+        let color_only = layers(ImageBuffer::new(1, 1));
+        assert!(AOVS.iter().all(|name| aov(&color_only, name).is_none()));
+        assert!(aov(&rendered, "color").is_none());
+    }
+
+    #[test]
+    fn widening_an_8_bit_canvas_round_trips() {
+        let canvas = ImageBuffer::from_fn(3, 2, |x, y| Rgb([x as u8 * 100, y as u8 * 255, 7]));
+        assert_eq!(quantize(&hdr_from_rgb8(canvas.clone())), canvas);
+    }
+}
@@ -0,0 +1,413 @@
+//!
+//! Charts on top of the canvas: a viewport that maps world coordinates (y pointing up) into a
+//! rectangle of the canvas, and the gridlines, axes, ticks and labels that give it a scale.
+//!
+
+use crate::draw::line;
+use crate::font::{draw_text, text_size, GLYPH_HEIGHT};
+use image::{ImageBuffer, Rgb, Rgba};
+
+type Canvas = ImageBuffer<Rgb<u8>, Vec<u8>>;
+
+///
+/// The room left around the plot area for tick labels and axis titles, in pixels
+///
+pub const MARGIN_LEFT: f64 = 44.0;
+pub const MARGIN_RIGHT: f64 = 12.0;
+pub const MARGIN_TOP: f64 = 14.0;
+pub const MARGIN_BOTTOM: f64 = 30.0;
+
+///
+/// The length of a tick mark outside the plot area
+///
+const TICK: i32 = 4;
+
+///
+/// Roughly how many pixels apart ticks are placed, before rounding to a nice step
+///
+const TICK_SPACING_X: f64 = 60.0;
+const TICK_SPACING_Y: f64 = 40.0;
+
+///
+/// A rectangle of the canvas, in pixels from the top left
+///
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Rect {
+    pub left: f64,
+    pub top: f64,
+    pub width: f64,
+    pub height: f64,
+}
+
+///
+/// The world ranges shown in an area of the canvas. x grows to the right and y upwards
+///
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Viewport {
+    pub x: (f64, f64),
+    pub y: (f64, f64),
+    pub area: Rect,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Axes {
+    pub x_label: String,
+    pub y_label: String,
+    pub color: Rgba<u8>,
+}
+
+///
+/// Creates a Rect
+///
+pub fn rect(left: f64, top: f64, width: f64, height: f64) -> Rect {
+    Rect {
+        left,
+        top,
+        width,
+        height,
+    }
+}
+
+///
+/// Creates a Viewport showing x.0..x.1 and y.0..y.1 in the area
+///
+pub fn viewport(x: (f64, f64), y: (f64, f64), area: Rect) -> Viewport {
+    Viewport { x, y, area }
+}
+
+///
+/// Creates axes titled with the given labels
+///
+pub fn axes(x_label: &str, y_label: &str, color: Rgba<u8>) -> Axes {
+    Axes {
+        x_label: x_label.to_string(),
+        y_label: y_label.to_string(),
+        color,
+    }
+}
+
+///
+/// The part of a canvas left for plotting once the margins for the axes are taken off
+///
+pub fn plot_area(width: u32, height: u32) -> Rect {
+    rect(
+        MARGIN_LEFT,
+        MARGIN_TOP,
+        (width as f64 - MARGIN_LEFT - MARGIN_RIGHT).max(1.0),
+        (height as f64 - MARGIN_TOP - MARGIN_BOTTOM).max(1.0),
+    )
+}
+
+///
+/// A viewport with its origin at the bottom left of the area and `scale` pixels per unit
+///
+pub fn scaled_viewport(area: Rect, scale: f64) -> Viewport {
+    viewport((0.0, area.width / scale), (0.0, area.height / scale), area)
+}
+
+///
+/// The viewport that shows every point as large as fits in the area with `margin` pixels to
+/// spare on each side. x and y share one scale so shapes aren't stretched, and the points are
+/// centered along the axis with room left over. None if there are no finite points
+///
+pub fn fit_viewport(points: &[(f64, f64)], area: Rect, margin: f64) -> Option<Viewport> {
+    let mut finite = points.iter().filter(|p| p.0.is_finite() && p.1.is_finite());
+    let first = *finite.next()?;
+    let (min, max) = finite.fold((first, first), |(min, max), p| {
+        (
+            (min.0.min(p.0), min.1.min(p.1)),
+            (max.0.max(p.0), max.1.max(p.1)),
+        )
+    });
+    let inner = (
+        (area.width - 2.0 * margin).max(1.0),
+        (area.height - 2.0 * margin).max(1.0),
+    );
+    let span = (max.0 - min.0, max.1 - min.1);
+    // a single point, or a flight straight up or sideways, only constrains one axis
+    let scale = [(inner.0, span.0), (inner.1, span.1)]
+        .iter()
+        .filter(|(_, span)| *span > 0.0)
+        .map(|(inner, span)| inner / span)
+        .fold(f64::INFINITY, f64::min);
+    let scale = if scale.is_finite() { scale } else { 1.0 };
+    let center = ((min.0 + max.0) / 2.0, (min.1 + max.1) / 2.0);
+    let half = (area.width / scale / 2.0, area.height / scale / 2.0);
+    Some(viewport(
+        (center.0 - half.0, center.0 + half.0),
+        (center.1 - half.1, center.1 + half.1),
+        area,
+    ))
+}
+
+impl Viewport {
+    ///
+    /// Maps a world point onto the canvas, where y points down
+    ///
+    pub fn to_canvas(&self, x: f64, y: f64) -> (f64, f64) {
+        let a = self.area;
+        (
+            a.left + (x - self.x.0) / (self.x.1 - self.x.0) * a.width,
+            a.top + (self.y.1 - y) / (self.y.1 - self.y.0) * a.height,
+        )
+    }
+}
+
+///
+/// A step of 1, 2 or 5 times a power of ten that splits `span` into about `target` parts
+///
+pub fn nice_step(span: f64, target: usize) -> f64 {
+    let raw = span.abs() / target.max(1) as f64;
+    if raw <= 0.0 || !raw.is_finite() {
+        return 1.0;
+    }
+    let magnitude = 10.0_f64.powf(raw.log10().floor());
+    let nice = match raw / magnitude {
+        f if f <= 1.0 => 1.0,
+        f if f <= 2.0 => 2.0,
+        f if f <= 5.0 => 5.0,
+        _ => 10.0,
+    };
+    nice * magnitude
+}
+
+///
+/// Every multiple of `step` from `min` to `max`, inclusive
+///
+pub fn ticks(min: f64, max: f64, step: f64) -> Vec<f64> {
+    if !(max >= min && step > 0.0 && min.is_finite() && max.is_finite()) {
+        return vec![];
+    }
+    // a little slack so ends that are multiples of the step aren't lost to rounding
+    let first = (min / step - 1.0e-9).ceil() as i64;
+    let last = (max / step + 1.0e-9).floor() as i64;
+    (first..=last).map(|i| i as f64 * step).collect()
+}
+
+///
+/// Formats a tick with as many decimals as its step needs, so 0.1 steps don't print as 0.1000001
+///
+pub fn format_tick(value: f64, step: f64) -> String {
+    let decimals = (-step.log10().floor()).max(0.0) as usize;
+    let value = if value.abs() < step * 1.0e-9 {
+        0.0
+    } else {
+        value
+    };
+    format!("{:.*}", decimals, value)
+}
+
+fn x_ticks(view: &Viewport) -> (Vec<f64>, f64) {
+    let target = (view.area.width / TICK_SPACING_X).round().max(2.0) as usize;
+    let step = nice_step(view.x.1 - view.x.0, target);
+    (ticks(view.x.0, view.x.1, step), step)
+}
+
+fn y_ticks(view: &Viewport) -> (Vec<f64>, f64) {
+    let target = (view.area.height / TICK_SPACING_Y).round().max(2.0) as usize;
+    let step = nice_step(view.y.1 - view.y.0, target);
+    (ticks(view.y.0, view.y.1, step), step)
+}
+
+// the pixel rows and columns just inside the area
+fn edges(area: &Rect) -> (i32, i32, i32, i32) {
+    let (left, top) = (area.left.round() as i32, area.top.round() as i32);
+    let right = (area.left + area.width).round() as i32 - 1;
+    let bottom = (area.top + area.height).round() as i32 - 1;
+    (left, top, right, bottom)
+}
+
+///
+/// Draws a gridline across the plot area at every tick. Draw it before the data so the data
+/// stays on top
+///
+pub fn draw_grid(canvas: &mut Canvas, view: &Viewport, color: Rgba<u8>) {
+    let (left, top, right, bottom) = edges(&view.area);
+    for x in x_ticks(view).0 {
+        let cx = view.to_canvas(x, 0.0).0.round() as i32;
+        line(canvas, (cx, top), (cx, bottom), color);
+    }
+    for y in y_ticks(view).0 {
+        let cy = view.to_canvas(0.0, y).1.round() as i32;
+        line(canvas, (left, cy), (right, cy), color);
+    }
+}
+
+///
+/// Paints everything outside the plot area, so data that runs past it doesn't cover the
+/// axes and labels drawn in the margins afterwards
+///
+pub fn clear_outside(canvas: &mut Canvas, area: &Rect, background: Rgb<u8>) {
+    let (left, top, right, bottom) = edges(area);
+    for (x, y, pixel) in canvas.enumerate_pixels_mut() {
+        let (x, y) = (x as i32, y as i32);
+        if x < left || x > right || y < top || y > bottom {
+            *pixel = background;
+        }
+    }
+}
+
+///
+/// Draws the x axis along the bottom of the plot area and the y axis along its left, with a
+/// labelled tick at every gridline and the axis titles
+///
+pub fn draw_axes(canvas: &mut Canvas, view: &Viewport, axes: &Axes) {
+    let (left, top, right, bottom) = edges(&view.area);
+    let (x_axis, y_axis) = (bottom + 1, left - 1);
+    let color = axes.color;
+    line(canvas, (y_axis, top), (y_axis, x_axis), color);
+    line(canvas, (y_axis, x_axis), (right, x_axis), color);
+
+    let label_top = x_axis + TICK + 3;
+    let (xs, step) = x_ticks(view);
+    for x in xs {
+        let cx = view.to_canvas(x, 0.0).0.round() as i32;
+        line(canvas, (cx, x_axis), (cx, x_axis + TICK), color);
+        let label = format_tick(x, step);
+        let (w, _) = text_size(&label, 1);
+        draw_text(canvas, (cx - w as i32 / 2, label_top), &label, 1, color);
+    }
+
+    let (ys, step) = y_ticks(view);
+    for y in ys {
+        let cy = view.to_canvas(0.0, y).1.round() as i32;
+        line(canvas, (y_axis - TICK, cy), (y_axis, cy), color);
+        let label = format_tick(y, step);
+        let (w, h) = text_size(&label, 1);
+        let origin = (y_axis - TICK - 3 - w as i32, cy - h as i32 / 2);
+        draw_text(canvas, origin, &label, 1, color);
+    }
+
+    // the x title sits centered under the tick labels, the y title over the top of its axis
+    let (w, _) = text_size(&axes.x_label, 1);
+    let center = (left + right) / 2;
+    let title_top = label_top + GLYPH_HEIGHT as i32 + 4;
+    draw_text(
+        canvas,
+        (center - w as i32 / 2, title_top),
+        &axes.x_label,
+        1,
+        color,
+    );
+    let (w, h) = text_size(&axes.y_label, 1);
+    let origin = (y_axis - w as i32 / 2, top - h as i32 - 4);
+    draw_text(canvas, origin, &axes.y_label, 1, color);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::canvas::new_image_buffer;
+    use crate::tuple::eq_f64;
+
+    const WHITE: Rgba<u8> = Rgba([255, 255, 255, 255]);
+
+    #[test]
+    fn viewports_flip_y_and_scale_into_their_area() {
+        let view = viewport((0.0, 10.0), (-5.0, 5.0), rect(20.0, 10.0, 100.0, 50.0));
+        assert_eq!(view.to_canvas(0.0, 5.0), (20.0, 10.0));
+        assert_eq!(view.to_canvas(10.0, -5.0), (120.0, 60.0));
+        assert_eq!(view.to_canvas(5.0, 0.0), (70.0, 35.0));
+    }
+
+    #[test]
+    fn scaled_viewports_start_at_the_origin() {
+        let view = scaled_viewport(rect(0.0, 0.0, 100.0, 50.0), 2.0);
+        assert_eq!(view.to_canvas(0.0, 0.0), (0.0, 50.0));
+        assert_eq!(view.to_canvas(10.0, 10.0), (20.0, 30.0));
+    }
+
+    #[test]
+    fn fitting_keeps_the_aspect_ratio_and_the_margin() {
+        let area = rect(0.0, 0.0, 200.0, 100.0);
+        let points = [(-10.0, 0.0), (10.0, 10.0), (0.0, 5.0)];
+        let view = fit_viewport(&points, area, 10.0).unwrap();
+        // the height limits the scale to 8 pixels per unit, and x is centered
+        assert_eq!(view.to_canvas(-10.0, 0.0), (20.0, 90.0));
+        assert_eq!(view.to_canvas(10.0, 10.0), (180.0, 10.0));
+    }
+
+    #[test]
+    fn fitting_a_line_or_a_point_only_scales_what_it_can() {
+        let area = rect(0.0, 0.0, 100.0, 100.0);
+        let view = fit_viewport(&[(0.0, 0.0), (0.0, 40.0)], area, 10.0).unwrap();
+        assert_eq!(view.to_canvas(0.0, 40.0), (50.0, 10.0));
+        let view = fit_viewport(&[(3.0, 3.0)], area, 10.0).unwrap();
+        assert_eq!(view.to_canvas(3.0, 3.0), (50.0, 50.0));
+        assert_eq!(view.to_canvas(4.0, 3.0), (51.0, 50.0));
+        assert!(fit_viewport(&[(f64::NAN, 0.0)], area, 10.0).is_none());
+    }
+
+    #[test]
+    fn steps_are_one_two_or_five_times_a_power_of_ten() {
+        assert!(eq_f64(nice_step(100.0, 10), 10.0));
+        assert!(eq_f64(nice_step(100.0, 6), 20.0));
+        assert!(eq_f64(nice_step(1.0, 4), 0.5));
+        assert!(eq_f64(nice_step(0.03, 3), 0.01));
+        assert!(eq_f64(nice_step(0.0, 3), 1.0));
+    }
+
+    #[test]
+    fn ticks_are_the_multiples_in_range() {
+        assert_eq!(ticks(-3.0, 12.0, 5.0), vec![0.0, 5.0, 10.0]);
+        assert_eq!(ticks(0.0, 0.3, 0.1).len(), 4);
+        assert!(ticks(1.0, 0.0, 1.0).is_empty());
+        assert!(ticks(0.0, f64::INFINITY, 1.0).is_empty());
+    }
+
+    #[test]
+    fn tick_labels_use_the_step_precision() {
+        assert_eq!(format_tick(20.0, 10.0), "20");
+        assert_eq!(format_tick(0.30000000000000004, 0.1), "0.3");
+        assert_eq!(format_tick(0.25, 0.05), "0.25");
+        assert_eq!(format_tick(-1.0e-17, 0.1), "0.0");
+    }
+
+    #[test]
+    fn plot_area_leaves_the_margins() {
+        let area = plot_area(200, 100);
+        assert_eq!(area, rect(MARGIN_LEFT, MARGIN_TOP, 144.0, 56.0));
+        assert!(plot_area(10, 10).width > 0.0);
+    }
+
+    #[test]
+    fn axes_frame_the_area_and_label_their_ticks() {
+        let mut canvas = new_image_buffer(200, 150);
+        let area = plot_area(200, 150);
+        let view = viewport((0.0, 100.0), (0.0, 50.0), area);
+        draw_axes(&mut canvas, &view, &axes("x", "y", WHITE));
+        let white = Rgb([255, 255, 255]);
+        // the axis lines sit just outside the area's bottom left corner
+        let (x_axis, y_axis) = ((area.top + area.height) as u32, area.left as u32 - 1);
+        assert_eq!(*canvas.get_pixel(y_axis, 20), white);
+        assert_eq!(*canvas.get_pixel(100, x_axis), white);
+        // nothing is drawn inside the area, and there are labels in the margins
+        assert_eq!(*canvas.get_pixel(100, 50), Rgb([0, 0, 0]));
+        let labels = (0..y_axis - TICK as u32)
+            .flat_map(|x| (0..150).map(move |y| (x, y)))
+            .filter(|(x, y)| *canvas.get_pixel(*x, *y) == white)
+            .count();
+        assert!(labels > 20);
+    }
+
+    #[test]
+    fn grids_cross_at_the_ticks() {
+        let mut canvas = new_image_buffer(100, 100);
+        let view = viewport((0.0, 100.0), (0.0, 100.0), rect(0.0, 0.0, 100.0, 100.0));
+        draw_grid(&mut canvas, &view, WHITE);
+        // two ticks per side round to a step of 50
+        assert_eq!(canvas.get_pixel(50, 10).0, [255, 255, 255]);
+        assert_eq!(canvas.get_pixel(10, 50).0, [255, 255, 255]);
+        assert_eq!(canvas.get_pixel(10, 10).0, [0, 0, 0]);
+    }
+
+    #[test]
+    fn clearing_keeps_only_the_area() {
+        let mut canvas = ImageBuffer::from_pixel(10, 10, Rgb([9, 9, 9]));
+        clear_outside(&mut canvas, &rect(2.0, 3.0, 4.0, 5.0), Rgb([0, 0, 0]));
+        let kept = canvas.pixels().filter(|p| p.0 == [9, 9, 9]).count();
+        assert_eq!(kept, 20);
+        assert_eq!(canvas.get_pixel(2, 3).0, [9, 9, 9]);
+        assert_eq!(canvas.get_pixel(6, 3).0, [0, 0, 0]);
+    }
+}
@@ -8,6 +8,20 @@ pub const MIN_COLOR: f64 = 0.0;
 pub type Color = (f64, f64, f64);
 pub type Pixel = [u8; 3];
 
+///
+/// Distinct colors for telling several plotted series apart, starting with arch's green
+///
+pub const PALETTE: [Color; 8] = [
+    (0.1, 0.8, 0.2),
+    (0.9, 0.3, 0.2),
+    (0.2, 0.5, 0.9),
+    (0.95, 0.75, 0.1),
+    (0.7, 0.3, 0.85),
+    (0.1, 0.8, 0.8),
+    (0.95, 0.5, 0.7),
+    (0.6, 0.45, 0.25),
+];
+
 ///
 /// Creates a tuple representing a Color
 ///
@@ -43,6 +57,20 @@ pub fn multiply_by_color(left: Color, right: Color) -> Color {
     (left.0 * right.0, left.1 * right.1, left.2 * right.2)
 }
 
+///
+/// Parses a Color from a hex string like "#1a80ff" or "1a80ff"
+///
+pub fn color_from_hex(hex: &str) -> Option<Color> {
+    let hex = hex.strip_prefix('#').unwrap_or(hex);
+    if hex.len() != 6 || !hex.is_ascii() {
+        return None;
+    }
+    let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).ok();
+    let (r, g, b) = (channel(0)?, channel(2)?, channel(4)?);
+    let max = RBG_MAX_INT as f64;
+    Some(color(r as f64 / max, g as f64 / max, b as f64 / max))
+}
+
 ///
 /// Creates a Pixel from a Color
 ///
@@ -72,10 +100,25 @@ pub fn color_into_rgb(c: Color) -> Rgb<u8> {
     pixel_into_rgb(color_into_pixel(c))
 }
 
+///
+/// Creates a floating-point Rgb<f32> from a Color without clipping it, so that values above 1
+/// survive to HDR outputs. NaNs become 0, as nothing downstream can make sense of them
+///
+pub fn color_into_rgb32f(c: Color) -> Rgb<f32> {
+    Rgb([c.0, c.1, c.2].map(|v| if v.is_nan() { 0.0 } else { v as f32 }))
+}
+
+///
+/// The palette color for the i-th series, cycling once the palette runs out
+///
+pub fn palette_color(i: usize) -> Color {
+    PALETTE[i % PALETTE.len()]
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::lib::tuple::*;
+    use crate::tuple::*;
 
     #[test]
     fn make_color() {
@@ -140,13 +183,24 @@ mod tests {
         assert!(eq_f64(3.4, c3.2));
     }
 
+    #[test]
+    fn parse_hex_colors() {
+        let c = color_from_hex("#ff8000").unwrap();
+        assert!(eq_f64(1.0, c.0));
+        assert!(eq_f64(128.0 / 255.0, c.1));
+        assert!(eq_f64(0.0, c.2));
+        assert!(color_from_hex("ff8000").is_some());
+        assert!(color_from_hex("#ff80").is_none());
+        assert!(color_from_hex("#gg8000").is_none());
+    }
+
     #[test]
     fn test_color_into_pixel_one() {
         let (r, g, b) = (0.0, 0.0, 0.0);
         let c = color(r, g, b);
         let p = color_into_pixel(c);
 
-        assert_eq!(*p.get(0).unwrap(), 0);
+        assert_eq!(*p.first().unwrap(), 0);
         assert_eq!(*p.get(1).unwrap(), 0);
         assert_eq!(*p.get(2).unwrap(), 0);
     }
@@ -157,7 +211,7 @@ mod tests {
         let c = color(r, g, b);
         let p = color_into_pixel(c);
 
-        assert_eq!(*p.get(0).unwrap(), 139);
+        assert_eq!(*p.first().unwrap(), 139);
         assert_eq!(*p.get(1).unwrap(), 51);
         assert_eq!(*p.get(2).unwrap(), 26);
     }
@@ -168,7 +222,7 @@ mod tests {
         let c = color(r, g, b);
         let p = color_into_rgb(c);
 
-        assert_eq!(*p.0.get(0).unwrap(), 0);
+        assert_eq!(*p.0.first().unwrap(), 0);
         assert_eq!(*p.0.get(1).unwrap(), 0);
         assert_eq!(*p.0.get(2).unwrap(), 0);
     }
@@ -179,8 +233,22 @@ mod tests {
         let c = color(r, g, b);
         let p = color_into_rgb(c);
 
-        assert_eq!(*p.0.get(0).unwrap(), 139);
+        assert_eq!(*p.0.first().unwrap(), 139);
         assert_eq!(*p.0.get(1).unwrap(), 51);
         assert_eq!(*p.0.get(2).unwrap(), 26);
     }
+
+    #[test]
+    fn color_into_rgb32f_keeps_values_above_one() {
+        let p = color_into_rgb32f(color(2.5, -0.25, f64::NAN));
+        assert_eq!(p.0, [2.5, -0.25, 0.0]);
+    }
+
+    #[test]
+    fn palette_cycles_through_distinct_colors() {
+        for (i, c) in PALETTE.iter().enumerate() {
+            assert!(PALETTE[i + 1..].iter().all(|other| other != c));
+        }
+        assert_eq!(palette_color(1), palette_color(1 + PALETTE.len()));
+    }
 }
@@ -0,0 +1,359 @@
+use crate::canvas::{HdrImage, Layers};
+use image::{ImageBuffer, Rgb};
+use std::fmt::{Display, Formatter};
+
+pub const DEFAULT_RADIUS: u32 = 3;
+pub const DEFAULT_SIGMA_SPATIAL: f32 = 2.0;
+pub const DEFAULT_SIGMA_COLOR: f32 = 0.1;
+pub const DEFAULT_ITERATIONS: u32 = 5;
+
+const NORMAL_SIGMA: f32 = 0.3;
+const DEPTH_SIGMA: f32 = 0.1;
+const ALBEDO_SIGMA: f32 = 0.1;
+const B3_SPLINE: [f32; 5] = [1.0 / 16.0, 1.0 / 4.0, 3.0 / 8.0, 1.0 / 4.0, 1.0 / 16.0];
+
+pub const DENOISERS: [&str; 3] = ["none", "bilateral", "atrous"];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Denoiser {
+    None,
+    Bilateral,
+    ATrous,
+}
+
+///
+/// Auxiliary buffers that steer the filters away from blurring across geometric edges.
+/// Each one must match the dimensions of the image being filtered
+///
+#[derive(Clone, Copy, Default)]
+pub struct Guides<'a> {
+    pub albedo: Option<&'a HdrImage>,
+    pub normal: Option<&'a HdrImage>,
+    pub depth: Option<&'a HdrImage>,
+}
+
+///
+/// A guide whose dimensions don't match the image being filtered
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct GuideMismatch {
+    pub guide: &'static str,
+    pub expected: (u32, u32),
+    pub found: (u32, u32),
+}
+
+impl Display for GuideMismatch {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "the {} guide is {}x{} but the image is {}x{}",
+            self.guide, self.found.0, self.found.1, self.expected.0, self.expected.1
+        )
+    }
+}
+
+impl std::error::Error for GuideMismatch {}
+
+///
+/// The guides a render's own albedo, normal and depth layers provide
+///
+pub fn guides(layers: &Layers) -> Guides<'_> {
+    Guides {
+        albedo: layers.albedo.as_ref(),
+        normal: layers.normal.as_ref(),
+        depth: layers.depth.as_ref(),
+    }
+}
+
+///
+/// Checks that every guide has the dimensions of the image
+///
+pub fn check_guides(image: &HdrImage, guides: Guides) -> Result<(), GuideMismatch> {
+    let named = [
+        ("albedo", guides.albedo),
+        ("normal", guides.normal),
+        ("depth", guides.depth),
+    ];
+    for (guide, buffer) in named {
+        if let Some(buffer) = buffer.filter(|b| b.dimensions() != image.dimensions()) {
+            return Err(GuideMismatch {
+                guide,
+                expected: image.dimensions(),
+                found: buffer.dimensions(),
+            });
+        }
+    }
+    Ok(())
+}
+
+///
+/// Parses one of `DENOISERS`
+///
+pub fn denoiser_from_name(name: &str) -> Option<Denoiser> {
+    match name {
+        "none" => Some(Denoiser::None),
+        "bilateral" => Some(Denoiser::Bilateral),
+        "atrous" => Some(Denoiser::ATrous),
+        _ => None,
+    }
+}
+
+///
+/// Runs a denoiser with its default settings
+///
+pub fn denoise(
+    image: &HdrImage,
+    guides: Guides,
+    denoiser: Denoiser,
+) -> Result<HdrImage, GuideMismatch> {
+    match denoiser {
+        Denoiser::None => Ok(image.clone()),
+        Denoiser::Bilateral => joint_bilateral(
+            image,
+            guides,
+            DEFAULT_RADIUS,
+            DEFAULT_SIGMA_SPATIAL,
+            DEFAULT_SIGMA_COLOR,
+        ),
+        Denoiser::ATrous => a_trous(image, guides, DEFAULT_ITERATIONS, DEFAULT_SIGMA_COLOR),
+    }
+}
+
+///
+/// A joint (cross) bilateral filter: each pixel becomes a weighted mean of its neighborhood,
+/// where weights fall off with distance, with color difference, and with difference in guides
+///
+pub fn joint_bilateral(
+    image: &HdrImage,
+    guides: Guides,
+    radius: u32,
+    sigma_spatial: f32,
+    sigma_color: f32,
+) -> Result<HdrImage, GuideMismatch> {
+    check_guides(image, guides)?;
+    let (w, h) = image.dimensions();
+    let r = radius as i64;
+    Ok(ImageBuffer::from_fn(w, h, |x, y| {
+        let center = *image.get_pixel(x, y);
+        let mut sum = [0.0f32; 3];
+        let mut total = 0.0f32;
+        for j in -r..=r {
+            for i in -r..=r {
+                let (nx, ny) = (x as i64 + i, y as i64 + j);
+                if nx < 0 || ny < 0 || nx >= w as i64 || ny >= h as i64 {
+                    continue;
+                }
+                let (nx, ny) = (nx as u32, ny as u32);
+                let p = *image.get_pixel(nx, ny);
+                let spatial = ((i * i + j * j) as f32) / (2.0 * sigma_spatial * sigma_spatial);
+                let range = distance_squared(&center, &p) / (2.0 * sigma_color * sigma_color);
+                let weight = (-spatial - range).exp() * guide_weight(guides, (x, y), (nx, ny));
+                accumulate(&mut sum, &p, weight);
+                total += weight;
+            }
+        }
+        normalize_sum(sum, total, center)
+    }))
+}
+
+///
+/// The edge-avoiding À-trous wavelet filter: repeated 5x5 B3-spline passes whose taps spread
+/// out by a factor of two each iteration, with edge-stopping on color and the guides.
+/// The color tolerance halves every pass so that later, wider passes only smooth flat areas
+///
+pub fn a_trous(
+    image: &HdrImage,
+    guides: Guides,
+    iterations: u32,
+    sigma_color: f32,
+) -> Result<HdrImage, GuideMismatch> {
+    check_guides(image, guides)?;
+    let (w, h) = image.dimensions();
+    let mut current = image.clone();
+    let mut sigma = sigma_color;
+    for iteration in 0..iterations {
+        let step = 1i64 << iteration;
+        let source = current;
+        current = ImageBuffer::from_fn(w, h, |x, y| {
+            let center = *source.get_pixel(x, y);
+            let mut sum = [0.0f32; 3];
+            let mut total = 0.0f32;
+            for (j, kj) in B3_SPLINE.iter().enumerate() {
+                for (i, ki) in B3_SPLINE.iter().enumerate() {
+                    let nx = x as i64 + (i as i64 - 2) * step;
+                    let ny = y as i64 + (j as i64 - 2) * step;
+                    if nx < 0 || ny < 0 || nx >= w as i64 || ny >= h as i64 {
+                        continue;
+                    }
+                    let (nx, ny) = (nx as u32, ny as u32);
+                    let p = *source.get_pixel(nx, ny);
+                    let range = (-distance_squared(&center, &p) / (2.0 * sigma * sigma)).exp();
+                    let weight = ki * kj * range * guide_weight(guides, (x, y), (nx, ny));
+                    accumulate(&mut sum, &p, weight);
+                    total += weight;
+                }
+            }
+            normalize_sum(sum, total, center)
+        });
+        sigma /= 2.0;
+    }
+    Ok(current)
+}
+
+fn guide_weight(guides: Guides, a: (u32, u32), b: (u32, u32)) -> f32 {
+    let mut weight = 1.0;
+    if let Some(n) = guides.normal {
+        let (p, q) = (n.get_pixel(a.0, a.1), n.get_pixel(b.0, b.1));
+        let dot = p.0[0] * q.0[0] + p.0[1] * q.0[1] + p.0[2] * q.0[2];
+        weight *= (-(1.0 - dot).max(0.0) / NORMAL_SIGMA).exp();
+    }
+    if let Some(d) = guides.depth {
+        let diff = d.get_pixel(a.0, a.1).0[0] - d.get_pixel(b.0, b.1).0[0];
+        weight *= (-(diff * diff) / (DEPTH_SIGMA * DEPTH_SIGMA)).exp();
+    }
+    if let Some(albedo) = guides.albedo {
+        let diff = distance_squared(albedo.get_pixel(a.0, a.1), albedo.get_pixel(b.0, b.1));
+        weight *= (-diff / (ALBEDO_SIGMA * ALBEDO_SIGMA)).exp();
+    }
+    weight
+}
+
+fn distance_squared(a: &Rgb<f32>, b: &Rgb<f32>) -> f32 {
+    let (dr, dg, db) = (a.0[0] - b.0[0], a.0[1] - b.0[1], a.0[2] - b.0[2]);
+    dr * dr + dg * dg + db * db
+}
+
+fn accumulate(sum: &mut [f32; 3], p: &Rgb<f32>, weight: f32) {
+    sum[0] += p.0[0] * weight;
+    sum[1] += p.0[1] * weight;
+    sum[2] += p.0[2] * weight;
+}
+
+fn normalize_sum(sum: [f32; 3], total: f32, fallback: Rgb<f32>) -> Rgb<f32> {
+    if total <= 0.0 {
+        fallback
+    } else {
+        Rgb([sum[0] / total, sum[1] / total, sum[2] / total])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn noisy(w: u32, h: u32) -> HdrImage {
+        ImageBuffer::from_fn(w, h, |x, y| {
+            let n = if (x * 7 + y * 13) % 5 < 2 {
+                0.05
+            } else {
+                -0.05
+            };
+            Rgb([0.5 + n, 0.5 + n, 0.5 + n])
+        })
+    }
+
+    fn variance(image: &HdrImage) -> f32 {
+        let n = (image.width() * image.height()) as f32;
+        let mean = image.pixels().map(|p| p.0[0]).sum::<f32>() / n;
+        image.pixels().map(|p| (p.0[0] - mean).powi(2)).sum::<f32>() / n
+    }
+
+    fn split(w: u32, h: u32) -> HdrImage {
+        ImageBuffer::from_fn(w, h, |x, _| {
+            if x < w / 2 {
+                Rgb([0.0, 0.0, 0.0])
+            } else {
+                Rgb([1.0, 1.0, 1.0])
+            }
+        })
+    }
+
+    #[test]
+    fn denoiser_names() {
+        for name in DENOISERS {
+            assert!(denoiser_from_name(name).is_some());
+        }
+        assert!(denoiser_from_name("median").is_none());
+    }
+
+    #[test]
+    fn none_is_identity() {
+        let image = noisy(8, 8);
+        assert_eq!(
+            denoise(&image, Guides::default(), Denoiser::None).unwrap(),
+            image
+        );
+    }
+
+    #[test]
+    fn bilateral_reduces_noise() {
+        let image = noisy(16, 16);
+        let out = denoise(&image, Guides::default(), Denoiser::Bilateral).unwrap();
+        assert!(variance(&out) < variance(&image) / 2.0);
+    }
+
+    #[test]
+    fn a_trous_reduces_noise() {
+        let image = noisy(16, 16);
+        let out = denoise(&image, Guides::default(), Denoiser::ATrous).unwrap();
+        assert!(variance(&out) < variance(&image) / 2.0);
+    }
+
+    #[test]
+    fn filters_preserve_color_edges() {
+        let image = split(16, 4);
+        for d in [Denoiser::Bilateral, Denoiser::ATrous] {
+            let out = denoise(&image, Guides::default(), d).unwrap();
+            assert!(out.get_pixel(7, 2).0[0] < 0.05);
+            assert!(out.get_pixel(8, 2).0[0] > 0.95);
+        }
+    }
+
+    #[test]
+    fn depth_guide_stops_blur_across_edges() {
+        let image = split(16, 4);
+        let guides = Guides {
+            depth: Some(&image),
+            ..Guides::default()
+        };
+        let guided = joint_bilateral(&image, guides, 3, 2.0, 10.0).unwrap();
+        let unguided = joint_bilateral(&image, Guides::default(), 3, 2.0, 10.0).unwrap();
+        assert!(guided.get_pixel(7, 1).0[0] < 0.01);
+        assert!(unguided.get_pixel(7, 1).0[0] > 0.1);
+    }
+
+    #[test]
+    fn guides_must_match_the_image() {
+        let image = noisy(8, 8);
+        let small = noisy(4, 8);
+        let guides = Guides {
+            normal: Some(&small),
+            ..Guides::default()
+        };
+        for d in [Denoiser::Bilateral, Denoiser::ATrous] {
+            let err = denoise(&image, guides, d).unwrap_err();
+            assert_eq!(err.guide, "normal");
+            assert_eq!((err.expected, err.found), ((8, 8), (4, 8)));
+        }
+    }
+
+    #[test]
+    fn layers_guide_the_filters() {
+        let mut layers = crate::canvas::layers(split(16, 4));
+        layers.depth = Some(split(16, 4));
+        let g = guides(&layers);
+        assert!(g.depth.is_some() && g.normal.is_none());
+        let guided = joint_bilateral(&layers.color, g, 3, 2.0, 10.0).unwrap();
+        assert!(guided.get_pixel(7, 1).0[0] < 0.01);
+    }
+
+    #[test]
+    fn flat_images_keep_their_values_above_one() {
+        let image = ImageBuffer::from_pixel(5, 3, Rgb([3.0, 0.25, 1.0]));
+        let out = denoise(&image, Guides::default(), Denoiser::ATrous).unwrap();
+        assert_eq!(out.dimensions(), (5, 3));
+        for (a, b) in out.get_pixel(2, 1).0.iter().zip([3.0, 0.25, 1.0]) {
+            assert!((a - b).abs() < 1e-5);
+        }
+    }
+}
@@ -0,0 +1,529 @@
+//!
+//! 2D drawing on a canvas. Coordinates are image coordinates (y grows downwards) with pixel
+//! centers on whole numbers, and every primitive clips to the canvas. Colors carry an alpha
+//! that is blended over what's already there, on top of any anti-aliasing coverage.
+//!
+
+use image::{ImageBuffer, Rgb, Rgba};
+
+type Canvas = ImageBuffer<Rgb<u8>, Vec<u8>>;
+
+///
+/// Blends a color over one pixel, scaling its alpha by `coverage` in 0..1
+///
+pub fn blend_pixel(canvas: &mut Canvas, x: i32, y: i32, color: Rgba<u8>, coverage: f64) {
+    let alpha = (color.0[3] as f64 / 255.0 * coverage).clamp(0.0, 1.0);
+    if alpha <= 0.0 || x < 0 || y < 0 || x >= canvas.width() as i32 || y >= canvas.height() as i32 {
+        return;
+    }
+    let dst = canvas.get_pixel_mut(x as u32, y as u32);
+    for c in 0..3 {
+        let blended = dst.0[c] as f64 * (1.0 - alpha) + color.0[c] as f64 * alpha;
+        dst.0[c] = blended.round() as u8;
+    }
+}
+
+///
+/// A one pixel wide line between two pixels with Bresenham's algorithm
+///
+pub fn line(canvas: &mut Canvas, from: (i32, i32), to: (i32, i32), color: Rgba<u8>) {
+    let (w, h) = (canvas.width() as i32, canvas.height() as i32);
+    let inside = |p: (i32, i32)| p.0 >= 0 && p.1 >= 0 && p.0 < w && p.1 < h;
+    // lines that leave the canvas are cut to it first, so far away ends don't cost a step per
+    // pixel or overflow; lines that stay on it keep their exact pixels
+    let (from, to) = if inside(from) && inside(to) {
+        (from, to)
+    } else {
+        let as_f64 = |p: (i32, i32)| (p.0 as f64, p.1 as f64);
+        let max = ((w - 1) as f64, (h - 1) as f64);
+        let Some((a, b)) = clip_segment(as_f64(from), as_f64(to), (0.0, 0.0), max) else {
+            return;
+        };
+        let round = |p: (f64, f64)| (p.0.round() as i32, p.1.round() as i32);
+        (round(a), round(b))
+    };
+    let (mut x, mut y) = from;
+    let (dx, dy) = ((to.0 - x).abs(), -(to.1 - y).abs());
+    let (sx, sy) = ((to.0 - x).signum(), (to.1 - y).signum());
+    let mut err = dx + dy;
+    loop {
+        blend_pixel(canvas, x, y, color, 1.0);
+        if (x, y) == to {
+            break;
+        }
+        let e2 = 2 * err;
+        if e2 >= dy {
+            err += dy;
+            x += sx;
+        }
+        if e2 <= dx {
+            err += dx;
+            y += sy;
+        }
+    }
+}
+
+///
+/// An anti-aliased one pixel wide line with Xiaolin Wu's algorithm, which splits each step
+/// between the two pixels the line passes between
+///
+pub fn line_aa(canvas: &mut Canvas, from: (f64, f64), to: (f64, f64), color: Rgba<u8>) {
+    // the ends and the pixels either side of them stay off the canvas with a two pixel margin,
+    // so cutting the line there doesn't change what's drawn on it
+    let max = (canvas.width() as f64 + 1.0, canvas.height() as f64 + 1.0);
+    let Some((from, to)) = clip_segment(from, to, (-2.0, -2.0), max) else {
+        return;
+    };
+    let steep = (to.1 - from.1).abs() > (to.0 - from.0).abs();
+    // walk along whichever axis the line is longer in
+    let (mut a, mut b) = if steep {
+        ((from.1, from.0), (to.1, to.0))
+    } else {
+        (from, to)
+    };
+    if a.0 > b.0 {
+        std::mem::swap(&mut a, &mut b);
+    }
+    let dx = b.0 - a.0;
+    let gradient = if dx == 0.0 { 1.0 } else { (b.1 - a.1) / dx };
+    let mut plot = |x: f64, y: f64, coverage: f64| {
+        let (x, y) = if steep { (y, x) } else { (x, y) };
+        blend_pixel(canvas, x as i32, y as i32, color, coverage);
+    };
+    let fract = |v: f64| v - v.floor();
+
+    let mut ends = [(0.0, 0.0); 2];
+    for (i, (p, gap)) in [(a, 1.0 - fract(a.0 + 0.5)), (b, fract(b.0 + 0.5))]
+        .into_iter()
+        .enumerate()
+    {
+        let x = p.0.round();
+        let y = p.1 + gradient * (x - p.0);
+        plot(x, y.floor(), (1.0 - fract(y)) * gap);
+        plot(x, y.floor() + 1.0, fract(y) * gap);
+        ends[i] = (x, y);
+    }
+
+    let mut y = ends[0].1 + gradient;
+    let mut x = ends[0].0 + 1.0;
+    while x < ends[1].0 {
+        plot(x, y.floor(), 1.0 - fract(y));
+        plot(x, y.floor() + 1.0, fract(y));
+        y += gradient;
+        x += 1.0;
+    }
+}
+
+///
+/// A thick anti-aliased line through every point with round joins and caps. Each pixel is
+/// blended once however many segments cover it, so translucent polylines don't darken where
+/// segments meet
+///
+pub fn polyline(canvas: &mut Canvas, points: &[(f64, f64)], width: f64, color: Rgba<u8>) {
+    let half = width / 2.0;
+    let reach = half + 1.0;
+    let (w, h) = (canvas.width() as i32, canvas.height() as i32);
+    let Some(bounds) = bounding_box(points, reach, w, h) else {
+        return;
+    };
+    let (x0, y0, x1, y1) = bounds;
+    let stride = (x1 - x0 + 1) as usize;
+    let mut coverage = vec![0.0_f64; stride * (y1 - y0 + 1) as usize];
+
+    let segments: Vec<_> = if points.len() == 1 {
+        vec![(points[0], points[0])]
+    } else {
+        points.windows(2).map(|s| (s[0], s[1])).collect()
+    };
+    for (a, b) in segments {
+        let sx0 = (a.0.min(b.0) - reach).floor().max(x0 as f64) as i32;
+        let sx1 = (a.0.max(b.0) + reach).ceil().min(x1 as f64) as i32;
+        let sy0 = (a.1.min(b.1) - reach).floor().max(y0 as f64) as i32;
+        let sy1 = (a.1.max(b.1) + reach).ceil().min(y1 as f64) as i32;
+        for y in sy0..=sy1 {
+            for x in sx0..=sx1 {
+                let d = distance_to_segment((x as f64, y as f64), a, b);
+                let c = (half - d + 0.5).clamp(0.0, 1.0);
+                let i = (y - y0) as usize * stride + (x - x0) as usize;
+                coverage[i] = coverage[i].max(c);
+            }
+        }
+    }
+
+    for (i, c) in coverage.into_iter().enumerate() {
+        let (x, y) = (x0 + (i % stride) as i32, y0 + (i / stride) as i32);
+        blend_pixel(canvas, x, y, color, c);
+    }
+}
+
+///
+/// The outline of a circle with the midpoint algorithm
+///
+pub fn circle(canvas: &mut Canvas, center: (i32, i32), radius: i32, color: Rgba<u8>) {
+    ellipse(canvas, center, radius, radius, color)
+}
+
+///
+/// The outline of an axis aligned ellipse with the midpoint algorithm
+///
+pub fn ellipse(canvas: &mut Canvas, center: (i32, i32), rx: i32, ry: i32, color: Rgba<u8>) {
+    if rx < 0 || ry < 0 {
+        return;
+    }
+    // the four mirrored points, skipping the duplicates on the axes so they only blend once
+    let mut plot = |x: i32, y: i32| {
+        let mut points = vec![(x, y)];
+        if x != 0 {
+            points.push((-x, y));
+        }
+        if y != 0 {
+            points.extend(points.clone().into_iter().map(|(x, y)| (x, -y)));
+        }
+        for (px, py) in points {
+            blend_pixel(canvas, center.0 + px, center.1 + py, color, 1.0);
+        }
+    };
+    let (rx2, ry2) = ((rx * rx) as i64, (ry * ry) as i64);
+    let (mut x, mut y) = (0_i64, ry as i64);
+
+    // where the slope is shallower than -1, step along x
+    let mut d = ry2 - rx2 * ry as i64 + rx2 / 4;
+    while ry2 * x < rx2 * y {
+        plot(x as i32, y as i32);
+        x += 1;
+        if d < 0 {
+            d += ry2 * (2 * x + 1);
+        } else {
+            y -= 1;
+            d += ry2 * (2 * x + 1) - 2 * rx2 * y;
+        }
+    }
+
+    // then step along y
+    let mut d = ry2 * (2 * x + 1) * (2 * x + 1) / 4 + rx2 * (y - 1) * (y - 1) - rx2 * ry2;
+    while y >= 0 {
+        plot(x as i32, y as i32);
+        y -= 1;
+        if d > 0 {
+            d += rx2 * (1 - 2 * y);
+        } else {
+            x += 1;
+            d += ry2 * 2 * x + rx2 * (1 - 2 * y);
+        }
+    }
+}
+
+///
+/// A filled anti-aliased circle, which is also the stamp of a round brush
+///
+pub fn fill_circle(canvas: &mut Canvas, center: (f64, f64), radius: f64, color: Rgba<u8>) {
+    fill_ellipse(canvas, center, radius, radius, color)
+}
+
+///
+/// A filled anti-aliased axis aligned ellipse
+///
+pub fn fill_ellipse(canvas: &mut Canvas, center: (f64, f64), rx: f64, ry: f64, color: Rgba<u8>) {
+    if rx <= 0.0 || ry <= 0.0 {
+        return;
+    }
+    let (w, h) = (canvas.width() as i32, canvas.height() as i32);
+    let x0 = ((center.0 - rx - 1.0).floor() as i32).max(0);
+    let x1 = ((center.0 + rx + 1.0).ceil() as i32).min(w - 1);
+    let y0 = ((center.1 - ry - 1.0).floor() as i32).max(0);
+    let y1 = ((center.1 + ry + 1.0).ceil() as i32).min(h - 1);
+    for y in y0..=y1 {
+        for x in x0..=x1 {
+            let (dx, dy) = (x as f64 - center.0, y as f64 - center.1);
+            // the implicit function over the length of its gradient approximates the
+            // distance to the edge in pixels
+            let f = (dx / rx).powi(2) + (dy / ry).powi(2) - 1.0;
+            let gradient = (2.0 * dx / (rx * rx)).hypot(2.0 * dy / (ry * ry));
+            let distance = if gradient > 0.0 {
+                f / gradient
+            } else {
+                f64::NEG_INFINITY
+            };
+            blend_pixel(canvas, x, y, color, (0.5 - distance).clamp(0.0, 1.0));
+        }
+    }
+}
+
+///
+/// Fills a polygon with the even-odd rule, one scanline per row of pixel centers
+///
+pub fn fill_polygon(canvas: &mut Canvas, points: &[(f64, f64)], color: Rgba<u8>) {
+    let (w, h) = (canvas.width() as i32, canvas.height() as i32);
+    if points.len() < 3 {
+        return;
+    }
+    let Some((_, y0, _, y1)) = bounding_box(points, 0.0, w, h) else {
+        return;
+    };
+    let edges: Vec<_> = points
+        .iter()
+        .zip(points.iter().cycle().skip(1))
+        .map(|(a, b)| (*a, *b))
+        .collect();
+    for y in y0..=y1 {
+        let fy = y as f64;
+        let mut crossings: Vec<f64> = edges
+            .iter()
+            .filter(|(a, b)| (a.1 <= fy && fy < b.1) || (b.1 <= fy && fy < a.1))
+            .map(|(a, b)| a.0 + (fy - a.1) * (b.0 - a.0) / (b.1 - a.1))
+            .collect();
+        crossings.sort_by(f64::total_cmp);
+        for span in crossings.chunks_exact(2) {
+            let start = (span[0].ceil() as i32).max(0);
+            let end = (span[1].ceil() as i32).min(w);
+            for x in start..end {
+                blend_pixel(canvas, x, y, color, 1.0);
+            }
+        }
+    }
+}
+
+// the pixels within `margin` of the points, clipped to the canvas, or None if that's empty
+fn bounding_box(
+    points: &[(f64, f64)],
+    margin: f64,
+    w: i32,
+    h: i32,
+) -> Option<(i32, i32, i32, i32)> {
+    let first = points.first()?;
+    let (mut min, mut max) = (*first, *first);
+    for p in points {
+        min = (min.0.min(p.0), min.1.min(p.1));
+        max = (max.0.max(p.0), max.1.max(p.1));
+    }
+    let x0 = (min.0 - margin).floor().max(0.0);
+    let y0 = (min.1 - margin).floor().max(0.0);
+    let x1 = (max.0 + margin).ceil().min((w - 1) as f64);
+    let y1 = (max.1 + margin).ceil().min((h - 1) as f64);
+    if x0 > x1 || y0 > y1 || !(x0.is_finite() && y0.is_finite() && x1.is_finite() && y1.is_finite())
+    {
+        return None;
+    }
+    Some((x0 as i32, y0 as i32, x1 as i32, y1 as i32))
+}
+
+// the part of the segment inside the rectangle from `min` to `max` with the Liang-Barsky
+// algorithm, or None if it misses it
+fn clip_segment(
+    from: (f64, f64),
+    to: (f64, f64),
+    min: (f64, f64),
+    max: (f64, f64),
+) -> Option<((f64, f64), (f64, f64))> {
+    if ![from.0, from.1, to.0, to.1].iter().all(|v| v.is_finite()) {
+        return None;
+    }
+    let (dx, dy) = (to.0 - from.0, to.1 - from.1);
+    let (mut t0, mut t1) = (0.0_f64, 1.0_f64);
+    for (p, q) in [
+        (-dx, from.0 - min.0),
+        (dx, max.0 - from.0),
+        (-dy, from.1 - min.1),
+        (dy, max.1 - from.1),
+    ] {
+        if p == 0.0 {
+            // parallel to this edge, so it's either all inside it or all outside
+            if q < 0.0 {
+                return None;
+            }
+        } else if p < 0.0 {
+            t0 = t0.max(q / p);
+        } else {
+            t1 = t1.min(q / p);
+        }
+    }
+    if t0 > t1 {
+        return None;
+    }
+    Some((
+        (from.0 + t0 * dx, from.1 + t0 * dy),
+        (from.0 + t1 * dx, from.1 + t1 * dy),
+    ))
+}
+
+fn distance_to_segment(p: (f64, f64), a: (f64, f64), b: (f64, f64)) -> f64 {
+    let (abx, aby) = (b.0 - a.0, b.1 - a.1);
+    let length2 = abx * abx + aby * aby;
+    let t = if length2 > 0.0 {
+        (((p.0 - a.0) * abx + (p.1 - a.1) * aby) / length2).clamp(0.0, 1.0)
+    } else {
+        0.0
+    };
+    (p.0 - (a.0 + t * abx)).hypot(p.1 - (a.1 + t * aby))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::imageops;
+
+    const WHITE: Rgba<u8> = Rgba([255, 255, 255, 255]);
+
+    fn canvas(w: u32, h: u32) -> Canvas {
+        ImageBuffer::new(w, h)
+    }
+
+    fn lit(canvas: &Canvas) -> Vec<(u32, u32)> {
+        canvas
+            .enumerate_pixels()
+            .filter(|(_, _, p)| p.0 != [0, 0, 0])
+            .map(|(x, y, _)| (x, y))
+            .collect()
+    }
+
+    #[test]
+    fn alpha_blends_over_the_canvas() {
+        let mut c = canvas(2, 1);
+        blend_pixel(&mut c, 0, 0, Rgba([200, 100, 0, 128]), 1.0);
+        assert_eq!(c.get_pixel(0, 0).0, [100, 50, 0]);
+        blend_pixel(&mut c, 1, 0, WHITE, 0.5);
+        assert_eq!(c.get_pixel(1, 0).0, [128, 128, 128]);
+        // off the canvas is ignored
+        blend_pixel(&mut c, -1, 0, WHITE, 1.0);
+        blend_pixel(&mut c, 2, 0, WHITE, 1.0);
+    }
+
+    #[test]
+    fn bresenham_lines_are_connected() {
+        let mut c = canvas(10, 10);
+        line(&mut c, (1, 1), (8, 4), WHITE);
+        let pixels = lit(&c);
+        assert_eq!(pixels.len(), 8);
+        assert!(pixels.contains(&(1, 1)) && pixels.contains(&(8, 4)));
+        let mut c = canvas(10, 10);
+        line(&mut c, (2, 9), (2, 0), WHITE);
+        assert_eq!(lit(&c).len(), 10);
+    }
+
+    #[test]
+    fn wu_lines_split_coverage_between_rows() {
+        let mut c = canvas(10, 10);
+        line_aa(&mut c, (1.0, 2.0), (8.0, 2.0), WHITE);
+        assert_eq!(c.get_pixel(4, 2).0, [255, 255, 255]);
+        assert_eq!(c.get_pixel(4, 3).0, [0, 0, 0]);
+
+        let mut c = canvas(10, 10);
+        line_aa(&mut c, (1.0, 2.5), (8.0, 2.5), WHITE);
+        assert_eq!(c.get_pixel(4, 2).0, [128, 128, 128]);
+        assert_eq!(c.get_pixel(4, 3).0, [128, 128, 128]);
+    }
+
+    #[test]
+    fn far_off_canvas_ends_are_clipped() {
+        let mut c = canvas(10, 10);
+        line(&mut c, (-2_000_000_000, 5), (2_000_000_000, 5), WHITE);
+        assert_eq!(lit(&c), (0..10).map(|x| (x, 5)).collect::<Vec<_>>());
+
+        let mut c = canvas(10, 10);
+        line(
+            &mut c,
+            (-1_000_000_000, -1_000_000_000),
+            (1_000_000_000, 1_000_000_000),
+            WHITE,
+        );
+        assert_eq!(lit(&c).len(), 10);
+        assert!(lit(&c).iter().all(|&(x, y)| x == y));
+
+        let mut c = canvas(10, 10);
+        line_aa(&mut c, (-1e15, 2.0), (1e15, 2.0), WHITE);
+        assert_eq!(lit(&c), (0..10).map(|x| (x, 2)).collect::<Vec<_>>());
+
+        // lines that miss the canvas draw nothing
+        let mut c = canvas(10, 10);
+        line(&mut c, (-2_000_000_000, -5), (2_000_000_000, -5), WHITE);
+        line_aa(&mut c, (-1e15, 1e15), (-1e15 + 1.0, -1e15), WHITE);
+        assert!(lit(&c).is_empty());
+    }
+
+    #[test]
+    fn clipping_keeps_the_pixels_on_the_canvas() {
+        let mut clipped = canvas(10, 10);
+        line_aa(&mut clipped, (-30.0, -7.0), (40.0, 13.0), WHITE);
+        let mut whole = canvas(80, 40);
+        line_aa(&mut whole, (10.0, 13.0), (80.0, 33.0), WHITE);
+        let window = imageops::crop_imm(&whole, 40, 20, 10, 10).to_image();
+        assert_eq!(clipped, window);
+    }
+
+    #[test]
+    fn thick_polylines_have_round_joins() {
+        let mut c = canvas(40, 40);
+        polyline(&mut c, &[(5.0, 5.0), (30.0, 5.0), (30.0, 30.0)], 5.0, WHITE);
+        assert_eq!(c.get_pixel(15, 7).0, [255, 255, 255]);
+        assert_eq!(c.get_pixel(15, 9).0, [0, 0, 0]);
+        assert_eq!(c.get_pixel(32, 20).0, [255, 255, 255]);
+        // the outside of the corner is rounded off, where a square join would fill it
+        assert!(c.get_pixel(32, 3).0[0] < 64);
+        assert_eq!(c.get_pixel(31, 4).0, [255, 255, 255]);
+    }
+
+    #[test]
+    fn translucent_polylines_blend_once_where_segments_overlap() {
+        let mut c = canvas(20, 20);
+        let half = Rgba([255, 255, 255, 128]);
+        polyline(&mut c, &[(2.0, 10.0), (10.0, 10.0), (2.0, 10.0)], 3.0, half);
+        assert_eq!(c.get_pixel(6, 10).0, [128, 128, 128]);
+    }
+
+    #[test]
+    fn midpoint_circles_are_symmetric() {
+        let mut c = canvas(21, 21);
+        circle(&mut c, (10, 10), 6, WHITE);
+        for (x, y) in [(16, 10), (4, 10), (10, 16), (10, 4)] {
+            assert_eq!(c.get_pixel(x, y).0, [255, 255, 255]);
+        }
+        assert_eq!(c.get_pixel(10, 10).0, [0, 0, 0]);
+        let pixels = lit(&c);
+        assert!(pixels
+            .iter()
+            .all(|(x, y)| pixels.contains(&(20 - x, *y)) && pixels.contains(&(*x, 20 - y))));
+    }
+
+    #[test]
+    fn ellipses_reach_both_radii() {
+        let mut c = canvas(30, 20);
+        ellipse(&mut c, (15, 10), 12, 5, WHITE);
+        assert_eq!(c.get_pixel(27, 10).0, [255, 255, 255]);
+        assert_eq!(c.get_pixel(15, 5).0, [255, 255, 255]);
+        assert_eq!(c.get_pixel(28, 10).0, [0, 0, 0]);
+    }
+
+    #[test]
+    fn filled_circles_cover_their_area() {
+        let mut c = canvas(41, 41);
+        fill_circle(&mut c, (20.0, 20.0), 10.0, WHITE);
+        let area: f64 = c.pixels().map(|p| p.0[0] as f64 / 255.0).sum();
+        let expected = std::f64::consts::PI * 100.0;
+        assert!((area - expected).abs() / expected < 0.01);
+        assert_eq!(c.get_pixel(20, 20).0, [255, 255, 255]);
+        assert_eq!(c.get_pixel(20, 31).0, [0, 0, 0]);
+    }
+
+    #[test]
+    fn polygons_fill_even_odd() {
+        let mut c = canvas(10, 10);
+        fill_polygon(
+            &mut c,
+            &[(1.5, 1.5), (7.5, 1.5), (7.5, 5.5), (1.5, 5.5)],
+            WHITE,
+        );
+        assert_eq!(lit(&c).len(), 6 * 4);
+
+        // a pentagram leaves its middle empty
+        let mut c = canvas(41, 41);
+        let star: Vec<_> = (0..5)
+            .map(|i| {
+                let a = (i * 144) as f64 * std::f64::consts::PI / 180.0;
+                (20.0 + 18.0 * a.sin(), 20.0 - 18.0 * a.cos())
+            })
+            .collect();
+        fill_polygon(&mut c, &star, WHITE);
+        assert_eq!(c.get_pixel(20, 21).0, [0, 0, 0]);
+        assert_eq!(c.get_pixel(20, 6).0, [255, 255, 255]);
+    }
+}
@@ -0,0 +1,400 @@
+use crate::canvas::HdrImage;
+use crate::color::{add_colors, color, multiply_by_scalar, Color};
+use crate::tuple::{normalize, vector, Tuple};
+use image::{ImageResult, Rgb};
+use std::f64::consts::PI;
+use std::path::Path;
+
+///
+/// What a ray sees when it escapes the scene
+///
+pub enum Environment {
+    Solid(Color),
+    /// Blends from the first color straight down to the second color straight up
+    Gradient(Color, Color),
+    /// A latitude/longitude panorama; the center of the image looks down -z
+    Equirectangular(HdrImage),
+    /// Six faces in the order +x, -x, +y, -y, +z, -z
+    CubeMap(Box<[HdrImage; 6]>),
+}
+
+///
+/// A piecewise-constant distribution over the texels of an equirectangular map,
+/// weighted by luminance so that bright regions (the sun, windows) are picked more often
+///
+pub struct EnvironmentSampler {
+    width: usize,
+    height: usize,
+    row_cdf: Vec<f64>,
+    column_cdfs: Vec<Vec<f64>>,
+    weights: Vec<f64>,
+    total: f64,
+}
+
+///
+/// Loads an equirectangular environment from any format the image crate reads, including .hdr
+///
+pub fn load_equirectangular<P: AsRef<Path>>(path: P) -> ImageResult<Environment> {
+    Ok(Environment::Equirectangular(
+        image::open(path)?.into_rgb32f(),
+    ))
+}
+
+///
+/// Loads a cube map from six images in the order +x, -x, +y, -y, +z, -z
+///
+pub fn load_cube_map<P: AsRef<Path>>(paths: [P; 6]) -> ImageResult<Environment> {
+    let [px, nx, py, ny, pz, nz] = paths;
+    let load = |p: P| image::open(p).map(|i| i.into_rgb32f());
+    Ok(Environment::CubeMap(Box::new([
+        load(px)?,
+        load(nx)?,
+        load(py)?,
+        load(ny)?,
+        load(pz)?,
+        load(nz)?,
+    ])))
+}
+
+///
+/// Looks up the environment color seen along a direction
+///
+pub fn sample_environment(env: &Environment, direction: Tuple) -> Color {
+    let d = normalize(vector(direction.0, direction.1, direction.2));
+    match env {
+        Environment::Solid(c) => *c,
+        Environment::Gradient(bottom, top) => {
+            let t = 0.5 * (d.1 + 1.0);
+            add_colors(
+                multiply_by_scalar(*bottom, 1.0 - t),
+                multiply_by_scalar(*top, t),
+            )
+        }
+        Environment::Equirectangular(image) => {
+            let (u, v) = direction_to_equirectangular(d);
+            texel(image, u, v)
+        }
+        Environment::CubeMap(faces) => {
+            let (face, u, v) = direction_to_cube_face(d);
+            texel(&faces[face], u, v)
+        }
+    }
+}
+
+///
+/// Maps a unit direction to (u, v) on an equirectangular image, with v = 0 at the top (+y)
+///
+pub fn direction_to_equirectangular(d: Tuple) -> (f64, f64) {
+    let u = 0.5 + d.0.atan2(-d.2) / (2.0 * PI);
+    let v = d.1.clamp(-1.0, 1.0).acos() / PI;
+    (u, v)
+}
+
+///
+/// The inverse of `direction_to_equirectangular`
+///
+pub fn equirectangular_to_direction(u: f64, v: f64) -> Tuple {
+    let phi = (u - 0.5) * 2.0 * PI;
+    let theta = v * PI;
+    vector(
+        theta.sin() * phi.sin(),
+        theta.cos(),
+        -theta.sin() * phi.cos(),
+    )
+}
+
+///
+/// Picks the cube face a direction points into and the (u, v) on it, with v = 0 at the top
+///
+pub fn direction_to_cube_face(d: Tuple) -> (usize, f64, f64) {
+    let (ax, ay, az) = (d.0.abs(), d.1.abs(), d.2.abs());
+    let (face, sc, tc, ma) = if ax >= ay && ax >= az {
+        if d.0 > 0.0 {
+            (0, -d.2, -d.1, ax)
+        } else {
+            (1, d.2, -d.1, ax)
+        }
+    } else if ay >= az {
+        if d.1 > 0.0 {
+            (2, d.0, d.2, ay)
+        } else {
+            (3, d.0, -d.2, ay)
+        }
+    } else if d.2 > 0.0 {
+        (4, d.0, -d.1, az)
+    } else {
+        (5, -d.0, -d.1, az)
+    };
+    (face, 0.5 * (sc / ma + 1.0), 0.5 * (tc / ma + 1.0))
+}
+
+///
+/// Builds an importance sampler for an equirectangular environment.
+/// Returns None for the other environment kinds, which are better sampled uniformly,
+/// and for an empty map, which has nothing to sample
+///
+pub fn importance_sampler(env: &Environment) -> Option<EnvironmentSampler> {
+    let image = match env {
+        Environment::Equirectangular(image) => image,
+        _ => return None,
+    };
+    let (width, height) = (image.width() as usize, image.height() as usize);
+    if width == 0 || height == 0 {
+        return None;
+    }
+    let mut weights = Vec::with_capacity(width * height);
+    let mut column_cdfs = Vec::with_capacity(height);
+    let mut row_sums = Vec::with_capacity(height);
+
+    for y in 0..height {
+        // rows near the poles cover less solid angle
+        let sin_theta = (PI * (y as f64 + 0.5) / height as f64).sin();
+        let mut cdf = Vec::with_capacity(width);
+        let mut sum = 0.0;
+        for x in 0..width {
+            let w = luminance(image.get_pixel(x as u32, y as u32)) * sin_theta;
+            weights.push(w);
+            sum += w;
+            cdf.push(sum);
+        }
+        row_sums.push(sum);
+        column_cdfs.push(cdf);
+    }
+
+    let mut row_cdf = Vec::with_capacity(height);
+    let mut total = 0.0;
+    for s in row_sums {
+        total += s;
+        row_cdf.push(total);
+    }
+
+    Some(EnvironmentSampler {
+        width,
+        height,
+        row_cdf,
+        column_cdfs,
+        weights,
+        total,
+    })
+}
+
+///
+/// Turns two uniform random numbers in 0..1 into a direction and its solid angle pdf.
+/// Each number first picks a texel through its CDF, and what's left of it once the texel's
+/// range is taken off places the direction within that texel
+///
+pub fn sample_direction(sampler: &EnvironmentSampler, r1: f64, r2: f64) -> (Tuple, f64) {
+    if sampler.total <= 0.0 {
+        let v = (1.0 - 2.0 * r2).clamp(-1.0, 1.0).acos() / PI;
+        return (equirectangular_to_direction(r1, v), 1.0 / (4.0 * PI));
+    }
+
+    // keep the targets strictly below the totals so zero-weight texels are never picked
+    let (r1, r2) = (
+        r1.clamp(0.0, 1.0 - f64::EPSILON),
+        r2.clamp(0.0, 1.0 - f64::EPSILON),
+    );
+    let (y, fy) = search_remainder(&sampler.row_cdf, r2 * sampler.total);
+    let row = &sampler.column_cdfs[y];
+    let row_total = row[sampler.width - 1];
+    let (x, fx) = search_remainder(row, r1 * row_total);
+
+    let u = (x as f64 + fx) / sampler.width as f64;
+    let v = (y as f64 + fy) / sampler.height as f64;
+    let d = equirectangular_to_direction(u, v);
+    (d, direction_pdf(sampler, d))
+}
+
+///
+/// The solid angle pdf with which `sample_direction` produces a direction
+///
+pub fn direction_pdf(sampler: &EnvironmentSampler, direction: Tuple) -> f64 {
+    if sampler.total <= 0.0 {
+        return 1.0 / (4.0 * PI);
+    }
+    let d = normalize(vector(direction.0, direction.1, direction.2));
+    let (u, v) = direction_to_equirectangular(d);
+    let x = ((u * sampler.width as f64) as usize).min(sampler.width - 1);
+    let y = ((v * sampler.height as f64) as usize).min(sampler.height - 1);
+    // the density is constant over a texel's (u, v), so over solid angle it follows the
+    // direction's own latitude rather than the texel's center
+    let sin_theta = (PI * v).sin();
+    if sin_theta <= 0.0 {
+        return 0.0;
+    }
+    let texel_pdf = sampler.weights[y * sampler.width + x] / sampler.total;
+    texel_pdf * (sampler.width * sampler.height) as f64 / (2.0 * PI * PI * sin_theta)
+}
+
+fn luminance(p: &Rgb<f32>) -> f64 {
+    0.2126 * p.0[0] as f64 + 0.7152 * p.0[1] as f64 + 0.0722 * p.0[2] as f64
+}
+
+///
+/// First index whose cumulative value exceeds `target`
+///
+fn search(cdf: &[f64], target: f64) -> usize {
+    cdf.partition_point(|c| *c <= target).min(cdf.len() - 1)
+}
+
+///
+/// `search`, along with how far `target` lies into the chosen entry's range, from 0 to 1
+///
+fn search_remainder(cdf: &[f64], target: f64) -> (usize, f64) {
+    let i = search(cdf, target);
+    let start = if i == 0 { 0.0 } else { cdf[i - 1] };
+    let width = cdf[i] - start;
+    if width <= 0.0 {
+        return (i, 0.5);
+    }
+    (i, ((target - start) / width).clamp(0.0, 1.0 - f64::EPSILON))
+}
+
+fn texel(image: &HdrImage, u: f64, v: f64) -> Color {
+    let (w, h) = image.dimensions();
+    if w == 0 || h == 0 {
+        return color(0.0, 0.0, 0.0);
+    }
+    let x = ((u.rem_euclid(1.0) * w as f64) as u32).min(w - 1);
+    let y = ((v.clamp(0.0, 1.0) * h as f64) as u32).min(h - 1);
+    let p = image.get_pixel(x, y);
+    color(p.0[0] as f64, p.0[1] as f64, p.0[2] as f64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tuple::{eq_f64, eq_tup};
+
+    fn uniform(w: u32, h: u32, v: f32) -> HdrImage {
+        HdrImage::from_pixel(w, h, Rgb([v, v, v]))
+    }
+
+    #[test]
+    fn solid_is_the_same_everywhere() {
+        let env = Environment::Solid(color(0.1, 0.2, 0.3));
+        let c = sample_environment(&env, vector(0.3, -0.9, 0.1));
+        assert!(eq_f64(c.0, 0.1) && eq_f64(c.1, 0.2) && eq_f64(c.2, 0.3))
+    }
+
+    #[test]
+    fn gradient_blends_vertically() {
+        let env = Environment::Gradient(color(0.0, 0.0, 0.0), color(1.0, 1.0, 1.0));
+        assert!(eq_f64(
+            sample_environment(&env, vector(0.0, 1.0, 0.0)).0,
+            1.0
+        ));
+        assert!(eq_f64(
+            sample_environment(&env, vector(0.0, -1.0, 0.0)).0,
+            0.0
+        ));
+        assert!(eq_f64(
+            sample_environment(&env, vector(1.0, 0.0, 0.0)).0,
+            0.5
+        ));
+    }
+
+    #[test]
+    fn equirectangular_round_trip() {
+        let d = normalize(vector(0.3, 0.4, -0.5));
+        let (u, v) = direction_to_equirectangular(d);
+        assert!(eq_tup(d, equirectangular_to_direction(u, v)))
+    }
+
+    #[test]
+    fn equirectangular_center_looks_down_negative_z() {
+        let mut image = uniform(4, 2, 0.0);
+        image.put_pixel(2, 0, Rgb([1.0, 0.0, 0.0]));
+        image.put_pixel(2, 1, Rgb([1.0, 0.0, 0.0]));
+        let env = Environment::Equirectangular(image);
+        let c = sample_environment(&env, vector(0.1, 0.0, -1.0));
+        assert!(eq_f64(c.0, 1.0))
+    }
+
+    #[test]
+    fn cube_faces() {
+        assert_eq!(direction_to_cube_face(vector(1.0, 0.1, 0.1)).0, 0);
+        assert_eq!(direction_to_cube_face(vector(-1.0, 0.1, 0.1)).0, 1);
+        assert_eq!(direction_to_cube_face(vector(0.1, 1.0, 0.1)).0, 2);
+        assert_eq!(direction_to_cube_face(vector(0.1, -1.0, 0.1)).0, 3);
+        assert_eq!(direction_to_cube_face(vector(0.1, 0.1, 1.0)).0, 4);
+        assert_eq!(direction_to_cube_face(vector(0.1, 0.1, -1.0)).0, 5);
+        let (_, u, v) = direction_to_cube_face(vector(0.0, 0.0, 1.0));
+        assert!(eq_f64(u, 0.5) && eq_f64(v, 0.5))
+    }
+
+    #[test]
+    fn cube_map_samples_the_right_face() {
+        let mut faces = [0.0, 0.0, 0.0, 0.0, 0.0, 0.0].map(|v| uniform(2, 2, v));
+        faces[3] = uniform(2, 2, 0.75);
+        let env = Environment::CubeMap(Box::new(faces));
+        let c = sample_environment(&env, vector(0.2, -1.0, 0.3));
+        assert!(eq_f64(c.1, 0.75))
+    }
+
+    #[test]
+    fn only_equirectangular_maps_get_a_sampler() {
+        assert!(importance_sampler(&Environment::Solid(color(1.0, 1.0, 1.0))).is_none());
+        assert!(importance_sampler(&Environment::Equirectangular(uniform(4, 2, 1.0))).is_some());
+    }
+
+    #[test]
+    fn sampler_prefers_bright_texels() {
+        let mut image = uniform(8, 4, 0.0);
+        image.put_pixel(5, 1, Rgb([100.0, 100.0, 100.0]));
+        let sampler = importance_sampler(&Environment::Equirectangular(image)).unwrap();
+        for i in 0..10 {
+            let r = i as f64 / 10.0;
+            let (d, pdf) = sample_direction(&sampler, r, 1.0 - r);
+            let (u, v) = direction_to_equirectangular(d);
+            assert_eq!((u * 8.0) as usize, 5);
+            assert_eq!((v * 4.0) as usize, 1);
+            assert!(pdf > 0.0);
+        }
+    }
+
+    #[test]
+    fn samples_spread_across_their_texel() {
+        let sampler =
+            importance_sampler(&Environment::Equirectangular(uniform(1, 1, 1.0))).unwrap();
+        let us: Vec<f64> = [0.1, 0.5, 0.9]
+            .iter()
+            .map(|r| direction_to_equirectangular(sample_direction(&sampler, *r, 0.5).0).0)
+            .collect();
+        assert!((us[0] - 0.1).abs() < 1e-9);
+        assert!((us[1] - 0.5).abs() < 1e-9);
+        assert!((us[2] - 0.9).abs() < 1e-9);
+
+        let mut image = uniform(8, 4, 0.0);
+        image.put_pixel(5, 1, Rgb([100.0, 100.0, 100.0]));
+        let sampler = importance_sampler(&Environment::Equirectangular(image)).unwrap();
+        let (low, _) = sample_direction(&sampler, 0.0, 0.0);
+        let (high, _) = sample_direction(&sampler, 0.99, 0.99);
+        assert!(!eq_tup(low, high));
+    }
+
+    #[test]
+    fn empty_maps_have_no_sampler() {
+        let env = Environment::Equirectangular(uniform(0, 0, 1.0));
+        assert!(importance_sampler(&env).is_none());
+        let c = sample_environment(&env, vector(0.0, 0.0, -1.0));
+        assert!(eq_f64(c.0, 0.0));
+    }
+
+    #[test]
+    fn uniform_sampler_pdf_integrates_to_one() {
+        let sampler =
+            importance_sampler(&Environment::Equirectangular(uniform(16, 8, 1.0))).unwrap();
+        let (w, h) = (16, 8);
+        let mut integral = 0.0;
+        for y in 0..h {
+            for x in 0..w {
+                let (u, v) = ((x as f64 + 0.5) / w as f64, (y as f64 + 0.5) / h as f64);
+                let d = equirectangular_to_direction(u, v);
+                let solid_angle = (2.0 * PI / w as f64) * (PI / h as f64) * (v * PI).sin();
+                integral += direction_pdf(&sampler, d) * solid_angle;
+            }
+        }
+        assert!((integral - 1.0).abs() < 0.01)
+    }
+}
@@ -0,0 +1,191 @@
+//!
+//! An embedded 5x7 bitmap font for labelling images. It covers printable ASCII with
+//! lowercase letters drawn as capitals, plus the degree sign; anything else draws as '?'.
+//!
+
+use crate::draw::blend_pixel;
+use image::{ImageBuffer, Rgb, Rgba};
+
+pub const GLYPH_WIDTH: u32 = 5;
+pub const GLYPH_HEIGHT: u32 = 7;
+
+///
+/// The gap after each glyph, in font pixels
+///
+const SPACING: u32 = 1;
+
+///
+/// The rows of every glyph from ' ' to '_', top first, with the leftmost column in bit 4
+///
+const GLYPHS: [[u8; 7]; 64] = [
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00], // ' '
+    [0x04, 0x04, 0x04, 0x04, 0x04, 0x00, 0x04], // '!'
+    [0x0A, 0x0A, 0x0A, 0x00, 0x00, 0x00, 0x00], // '"'
+    [0x0A, 0x0A, 0x1F, 0x0A, 0x1F, 0x0A, 0x0A], // '#'
+    [0x04, 0x0F, 0x14, 0x0E, 0x05, 0x1E, 0x04], // '$'
+    [0x18, 0x19, 0x02, 0x04, 0x08, 0x13, 0x03], // '%'
+    [0x0C, 0x12, 0x14, 0x08, 0x15, 0x12, 0x0D], // '&'
+    [0x0C, 0x04, 0x08, 0x00, 0x00, 0x00, 0x00], // '\''
+    [0x02, 0x04, 0x08, 0x08, 0x08, 0x04, 0x02], // '('
+    [0x08, 0x04, 0x02, 0x02, 0x02, 0x04, 0x08], // ')'
+    [0x00, 0x04, 0x15, 0x0E, 0x15, 0x04, 0x00], // '*'
+    [0x00, 0x04, 0x04, 0x1F, 0x04, 0x04, 0x00], // '+'
+    [0x00, 0x00, 0x00, 0x00, 0x0C, 0x04, 0x08], // ','
+    [0x00, 0x00, 0x00, 0x1F, 0x00, 0x00, 0x00], // '-'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x0C, 0x0C], // '.'
+    [0x00, 0x01, 0x02, 0x04, 0x08, 0x10, 0x00], // '/'
+    [0x0E, 0x11, 0x13, 0x15, 0x19, 0x11, 0x0E], // '0'
+    [0x04, 0x0C, 0x04, 0x04, 0x04, 0x04, 0x0E], // '1'
+    [0x0E, 0x11, 0x01, 0x02, 0x04, 0x08, 0x1F], // '2'
+    [0x1F, 0x02, 0x04, 0x02, 0x01, 0x11, 0x0E], // '3'
+    [0x02, 0x06, 0x0A, 0x12, 0x1F, 0x02, 0x02], // '4'
+    [0x1F, 0x10, 0x1E, 0x01, 0x01, 0x11, 0x0E], // '5'
+    [0x06, 0x08, 0x10, 0x1E, 0x11, 0x11, 0x0E], // '6'
+    [0x1F, 0x01, 0x02, 0x04, 0x08, 0x08, 0x08], // '7'
+    [0x0E, 0x11, 0x11, 0x0E, 0x11, 0x11, 0x0E], // '8'
+    [0x0E, 0x11, 0x11, 0x0F, 0x01, 0x02, 0x0C], // '9'
+    [0x00, 0x0C, 0x0C, 0x00, 0x0C, 0x0C, 0x00], // ':'
+    [0x00, 0x0C, 0x0C, 0x00, 0x0C, 0x04, 0x08], // ';'
+    [0x02, 0x04, 0x08, 0x10, 0x08, 0x04, 0x02], // '<'
+    [0x00, 0x00, 0x1F, 0x00, 0x1F, 0x00, 0x00], // '='
+    [0x08, 0x04, 0x02, 0x01, 0x02, 0x04, 0x08], // '>'
+    [0x0E, 0x11, 0x01, 0x02, 0x04, 0x00, 0x04], // '?'
+    [0x0E, 0x11, 0x01, 0x0D, 0x15, 0x15, 0x0E], // '@'
+    [0x0E, 0x11, 0x11, 0x11, 0x1F, 0x11, 0x11], // 'A'
+    [0x1E, 0x11, 0x11, 0x1E, 0x11, 0x11, 0x1E], // 'B'
+    [0x0E, 0x11, 0x10, 0x10, 0x10, 0x11, 0x0E], // 'C'
+    [0x1C, 0x12, 0x11, 0x11, 0x11, 0x12, 0x1C], // 'D'
+    [0x1F, 0x10, 0x10, 0x1E, 0x10, 0x10, 0x1F], // 'E'
+    [0x1F, 0x10, 0x10, 0x1E, 0x10, 0x10, 0x10], // 'F'
+    [0x0E, 0x11, 0x10, 0x17, 0x11, 0x11, 0x0F], // 'G'
+    [0x11, 0x11, 0x11, 0x1F, 0x11, 0x11, 0x11], // 'H'
+    [0x0E, 0x04, 0x04, 0x04, 0x04, 0x04, 0x0E], // 'I'
+    [0x07, 0x02, 0x02, 0x02, 0x02, 0x12, 0x0C], // 'J'
+    [0x11, 0x12, 0x14, 0x18, 0x14, 0x12, 0x11], // 'K'
+    [0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x1F], // 'L'
+    [0x11, 0x1B, 0x15, 0x15, 0x11, 0x11, 0x11], // 'M'
+    [0x11, 0x11, 0x19, 0x15, 0x13, 0x11, 0x11], // 'N'
+    [0x0E, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0E], // 'O'
+    [0x1E, 0x11, 0x11, 0x1E, 0x10, 0x10, 0x10], // 'P'
+    [0x0E, 0x11, 0x11, 0x11, 0x15, 0x12, 0x0D], // 'Q'
+    [0x1E, 0x11, 0x11, 0x1E, 0x14, 0x12, 0x11], // 'R'
+    [0x0F, 0x10, 0x10, 0x0E, 0x01, 0x01, 0x1E], // 'S'
+    [0x1F, 0x04, 0x04, 0x04, 0x04, 0x04, 0x04], // 'T'
+    [0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0E], // 'U'
+    [0x11, 0x11, 0x11, 0x11, 0x11, 0x0A, 0x04], // 'V'
+    [0x11, 0x11, 0x11, 0x15, 0x15, 0x15, 0x0A], // 'W'
+    [0x11, 0x11, 0x0A, 0x04, 0x0A, 0x11, 0x11], // 'X'
+    [0x11, 0x11, 0x11, 0x0A, 0x04, 0x04, 0x04], // 'Y'
+    [0x1F, 0x01, 0x02, 0x04, 0x08, 0x10, 0x1F], // 'Z'
+    [0x0E, 0x08, 0x08, 0x08, 0x08, 0x08, 0x0E], // '['
+    [0x00, 0x10, 0x08, 0x04, 0x02, 0x01, 0x00], // '\\'
+    [0x0E, 0x02, 0x02, 0x02, 0x02, 0x02, 0x0E], // ']'
+    [0x04, 0x0A, 0x11, 0x00, 0x00, 0x00, 0x00], // '^'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x1F], // '_'
+];
+
+const DEGREE: [u8; 7] = [0x0C, 0x12, 0x12, 0x0C, 0x00, 0x00, 0x00];
+
+///
+/// The rows of a character's glyph, top first, with the leftmost column in bit 4
+///
+pub fn glyph(c: char) -> [u8; 7] {
+    match c.to_ascii_uppercase() {
+        '°' => DEGREE,
+        c @ ' '..='_' => GLYPHS[c as usize - ' ' as usize],
+        _ => GLYPHS['?' as usize - ' ' as usize],
+    }
+}
+
+///
+/// The width and height in pixels of one line of text drawn `scale` pixels per font pixel
+///
+pub fn text_size(text: &str, scale: u32) -> (u32, u32) {
+    let count = text.chars().count() as u32;
+    let width = (count * (GLYPH_WIDTH + SPACING)).saturating_sub(SPACING);
+    (width * scale, GLYPH_HEIGHT * scale)
+}
+
+///
+/// Draws one line of text with its top left corner at `origin`, clipped to the canvas
+///
+pub fn draw_text(
+    canvas: &mut ImageBuffer<Rgb<u8>, Vec<u8>>,
+    origin: (i32, i32),
+    text: &str,
+    scale: u32,
+    color: Rgba<u8>,
+) {
+    let scale = scale as i32;
+    let advance = (GLYPH_WIDTH + SPACING) as i32 * scale;
+    for (i, c) in text.chars().enumerate() {
+        let left = origin.0 + i as i32 * advance;
+        for (row, bits) in glyph(c).iter().enumerate() {
+            for column in 0..GLYPH_WIDTH as i32 {
+                if bits & (0x10 >> column) == 0 {
+                    continue;
+                }
+                let (x, y) = (left + column * scale, origin.1 + row as i32 * scale);
+                for dy in 0..scale {
+                    for dx in 0..scale {
+                        blend_pixel(canvas, x + dx, y + dy, color, 1.0);
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::canvas::new_image_buffer;
+
+    const WHITE: Rgba<u8> = Rgba([255, 255, 255, 255]);
+
+    fn lit(canvas: &ImageBuffer<Rgb<u8>, Vec<u8>>) -> usize {
+        canvas.pixels().filter(|p| p.0 != [0, 0, 0]).count()
+    }
+
+    #[test]
+    fn lowercase_draws_as_capitals_and_unknowns_as_a_question_mark() {
+        assert_eq!(glyph('a'), glyph('A'));
+        assert_eq!(glyph('é'), glyph('?'));
+        assert_ne!(glyph('°'), glyph('?'));
+    }
+
+    #[test]
+    fn text_size_leaves_no_trailing_gap() {
+        assert_eq!(text_size("", 1), (0, 7));
+        assert_eq!(text_size("A", 1), (5, 7));
+        assert_eq!(text_size("10", 2), (22, 14));
+    }
+
+    #[test]
+    fn text_is_drawn_at_its_origin() {
+        let mut canvas = new_image_buffer(20, 10);
+        draw_text(&mut canvas, (2, 1), "1", 1, WHITE);
+        // the foot of a '1' is three pixels wide
+        for x in 3..6 {
+            assert_eq!(canvas.get_pixel(x, 7).0, [255, 255, 255]);
+        }
+        assert_eq!(canvas.get_pixel(2, 7).0, [0, 0, 0]);
+        assert_eq!(lit(&canvas), 10);
+    }
+
+    #[test]
+    fn scaled_text_covers_more_pixels() {
+        let mut small = new_image_buffer(40, 20);
+        draw_text(&mut small, (0, 0), "8", 1, WHITE);
+        let mut large = new_image_buffer(40, 20);
+        draw_text(&mut large, (0, 0), "8", 2, WHITE);
+        assert_eq!(lit(&large), 4 * lit(&small));
+    }
+
+    #[test]
+    fn text_is_clipped_to_the_canvas() {
+        let mut canvas = new_image_buffer(4, 4);
+        draw_text(&mut canvas, (-2, -3), "W", 3, WHITE);
+        assert!(lit(&canvas) > 0);
+    }
+}
@@ -0,0 +1,74 @@
+use crate::sphere::Sphere;
+
+///
+/// The distance t along a ray at which it crosses an object
+///
+#[derive(Clone, Copy, Debug)]
+pub struct Intersection<'a> {
+    pub t: f64,
+    pub object: &'a Sphere,
+}
+
+///
+/// Creates an intersection at t with an object
+///
+pub fn intersection(t: f64, object: &Sphere) -> Intersection<'_> {
+    Intersection { t, object }
+}
+
+///
+/// Finds the visible intersection: the one with the lowest non-negative t
+///
+pub fn hit<'a, 'b>(xs: &'b [Intersection<'a>]) -> Option<&'b Intersection<'a>> {
+    xs.iter()
+        .filter(|i| i.t >= 0.0)
+        .min_by(|a, b| a.t.total_cmp(&b.t))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sphere::sphere;
+    use crate::tuple::eq_f64;
+
+    #[test]
+    fn intersection_holds_t_and_object() {
+        let s = sphere();
+        let i = intersection(3.5, &s);
+        assert!(eq_f64(i.t, 3.5));
+        assert!(std::ptr::eq(i.object, &s));
+    }
+
+    #[test]
+    fn hit_when_all_positive() {
+        let s = sphere();
+        let xs = [intersection(1.0, &s), intersection(2.0, &s)];
+        assert!(eq_f64(hit(&xs).unwrap().t, 1.0));
+    }
+
+    #[test]
+    fn hit_when_some_negative() {
+        let s = sphere();
+        let xs = [intersection(-1.0, &s), intersection(1.0, &s)];
+        assert!(eq_f64(hit(&xs).unwrap().t, 1.0));
+    }
+
+    #[test]
+    fn no_hit_when_all_negative() {
+        let s = sphere();
+        let xs = [intersection(-2.0, &s), intersection(-1.0, &s)];
+        assert!(hit(&xs).is_none());
+    }
+
+    #[test]
+    fn hit_is_lowest_non_negative() {
+        let s = sphere();
+        let xs = [
+            intersection(5.0, &s),
+            intersection(7.0, &s),
+            intersection(-3.0, &s),
+            intersection(2.0, &s),
+        ];
+        assert!(eq_f64(hit(&xs).unwrap().t, 2.0));
+    }
+}
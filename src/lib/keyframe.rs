@@ -0,0 +1,324 @@
+use crate::color::Color;
+use crate::matrix::Matrix4x4;
+use crate::transform::{chain, scaling, translation};
+use crate::tuple::Tuple;
+use nalgebra::{UnitQuaternion, Vector3};
+
+pub const EASINGS: [&str; 6] = [
+    "linear",
+    "smoothstep",
+    "ease",
+    "ease-in",
+    "ease-out",
+    "ease-in-out",
+];
+
+///
+/// How time is reshaped between two keyframes
+///
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Easing {
+    Linear,
+    /// 3t² - 2t³, which starts and stops with zero speed
+    SmoothStep,
+    /// A CSS style cubic Bézier from (0, 0) to (1, 1) with control points (x1, y1) and (x2, y2)
+    Bezier(f64, f64, f64, f64),
+}
+
+///
+/// A value that can be blended between two keyframes, `t` running from 0 to 1
+///
+pub trait Interpolate: Copy {
+    fn interpolate(a: Self, b: Self, t: f64) -> Self;
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Keyframe<T> {
+    pub time: f64,
+    pub value: T,
+    /// The easing of the segment from this keyframe to the next
+    pub easing: Easing,
+}
+
+///
+/// Keyframes sorted by time; sampling between them eases and interpolates, and sampling
+/// outside of them holds the first or last value
+///
+#[derive(Clone, Debug, PartialEq)]
+pub struct Track<T> {
+    keys: Vec<Keyframe<T>>,
+}
+
+///
+/// A transform split into parts that interpolate well: rotations slerp instead of having
+/// their matrices blended, which would shear and shrink the object in between
+///
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TransformKey {
+    pub scale: Tuple,
+    pub rotation: UnitQuaternion<f64>,
+    pub translation: Tuple,
+}
+
+///
+/// Parses one of `EASINGS`; the CSS names map to their standard Bézier curves
+///
+pub fn easing_from_name(name: &str) -> Option<Easing> {
+    match name {
+        "linear" => Some(Easing::Linear),
+        "smoothstep" => Some(Easing::SmoothStep),
+        "ease" => Some(Easing::Bezier(0.25, 0.1, 0.25, 1.0)),
+        "ease-in" => Some(Easing::Bezier(0.42, 0.0, 1.0, 1.0)),
+        "ease-out" => Some(Easing::Bezier(0.0, 0.0, 0.58, 1.0)),
+        "ease-in-out" => Some(Easing::Bezier(0.42, 0.0, 0.58, 1.0)),
+        _ => None,
+    }
+}
+
+///
+/// Reshapes `t` in 0..1 with an easing, clamping values outside of that range
+///
+pub fn ease(easing: Easing, t: f64) -> f64 {
+    let t = t.clamp(0.0, 1.0);
+    match easing {
+        Easing::Linear => t,
+        Easing::SmoothStep => t * t * (3.0 - 2.0 * t),
+        Easing::Bezier(x1, y1, x2, y2) => {
+            let s = solve_bezier(x1, x2, t);
+            bezier(y1, y2, s)
+        }
+    }
+}
+
+// one coordinate of a cubic Bézier whose end points are 0 and 1
+fn bezier(p1: f64, p2: f64, s: f64) -> f64 {
+    let r = 1.0 - s;
+    3.0 * r * r * s * p1 + 3.0 * r * s * s * p2 + s * s * s
+}
+
+// finds the curve parameter whose x is `x`; x only grows along the curve since the control
+// points are kept within 0..1, so bisection always converges
+fn solve_bezier(x1: f64, x2: f64, x: f64) -> f64 {
+    let (x1, x2) = (x1.clamp(0.0, 1.0), x2.clamp(0.0, 1.0));
+    let (mut lo, mut hi) = (0.0, 1.0);
+    for _ in 0..64 {
+        let mid = (lo + hi) / 2.0;
+        if bezier(x1, x2, mid) < x {
+            lo = mid;
+        } else {
+            hi = mid;
+        }
+    }
+    (lo + hi) / 2.0
+}
+
+///
+/// Creates a keyframe
+///
+pub fn keyframe<T>(time: f64, value: T, easing: Easing) -> Keyframe<T> {
+    Keyframe {
+        time,
+        value,
+        easing,
+    }
+}
+
+///
+/// Creates a track from keyframes in any order
+///
+pub fn track<T>(mut keys: Vec<Keyframe<T>>) -> Track<T> {
+    keys.sort_by(|a, b| a.time.total_cmp(&b.time));
+    Track { keys }
+}
+
+impl<T: Interpolate> Track<T> {
+    ///
+    /// The value at a time, or None if the track has no keyframes
+    ///
+    pub fn sample(&self, time: f64) -> Option<T> {
+        let first = self.keys.first()?;
+        let last = self.keys.last()?;
+        if time <= first.time {
+            return Some(first.value);
+        }
+        if time >= last.time {
+            return Some(last.value);
+        }
+        let next = self.keys.iter().position(|k| k.time > time)?;
+        let (a, b) = (&self.keys[next - 1], &self.keys[next]);
+        let t = (time - a.time) / (b.time - a.time);
+        Some(T::interpolate(a.value, b.value, ease(a.easing, t)))
+    }
+}
+
+impl Interpolate for f64 {
+    fn interpolate(a: Self, b: Self, t: f64) -> Self {
+        a + (b - a) * t
+    }
+}
+
+impl Interpolate for Tuple {
+    fn interpolate(a: Self, b: Self, t: f64) -> Self {
+        (
+            f64::interpolate(a.0, b.0, t),
+            f64::interpolate(a.1, b.1, t),
+            f64::interpolate(a.2, b.2, t),
+            f64::interpolate(a.3, b.3, t),
+        )
+    }
+}
+
+impl Interpolate for Color {
+    fn interpolate(a: Self, b: Self, t: f64) -> Self {
+        (
+            f64::interpolate(a.0, b.0, t),
+            f64::interpolate(a.1, b.1, t),
+            f64::interpolate(a.2, b.2, t),
+        )
+    }
+}
+
+impl Interpolate for UnitQuaternion<f64> {
+    fn interpolate(a: Self, b: Self, t: f64) -> Self {
+        // nlerp covers the opposite rotations slerp can't pick a path between
+        a.try_slerp(&b, t, 1.0e-9).unwrap_or_else(|| a.nlerp(&b, t))
+    }
+}
+
+impl Interpolate for TransformKey {
+    fn interpolate(a: Self, b: Self, t: f64) -> Self {
+        TransformKey {
+            scale: Tuple::interpolate(a.scale, b.scale, t),
+            rotation: UnitQuaternion::interpolate(a.rotation, b.rotation, t),
+            translation: Tuple::interpolate(a.translation, b.translation, t),
+        }
+    }
+}
+
+///
+/// The rotation of rotating around x, then y, then z, by angles in radians
+///
+pub fn rotation_from_euler(x: f64, y: f64, z: f64) -> UnitQuaternion<f64> {
+    UnitQuaternion::from_axis_angle(&Vector3::z_axis(), z)
+        * UnitQuaternion::from_axis_angle(&Vector3::y_axis(), y)
+        * UnitQuaternion::from_axis_angle(&Vector3::x_axis(), x)
+}
+
+impl TransformKey {
+    ///
+    /// The matrix that scales, then rotates, then translates
+    ///
+    pub fn to_matrix(&self) -> Matrix4x4 {
+        let (s, t) = (self.scale, self.translation);
+        chain(&[
+            scaling(s.0, s.1, s.2),
+            self.rotation.to_homogeneous(),
+            translation(t.0, t.1, t.2),
+        ])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transform::{rotation_x, rotation_y, rotation_z, transform};
+    use crate::tuple::{eq_f64, eq_tup, point, vector};
+    use std::f64::consts::{FRAC_PI_2, FRAC_PI_4, PI};
+
+    #[test]
+    fn easing_names_parse() {
+        for name in EASINGS {
+            assert!(easing_from_name(name).is_some());
+        }
+        assert!(easing_from_name("bounce").is_none());
+    }
+
+    #[test]
+    fn easings_keep_their_end_points() {
+        for name in EASINGS {
+            let easing = easing_from_name(name).unwrap();
+            assert!(eq_f64(ease(easing, 0.0), 0.0), "{}", name);
+            assert!(eq_f64(ease(easing, 1.0), 1.0), "{}", name);
+            assert!(eq_f64(ease(easing, 2.0), 1.0), "{}", name);
+        }
+    }
+
+    #[test]
+    fn smoothstep_is_slow_at_the_ends() {
+        assert!(eq_f64(ease(Easing::SmoothStep, 0.5), 0.5));
+        assert!(ease(Easing::SmoothStep, 0.1) < 0.1);
+        assert!(ease(Easing::SmoothStep, 0.9) > 0.9);
+    }
+
+    #[test]
+    fn bezier_easings_follow_their_curves() {
+        let in_out = easing_from_name("ease-in-out").unwrap();
+        assert!(eq_f64(ease(in_out, 0.5), 0.5));
+        let ease_in = easing_from_name("ease-in").unwrap();
+        assert!(ease(ease_in, 0.3) < 0.3);
+        // a straight Bézier is linear
+        assert!(eq_f64(
+            ease(Easing::Bezier(0.25, 0.25, 0.75, 0.75), 0.3),
+            0.3
+        ));
+    }
+
+    #[test]
+    fn tracks_interpolate_between_keys_and_hold_outside() {
+        let t = track(vec![
+            keyframe(2.0, 10.0, Easing::Linear),
+            keyframe(0.0, 0.0, Easing::Linear),
+        ]);
+        assert!(eq_f64(t.sample(-1.0).unwrap(), 0.0));
+        assert!(eq_f64(t.sample(0.5).unwrap(), 2.5));
+        assert!(eq_f64(t.sample(3.0).unwrap(), 10.0));
+        assert!(track::<f64>(vec![]).sample(0.0).is_none());
+    }
+
+    #[test]
+    fn each_segment_uses_its_own_easing() {
+        let t = track(vec![
+            keyframe(0.0, (0.0, 0.0, 0.0), Easing::SmoothStep),
+            keyframe(1.0, (1.0, 1.0, 1.0), Easing::Linear),
+            keyframe(2.0, (0.0, 0.0, 0.0), Easing::Linear),
+        ]);
+        let c = t.sample(0.25).unwrap();
+        assert!(eq_f64(c.0, ease(Easing::SmoothStep, 0.25)));
+        assert!(eq_f64(t.sample(1.25).unwrap().1, 0.75));
+    }
+
+    #[test]
+    fn rotations_slerp() {
+        let a = UnitQuaternion::identity();
+        let b = rotation_from_euler(0.0, FRAC_PI_2, 0.0);
+        let half = UnitQuaternion::interpolate(a, b, 0.5);
+        assert!(eq_f64(half.angle(), FRAC_PI_4));
+        let opposite = rotation_from_euler(0.0, PI, 0.0);
+        assert!(UnitQuaternion::interpolate(a, opposite, 0.5)
+            .angle()
+            .is_finite());
+    }
+
+    #[test]
+    fn euler_rotations_apply_x_first() {
+        let m = rotation_from_euler(FRAC_PI_2, FRAC_PI_4, 0.3).to_homogeneous();
+        let expected = chain(&[
+            rotation_x(FRAC_PI_2),
+            rotation_y(FRAC_PI_4),
+            rotation_z(0.3),
+        ]);
+        let p = point(1.0, 2.0, 3.0);
+        assert!(eq_tup(transform(&m, p), transform(&expected, p)));
+    }
+
+    #[test]
+    fn transform_keys_scale_rotate_then_translate() {
+        let key = TransformKey {
+            scale: vector(2.0, 2.0, 2.0),
+            rotation: rotation_from_euler(0.0, 0.0, FRAC_PI_2),
+            translation: vector(1.0, 0.0, 0.0),
+        };
+        let p = transform(&key.to_matrix(), point(1.0, 0.0, 0.0));
+        assert!(eq_tup(p, point(1.0, 2.0, 0.0)));
+    }
+}
@@ -0,0 +1,192 @@
+use crate::ray::{position, ray_at_time, Ray};
+use crate::tuple::{add_tup, dot_product, mul_tup, normalize, sub_tup, Tuple};
+use std::f64::consts::{FRAC_PI_4, PI};
+
+///
+/// The shape of the aperture, which is also the shape out-of-focus highlights take
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Aperture {
+    Disk,
+    /// A regular polygon with this many blades, at least 3
+    Polygon(u32),
+}
+
+///
+/// A thin lens: rays leave from a point on an aperture `radius` across and meet again on the
+/// plane `focus` units ahead, so only that plane is sharp
+///
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Lens {
+    pub radius: f64,
+    pub focus: f64,
+    pub aperture: Aperture,
+}
+
+///
+/// Creates a thin lens with a round aperture
+///
+pub fn lens(radius: f64, focus: f64) -> Lens {
+    Lens {
+        radius,
+        focus,
+        aperture: Aperture::Disk,
+    }
+}
+
+///
+/// Maps two uniform samples in [0, 1) to a point on the unit aperture, spread evenly over
+/// its area. Disks use the concentric mapping, which keeps neighbouring samples together
+///
+pub fn sample_aperture(aperture: Aperture, r1: f64, r2: f64) -> (f64, f64) {
+    match aperture {
+        Aperture::Disk => {
+            let (a, b) = (2.0 * r1 - 1.0, 2.0 * r2 - 1.0);
+            if a == 0.0 && b == 0.0 {
+                return (0.0, 0.0);
+            }
+            let (r, phi) = if a.abs() > b.abs() {
+                (a, FRAC_PI_4 * (b / a))
+            } else {
+                (b, PI / 2.0 - FRAC_PI_4 * (a / b))
+            };
+            (r * phi.cos(), r * phi.sin())
+        }
+        Aperture::Polygon(blades) => {
+            // pick one of the triangles fanning out from the center, then a point inside it
+            let blades = blades.max(3);
+            let scaled = r1 * blades as f64;
+            let i = (scaled.floor() as u32).min(blades - 1);
+            let (s, t) = ((scaled - i as f64).sqrt(), r2);
+            let corner = |k: u32| {
+                let angle = 2.0 * PI * k as f64 / blades as f64 + PI / 2.0;
+                (angle.cos(), angle.sin())
+            };
+            let (a, b) = (corner(i), corner(i + 1));
+            (
+                s * ((1.0 - t) * a.0 + t * b.0),
+                s * ((1.0 - t) * a.1 + t * b.1),
+            )
+        }
+    }
+}
+
+///
+/// Turns a pinhole camera's ray into one through a thin lens. The ray leaves from the lens
+/// point picked by (r1, r2), in the plane spanned by `right` and `up`, and passes through
+/// the point the pinhole ray reaches on the focal plane `lens.focus` units along `forward`,
+/// at the same time
+///
+pub fn thin_lens_ray(
+    pinhole: &Ray,
+    forward: Tuple,
+    right: Tuple,
+    up: Tuple,
+    lens: &Lens,
+    r1: f64,
+    r2: f64,
+) -> Ray {
+    let along = dot_product(pinhole.direction, normalize(forward));
+    let focal_point = position(pinhole, lens.focus / along);
+    let (u, v) = sample_aperture(lens.aperture, r1, r2);
+    let origin = add_tup(
+        pinhole.origin,
+        add_tup(
+            mul_tup(normalize(right), u * lens.radius),
+            mul_tup(normalize(up), v * lens.radius),
+        ),
+    );
+    ray_at_time(
+        origin,
+        normalize(sub_tup(focal_point, origin)),
+        pinhole.time,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tuple::{eq_f64, eq_tup, magnitude, point, vector};
+
+    const SAMPLES: [(f64, f64); 6] = [
+        (0.0, 0.0),
+        (0.1, 0.9),
+        (0.5, 0.5),
+        (0.7, 0.2),
+        (0.99, 0.99),
+        (0.33, 0.01),
+    ];
+
+    fn pinhole() -> Ray {
+        ray_at_time(
+            point(0.0, 0.0, 0.0),
+            normalize(vector(0.3, -0.2, -1.0)),
+            0.4,
+        )
+    }
+
+    fn axes() -> (Tuple, Tuple, Tuple) {
+        (
+            vector(0.0, 0.0, -1.0),
+            vector(1.0, 0.0, 0.0),
+            vector(0.0, 1.0, 0.0),
+        )
+    }
+
+    #[test]
+    fn a_closed_aperture_is_a_pinhole() {
+        let (forward, right, up) = axes();
+        let r = thin_lens_ray(&pinhole(), forward, right, up, &lens(0.0, 3.0), 0.8, 0.3);
+        assert!(eq_tup(r.origin, pinhole().origin));
+        assert!(eq_tup(r.direction, pinhole().direction));
+    }
+
+    #[test]
+    fn rays_meet_on_the_focal_plane() {
+        let (forward, right, up) = axes();
+        let focus = 4.0;
+        let sharp = position(
+            &pinhole(),
+            focus / dot_product(pinhole().direction, forward),
+        );
+        for aperture in [Aperture::Disk, Aperture::Polygon(6)] {
+            let l = Lens {
+                radius: 0.5,
+                focus,
+                aperture,
+            };
+            for (r1, r2) in SAMPLES {
+                let r = thin_lens_ray(&pinhole(), forward, right, up, &l, r1, r2);
+                assert!(eq_f64(r.origin.2, 0.0));
+                assert!(eq_f64(magnitude(r.direction), 1.0));
+                let t = focus / dot_product(r.direction, forward);
+                assert!(eq_tup(position(&r, t), sharp));
+                assert_eq!(r.time, pinhole().time);
+            }
+        }
+    }
+
+    #[test]
+    fn disk_samples_fill_the_unit_disk() {
+        for (r1, r2) in SAMPLES {
+            let (u, v) = sample_aperture(Aperture::Disk, r1, r2);
+            assert!(u * u + v * v <= 1.0 + 1e-9);
+        }
+        let (u, v) = sample_aperture(Aperture::Disk, 0.99, 0.5);
+        assert!(u * u + v * v > 0.9);
+    }
+
+    #[test]
+    fn polygon_samples_stay_inside_the_polygon() {
+        let blades = 5;
+        // a point is inside a regular polygon when it's within the apothem along every edge normal
+        let apothem = (PI / blades as f64).cos();
+        for (r1, r2) in SAMPLES {
+            let (u, v) = sample_aperture(Aperture::Polygon(blades), r1, r2);
+            for k in 0..blades {
+                let angle = 2.0 * PI * (k as f64 + 0.5) / blades as f64 + PI / 2.0;
+                assert!(u * angle.cos() + v * angle.sin() <= apothem + 1e-9);
+            }
+        }
+    }
+}
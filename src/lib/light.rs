@@ -0,0 +1,126 @@
+use crate::color::{add_colors, color, multiply_by_color, multiply_by_scalar, Color};
+use crate::material::Material;
+use crate::tuple::{dot_product, neg_tup, normalize, reflect, sub_tup, Tuple};
+
+///
+/// A light with no size, radiating equally in every direction
+///
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PointLight {
+    pub position: Tuple,
+    pub intensity: Color,
+}
+
+///
+/// Creates a point light at a position with an intensity
+///
+pub fn point_light(position: Tuple, intensity: Color) -> PointLight {
+    PointLight {
+        position,
+        intensity,
+    }
+}
+
+///
+/// Shades a point with the Phong reflection model: ambient + diffuse + specular
+///
+pub fn lighting(
+    m: &Material,
+    light: &PointLight,
+    point: Tuple,
+    eyev: Tuple,
+    normalv: Tuple,
+) -> Color {
+    let black = color(0.0, 0.0, 0.0);
+    let effective_color = multiply_by_color(m.color, light.intensity);
+    let lightv = normalize(sub_tup(light.position, point));
+    let ambient = multiply_by_scalar(effective_color, m.ambient);
+
+    let light_dot_normal = dot_product(lightv, normalv);
+    let (diffuse, specular) = if light_dot_normal < 0.0 {
+        // the light is on the other side of the surface
+        (black, black)
+    } else {
+        let diffuse = multiply_by_scalar(effective_color, m.diffuse * light_dot_normal);
+        let reflectv = reflect(neg_tup(lightv), normalv);
+        let reflect_dot_eye = dot_product(reflectv, eyev);
+        let specular = if reflect_dot_eye <= 0.0 {
+            black
+        } else {
+            let factor = reflect_dot_eye.powf(m.shininess);
+            multiply_by_scalar(light.intensity, m.specular * factor)
+        };
+        (diffuse, specular)
+    };
+
+    add_colors(add_colors(ambient, diffuse), specular)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::material;
+    use crate::tuple::{eq_f64, point, vector};
+
+    fn assert_color(c: Color, r: f64, g: f64, b: f64) {
+        assert!(
+            eq_f64(c.0, r) && eq_f64(c.1, g) && eq_f64(c.2, b),
+            "{:?}",
+            c
+        );
+    }
+
+    #[test]
+    fn point_light_has_position_and_intensity() {
+        let light = point_light(point(0.0, 0.0, 0.0), color(1.0, 1.0, 1.0));
+        assert_eq!(light.position, point(0.0, 0.0, 0.0));
+        assert_eq!(light.intensity, color(1.0, 1.0, 1.0));
+    }
+
+    #[test]
+    fn eye_between_light_and_surface() {
+        let eyev = vector(0.0, 0.0, -1.0);
+        let normalv = vector(0.0, 0.0, -1.0);
+        let light = point_light(point(0.0, 0.0, -10.0), color(1.0, 1.0, 1.0));
+        let c = lighting(&material(), &light, point(0.0, 0.0, 0.0), eyev, normalv);
+        assert_color(c, 1.9, 1.9, 1.9);
+    }
+
+    #[test]
+    fn eye_offset_45_degrees() {
+        let r = 2.0_f64.sqrt() / 2.0;
+        let eyev = vector(0.0, r, -r);
+        let normalv = vector(0.0, 0.0, -1.0);
+        let light = point_light(point(0.0, 0.0, -10.0), color(1.0, 1.0, 1.0));
+        let c = lighting(&material(), &light, point(0.0, 0.0, 0.0), eyev, normalv);
+        assert_color(c, 1.0, 1.0, 1.0);
+    }
+
+    #[test]
+    fn light_offset_45_degrees() {
+        let eyev = vector(0.0, 0.0, -1.0);
+        let normalv = vector(0.0, 0.0, -1.0);
+        let light = point_light(point(0.0, 10.0, -10.0), color(1.0, 1.0, 1.0));
+        let c = lighting(&material(), &light, point(0.0, 0.0, 0.0), eyev, normalv);
+        assert_color(c, 0.7364, 0.7364, 0.7364);
+    }
+
+    #[test]
+    fn eye_in_the_reflection_path() {
+        let r = 2.0_f64.sqrt() / 2.0;
+        let eyev = vector(0.0, -r, -r);
+        let normalv = vector(0.0, 0.0, -1.0);
+        let light = point_light(point(0.0, 10.0, -10.0), color(1.0, 1.0, 1.0));
+        let c = lighting(&material(), &light, point(0.0, 0.0, 0.0), eyev, normalv);
+        assert_color(c, 1.6364, 1.6364, 1.6364);
+    }
+
+    #[test]
+    fn light_behind_the_surface() {
+        let eyev = vector(0.0, 0.0, -1.0);
+        let normalv = vector(0.0, 0.0, -1.0);
+        let light = point_light(point(0.0, 0.0, 10.0), color(1.0, 1.0, 1.0));
+        let c = lighting(&material(), &light, point(0.0, 0.0, 0.0), eyev, normalv);
+        assert_color(c, 0.1, 0.1, 0.1);
+    }
+}
@@ -0,0 +1,64 @@
+use crate::color::{color, Color};
+use crate::normal_map::{HeightMap, NormalMap};
+use std::sync::Arc;
+
+pub const DEFAULT_AMBIENT: f64 = 0.1;
+pub const DEFAULT_DIFFUSE: f64 = 0.9;
+pub const DEFAULT_SPECULAR: f64 = 0.9;
+pub const DEFAULT_SHININESS: f64 = 200.0;
+
+///
+/// Fine surface detail that tilts the shading normal without changing the shape.
+/// The maps are shared, so that copies of a material don't copy the images
+///
+#[derive(Clone, Debug, PartialEq)]
+pub enum Perturbation {
+    /// A tangent-space normal map
+    NormalMap(Arc<NormalMap>),
+    /// A height map, and how strongly its slope tilts the normal
+    Bump(Arc<HeightMap>, f64),
+}
+
+///
+/// The Phong reflection attributes of a surface
+///
+#[derive(Clone, Debug, PartialEq)]
+pub struct Material {
+    pub color: Color,
+    pub ambient: f64,
+    pub diffuse: f64,
+    pub specular: f64,
+    pub shininess: f64,
+    pub perturbation: Option<Perturbation>,
+}
+
+///
+/// Creates the default material: white, mostly diffuse, with a tight highlight
+///
+pub fn material() -> Material {
+    Material {
+        color: color(1.0, 1.0, 1.0),
+        ambient: DEFAULT_AMBIENT,
+        diffuse: DEFAULT_DIFFUSE,
+        specular: DEFAULT_SPECULAR,
+        shininess: DEFAULT_SHININESS,
+        perturbation: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tuple::eq_f64;
+
+    #[test]
+    fn default_material() {
+        let m = material();
+        assert_eq!(m.color, color(1.0, 1.0, 1.0));
+        assert!(eq_f64(m.ambient, 0.1));
+        assert!(eq_f64(m.diffuse, 0.9));
+        assert!(eq_f64(m.specular, 0.9));
+        assert!(eq_f64(m.shininess, 200.0));
+        assert!(m.perturbation.is_none());
+    }
+}
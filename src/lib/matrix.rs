@@ -8,10 +8,8 @@ pub type Matrix4x1 = SMatrix<f64, 4, 1>;
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::lib::tuple::{eq_f64, point, Tuple, EPSILON};
-    use crate::programs::arch::M;
-    use nalgebra::Matrix;
-    use std::ops::{Mul, Sub};
+    use crate::tuple::{eq_f64, point, Tuple};
+    use std::ops::Mul;
 
     fn sub_matrix_4x4(m: &Matrix4x4, row: usize, col: usize) -> Matrix3x3 {
         m.remove_row(row).remove_column(col)
@@ -53,7 +51,7 @@ mod tests {
 
     #[test]
     fn matrix_constructs_properly_4x4() {
-        let mut m = Matrix4x4::new(
+        let m = Matrix4x4::new(
             1.0, 2.0, 3.0, 4.0, 5.5, 6.5, 7.5, 8.5, 9.0, 10.0, 11.0, 12.0, 13.5, 14.5, 15.5, 16.5,
         );
 
@@ -68,7 +66,7 @@ mod tests {
 
     #[test]
     fn matrix_constructs_properly_3x3() {
-        let mut m = Matrix3x3::new(-3.0, 5.0, 0.0, 1.0, -2.0, -7.0, 0.0, 1.0, 1.0);
+        let m = Matrix3x3::new(-3.0, 5.0, 0.0, 1.0, -2.0, -7.0, 0.0, 1.0, 1.0);
 
         assert!(eq_f64(m[(0, 0)], -3.0));
         assert!(eq_f64(m[(1, 1)], -2.0));
@@ -78,7 +76,7 @@ mod tests {
 
     #[test]
     fn matrix_constructs_properly_2x2() {
-        let mut m = Matrix2x2::new(-3.0, 5.0, 1.0, -2.0);
+        let m = Matrix2x2::new(-3.0, 5.0, 1.0, -2.0);
 
         assert!(eq_f64(m[(0, 0)], -3.0));
         assert!(eq_f64(m[(0, 1)], 5.0));
@@ -88,30 +86,30 @@ mod tests {
 
     #[test]
     fn matrices_are_equal() {
-        let mut m1 = Matrix2x2::new(-3.0, 5.0, 1.0, -2.0);
-        let mut m2 = Matrix2x2::new(-3.0, 5.0, 1.0, -2.0);
+        let m1 = Matrix2x2::new(-3.0, 5.0, 1.0, -2.0);
+        let m2 = Matrix2x2::new(-3.0, 5.0, 1.0, -2.0);
         assert!(m1.relative_eq(&m2, f64::EPSILON, f64::EPSILON))
     }
 
     #[test]
     fn matrices_are_not_equal() {
-        let mut m1 = Matrix2x2::new(-2.95, 5.0, 1.0, -2.0);
-        let mut m2 = Matrix2x2::new(-3.0, 5.0, 1.0, -2.0);
+        let m1 = Matrix2x2::new(-2.95, 5.0, 1.0, -2.0);
+        let m2 = Matrix2x2::new(-3.0, 5.0, 1.0, -2.0);
         assert!(!m1.relative_eq(&m2, f64::EPSILON, f64::EPSILON))
     }
 
     #[test]
     fn matrix_multiplication_4x4() {
-        let mut m1 = Matrix4x4::new(
+        let m1 = Matrix4x4::new(
             1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0, 8.0, 9.0, 8.0, 7.0, 6.0, 5.0, 4.0, 3.0, 2.0,
         );
-        let mut m2 = Matrix4x4::new(
+        let m2 = Matrix4x4::new(
             -2.0, 1.0, 2.0, 3.0, 3.0, 2.0, 1.0, -1.0, 4.0, 3.0, 6.0, 5.0, 1.0, 2.0, 7.0, 8.0,
         );
 
         let m3 = m1.mul(m2);
 
-        let mut expected = Matrix4x4::new(
+        let expected = Matrix4x4::new(
             20.0, 22.0, 50.0, 48.0, 44.0, 54.0, 114.0, 108.0, 40.0, 58.0, 110.0, 102.0, 16.0, 26.0,
             46.0, 42.0,
         );
@@ -121,18 +119,18 @@ mod tests {
 
     #[test]
     fn matrix_multiplication_4x4_and_4x1() {
-        let mut m1 = Matrix4x4::new(
+        let m1 = Matrix4x4::new(
             1.0, 2.0, 3.0, 4.0, 2.0, 4.0, 4.0, 2.0, 8.0, 6.0, 4.0, 1.0, 0.0, 0.0, 0.0, 1.0,
         );
-        let mut m2 = Matrix4x1::new(1.0, 2.0, 3.0, 1.0);
+        let m2 = Matrix4x1::new(1.0, 2.0, 3.0, 1.0);
         let m3 = m1.mul(m2);
-        let mut expected = Matrix4x1::new(18.0, 24.0, 33.0, 1.0);
+        let expected = Matrix4x1::new(18.0, 24.0, 33.0, 1.0);
         assert!(expected.relative_eq(&m3, f64::EPSILON, f64::EPSILON));
     }
 
     #[test]
     fn matrix_identity() {
-        let mut m1 = Matrix4x4::new(
+        let m1 = Matrix4x4::new(
             1.0, 2.0, 3.0, 4.0, 2.0, 4.0, 4.0, 2.0, 8.0, 6.0, 4.0, 1.0, 0.0, 0.0, 0.0, 1.0,
         );
         let m2 = m1.mul(Matrix4x4::identity());
@@ -141,26 +139,26 @@ mod tests {
 
     #[test]
     fn matrix_transpose() {
-        let mut m1 = Matrix4x4::new(
+        let m1 = Matrix4x4::new(
             1.0, 2.0, 3.0, 4.0, 2.0, 4.0, 4.0, 2.0, 8.0, 6.0, 4.0, 1.0, 0.0, 0.0, 0.0, 1.0,
         );
         let m2 = m1.transpose();
-        let mut expected = Matrix4x4::new(
+        let expected = Matrix4x4::new(
             1.0, 2.0, 8.0, 0.0, 2.0, 4.0, 6.0, 0.0, 3.0, 4.0, 4.0, 0.0, 4.0, 2.0, 1.0, 1.0,
         );
-        assert!(m2.relative_eq(&m2, f64::EPSILON, f64::EPSILON));
+        assert!(expected.relative_eq(&m2, f64::EPSILON, f64::EPSILON));
     }
 
     #[test]
     fn matrix_determinant() {
-        let mut m1 = Matrix2x2::new(1.0, 5.0, -3.0, 2.0);
+        let m1 = Matrix2x2::new(1.0, 5.0, -3.0, 2.0);
         let det = m1.determinant();
         assert!(eq_f64(17.0, det));
     }
 
     #[test]
     fn matrix_resize_3x3() {
-        let mut m1 = Matrix3x3::new(1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0, 8.0, 9.0);
+        let m1 = Matrix3x3::new(1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0, 8.0, 9.0);
         let m2 = sub_matrix_3x3(&m1, 0, 0);
         let m3 = Matrix2x2::new(5.0, 6.0, 8.0, 9.0);
         assert!(m3.relative_eq(&m2, f64::EPSILON, f64::EPSILON));
@@ -168,7 +166,7 @@ mod tests {
 
     #[test]
     fn matrix_resize_4x4() {
-        let mut m1 = Matrix4x4::new(
+        let m1 = Matrix4x4::new(
             1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0, 8.0, 9.0, 10.0, 11.0, 12.0, 13.0, 14.0, 15.0, 16.0,
         );
         let m2 = sub_matrix_4x4(&m1, 0, 0);
@@ -178,7 +176,7 @@ mod tests {
 
     #[test]
     fn minor_4x4() {
-        let mut m1 = Matrix4x4::new(
+        let m1 = Matrix4x4::new(
             1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0, 8.0, 9.0, 10.0, 11.0, 12.0, 13.0, 14.0, 15.0, 16.0,
         );
         let m2 = sub_matrix_4x4(&m1, 0, 0);
@@ -187,7 +185,7 @@ mod tests {
 
     #[test]
     fn determinant_4x4() {
-        let mut m1 = Matrix4x4::new(
+        let m1 = Matrix4x4::new(
             -2.0, -8.0, 3.0, 5.0, -3.0, 1.0, 7.0, 3.0, 1.0, 2.0, -9.0, 6.0, -6.0, 7.0, 7.0, -9.0,
         );
         assert!(eq_f64(-4071.0, m1.determinant()));
@@ -195,7 +193,7 @@ mod tests {
 
     #[test]
     fn determinant_of_4x4_is_zero() {
-        let mut m1 = Matrix4x4::new(
+        let m1 = Matrix4x4::new(
             -4.0, 2.0, -2.0, -3.0, 9.0, 6.0, 2.0, 6.0, 0.0, -5.0, 1.0, -5.0, 0.0, 0.0, 0.0, 0.0,
         );
         assert!(eq_f64(0.0, m1.determinant()));
@@ -204,7 +202,7 @@ mod tests {
 
     #[test]
     fn determinant_of_4x4_is_not_zero() {
-        let mut m1 = Matrix4x4::new(
+        let m1 = Matrix4x4::new(
             6.0, 4.0, 4.0, 4.0, 5.0, 5.0, 7.0, 6.0, 4.0, -9.0, 3.0, -7.0, 9.0, 1.0, 7.0, -6.0,
         );
         let a = -2120.0;
@@ -215,7 +213,7 @@ mod tests {
 
     #[test]
     fn inverse_of_4x4() {
-        let mut a = Matrix4x4::new(
+        let a = Matrix4x4::new(
             -5.0, 2.0, 6.0, -8.0, 1.0, -5.0, 1.0, 8.0, 7.0, 7.0, -6.0, -7.0, 1.0, -3.0, 7.0, 4.0,
         );
 
@@ -230,7 +228,7 @@ mod tests {
 
     #[test]
     fn inverse_of_4x4_example_two() {
-        // let mut a = Matrix4x4::new(8.0, -5.0);
+        // let a = Matrix4x4::new(8.0, -5.0);
 
         // let b = a.try_inverse();
         // assert!(b.is_some());
@@ -243,7 +241,7 @@ mod tests {
 
     #[test]
     fn inverse_of_4x4_example_three() {
-        let mut a = Matrix4x4::new(
+        let a = Matrix4x4::new(
             8.0, -5.0, 9.0, 2.0, 7.0, 5.0, 6.0, 1.0, -6.0, 0.0, 9.0, 6.0, -3.0, 0.0, -9.0, -4.0,
         );
 
@@ -261,11 +259,11 @@ mod tests {
 
     #[test]
     fn product_inverse() {
-        let mut a = Matrix4x4::new(
+        let a = Matrix4x4::new(
             3.0, -9.0, 7.0, 3.0, 3.0, -8.0, 2.0, -9.0, -4.0, 4.0, 4.0, 1.0, -6.0, 5.0, -1.0, 1.0,
         );
 
-        let mut b = Matrix4x4::new(
+        let b = Matrix4x4::new(
             8.0, 2.0, 2.0, 2.0, 3.0, -1.0, 7.0, 0.0, 7.0, 0.0, 5.0, 4.0, 6.0, -2.0, 0.0, 5.0,
         );
 
@@ -1,4 +1,38 @@
+//!
+//! The math, color and canvas building blocks of the ray tracer.
+//!
+//! `rtc` is built on top of this crate, and other crates can depend on it directly.
+//! Everything reachable from the module tree is public API; [`prelude`] gathers the
+//! items most programs need so they can be brought in with a single `use`.
+//!
+
 pub mod canvas;
+pub mod chart;
 pub mod color;
+pub mod denoise;
+pub mod draw;
+pub mod environment;
+pub mod font;
+pub mod intersection;
+pub mod keyframe;
+pub mod lens;
+pub mod light;
+pub mod material;
 pub mod matrix;
+pub mod noise;
+pub mod normal_map;
+pub mod output;
+pub mod prelude;
+pub mod projectile;
+pub mod projection;
+pub mod ray;
+pub mod sampling;
+pub mod sphere;
+pub mod stereo;
+pub mod trajectory;
+pub mod transform;
 pub mod tuple;
+
+pub use color::Color;
+pub use matrix::{Matrix2x2, Matrix3x3, Matrix4x1, Matrix4x4};
+pub use tuple::Tuple;
@@ -0,0 +1,340 @@
+use crate::color::{add_colors, multiply_by_scalar, Color};
+use crate::tuple::{add_tup, normalize, vector, Tuple};
+
+pub const PERMUTATION_SIZE: usize = 256;
+pub const DEFAULT_SEED: u64 = 0x5EED;
+pub const DEFAULT_LACUNARITY: f64 = 2.0;
+pub const DEFAULT_GAIN: f64 = 0.5;
+
+///
+/// A shuffled table of 0..256, doubled so lookups never need to wrap
+///
+pub type Permutation = [usize; PERMUTATION_SIZE * 2];
+
+///
+/// The 12 edge directions of a cube, used as the gradients of the noise lattice
+///
+const GRADIENTS: [(f64, f64, f64); 12] = [
+    (1.0, 1.0, 0.0),
+    (-1.0, 1.0, 0.0),
+    (1.0, -1.0, 0.0),
+    (-1.0, -1.0, 0.0),
+    (1.0, 0.0, 1.0),
+    (-1.0, 0.0, 1.0),
+    (1.0, 0.0, -1.0),
+    (-1.0, 0.0, -1.0),
+    (0.0, 1.0, 1.0),
+    (0.0, -1.0, 1.0),
+    (0.0, 1.0, -1.0),
+    (0.0, -1.0, -1.0),
+];
+
+///
+/// Creates a permutation table from a seed; the same seed always yields the same table
+///
+pub fn permutation(seed: u64) -> Permutation {
+    let mut table = [0; PERMUTATION_SIZE];
+    for (i, v) in table.iter_mut().enumerate() {
+        *v = i;
+    }
+
+    // xorshift64*, which only needs a non-zero state
+    let mut state = seed ^ 0x9E37_79B9_7F4A_7C15;
+    if state == 0 {
+        state = 0x9E37_79B9_7F4A_7C15;
+    }
+    for i in (1..PERMUTATION_SIZE).rev() {
+        state ^= state >> 12;
+        state ^= state << 25;
+        state ^= state >> 27;
+        let j = (state.wrapping_mul(0x2545_F491_4F6C_DD1D) % (i as u64 + 1)) as usize;
+        table.swap(i, j);
+    }
+
+    let mut perm = [0; PERMUTATION_SIZE * 2];
+    for i in 0..perm.len() {
+        perm[i] = table[i % PERMUTATION_SIZE];
+    }
+    perm
+}
+
+///
+/// The quintic fade curve 6t^5 - 15t^4 + 10t^3
+///
+fn fade(t: f64) -> f64 {
+    t * t * t * (t * (t * 6.0 - 15.0) + 10.0)
+}
+
+fn lerp(t: f64, a: f64, b: f64) -> f64 {
+    a + t * (b - a)
+}
+
+fn grad(hash: usize, x: f64, y: f64, z: f64) -> f64 {
+    let (gx, gy, gz) = GRADIENTS[hash % GRADIENTS.len()];
+    gx * x + gy * y + gz * z
+}
+
+///
+/// 3D gradient (Perlin) noise at point p, roughly in the range -1..1 and 0 on every lattice point
+///
+pub fn noise(perm: &Permutation, p: Tuple) -> f64 {
+    let (fx, fy, fz) = (p.0.floor(), p.1.floor(), p.2.floor());
+    let xi = (fx as i64 & 255) as usize;
+    let yi = (fy as i64 & 255) as usize;
+    let zi = (fz as i64 & 255) as usize;
+    let (x, y, z) = (p.0 - fx, p.1 - fy, p.2 - fz);
+    let (u, v, w) = (fade(x), fade(y), fade(z));
+
+    let a = perm[xi] + yi;
+    let aa = perm[a] + zi;
+    let ab = perm[a + 1] + zi;
+    let b = perm[xi + 1] + yi;
+    let ba = perm[b] + zi;
+    let bb = perm[b + 1] + zi;
+
+    lerp(
+        w,
+        lerp(
+            v,
+            lerp(u, grad(perm[aa], x, y, z), grad(perm[ba], x - 1.0, y, z)),
+            lerp(
+                u,
+                grad(perm[ab], x, y - 1.0, z),
+                grad(perm[bb], x - 1.0, y - 1.0, z),
+            ),
+        ),
+        lerp(
+            v,
+            lerp(
+                u,
+                grad(perm[aa + 1], x, y, z - 1.0),
+                grad(perm[ba + 1], x - 1.0, y, z - 1.0),
+            ),
+            lerp(
+                u,
+                grad(perm[ab + 1], x, y - 1.0, z - 1.0),
+                grad(perm[bb + 1], x - 1.0, y - 1.0, z - 1.0),
+            ),
+        ),
+    )
+}
+
+///
+/// Fractal Brownian motion: sums `octaves` layers of noise, each one scaled in frequency
+/// by `lacunarity` and in amplitude by `gain`. The result is normalized back into -1..1
+///
+pub fn fbm(perm: &Permutation, p: Tuple, octaves: u32, lacunarity: f64, gain: f64) -> f64 {
+    let (mut sum, mut total, mut amplitude, mut frequency) = (0.0, 0.0, 1.0, 1.0);
+    for _ in 0..octaves {
+        sum += amplitude * noise(perm, scale_point(p, frequency));
+        total += amplitude;
+        amplitude *= gain;
+        frequency *= lacunarity;
+    }
+    if total == 0.0 {
+        0.0
+    } else {
+        sum / total
+    }
+}
+
+///
+/// Like fbm but sums the absolute value of each octave, giving the creased look of turbulence.
+/// The result is normalized into 0..1
+///
+pub fn turbulence(perm: &Permutation, p: Tuple, octaves: u32, lacunarity: f64, gain: f64) -> f64 {
+    let (mut sum, mut total, mut amplitude, mut frequency) = (0.0, 0.0, 1.0, 1.0);
+    for _ in 0..octaves {
+        sum += amplitude * noise(perm, scale_point(p, frequency)).abs();
+        total += amplitude;
+        amplitude *= gain;
+        frequency *= lacunarity;
+    }
+    if total == 0.0 {
+        0.0
+    } else {
+        sum / total
+    }
+}
+
+///
+/// Offsets a point by a noise field so that any pattern sampled at the result gets jittered
+///
+pub fn perturb_point(perm: &Permutation, p: Tuple, scale: f64) -> Tuple {
+    add_tup(p, noise_vector(perm, p, scale))
+}
+
+///
+/// Perturbs a surface normal at point p by a noise field, returning a normalized vector
+///
+pub fn perturb_normal(perm: &Permutation, normal: Tuple, p: Tuple, scale: f64) -> Tuple {
+    normalize(add_tup(normal, noise_vector(perm, p, scale)))
+}
+
+///
+/// Veined marble: a sine wave along x, distorted by turbulence
+///
+pub fn marble(perm: &Permutation, p: Tuple, a: Color, b: Color) -> Color {
+    let t =
+        (p.0 + 5.0 * turbulence(perm, p, 6, DEFAULT_LACUNARITY, DEFAULT_GAIN)).sin() * 0.5 + 0.5;
+    blend(a, b, t)
+}
+
+///
+/// Wood grain: concentric rings around the y axis, warped by low frequency noise
+///
+pub fn wood(perm: &Permutation, p: Tuple, a: Color, b: Color) -> Color {
+    let r = (p.0 * p.0 + p.2 * p.2).sqrt() + 0.5 * noise(perm, p);
+    let rings = r * 8.0;
+    blend(a, b, rings - rings.floor())
+}
+
+///
+/// Soft clouds: fbm remapped into 0..1 and used to mix between sky and cloud colors
+///
+pub fn clouds(perm: &Permutation, p: Tuple, sky: Color, cloud: Color) -> Color {
+    let t = fbm(perm, p, 6, DEFAULT_LACUNARITY, DEFAULT_GAIN) * 0.5 + 0.5;
+    blend(sky, cloud, t.clamp(0.0, 1.0))
+}
+
+fn blend(a: Color, b: Color, t: f64) -> Color {
+    add_colors(multiply_by_scalar(a, 1.0 - t), multiply_by_scalar(b, t))
+}
+
+fn scale_point(p: Tuple, s: f64) -> Tuple {
+    (p.0 * s, p.1 * s, p.2 * s, p.3)
+}
+
+///
+/// Three decorrelated noise samples packed into a vector, used to push points and normals around
+///
+fn noise_vector(perm: &Permutation, p: Tuple, scale: f64) -> Tuple {
+    let x = noise(perm, p);
+    let y = noise(perm, (p.0 + 31.4, p.1 + 47.2, p.2 + 12.9, p.3));
+    let z = noise(perm, (p.0 - 19.1, p.1 + 7.3, p.2 - 53.7, p.3));
+    vector(x * scale, y * scale, z * scale)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::color;
+    use crate::tuple::{eq_f64, is_point, is_vector, magnitude, point};
+
+    #[test]
+    fn permutation_is_a_shuffle() {
+        let perm = permutation(DEFAULT_SEED);
+        let mut seen = [false; PERMUTATION_SIZE];
+        for v in &perm[..PERMUTATION_SIZE] {
+            seen[*v] = true;
+        }
+        assert!(seen.iter().all(|s| *s));
+        assert_eq!(perm[..PERMUTATION_SIZE], perm[PERMUTATION_SIZE..]);
+    }
+
+    #[test]
+    fn same_seed_same_noise() {
+        let p = point(1.3, -2.7, 0.45);
+        let a = noise(&permutation(42), p);
+        let b = noise(&permutation(42), p);
+        assert!(eq_f64(a, b))
+    }
+
+    #[test]
+    fn different_seed_different_noise() {
+        let p = point(1.3, -2.7, 0.45);
+        let a = noise(&permutation(1), p);
+        let b = noise(&permutation(2), p);
+        assert!(!eq_f64(a, b))
+    }
+
+    #[test]
+    fn noise_is_zero_on_lattice_points() {
+        let perm = permutation(DEFAULT_SEED);
+        assert!(eq_f64(0.0, noise(&perm, point(0.0, 0.0, 0.0))));
+        assert!(eq_f64(0.0, noise(&perm, point(3.0, -7.0, 12.0))));
+    }
+
+    #[test]
+    fn noise_stays_in_range() {
+        let perm = permutation(DEFAULT_SEED);
+        for i in 0..1000 {
+            let t = i as f64 * 0.173;
+            let n = noise(&perm, point(t, t * 0.7 - 3.0, t * 1.3 + 5.0));
+            assert!((-1.0..=1.0).contains(&n));
+        }
+    }
+
+    #[test]
+    fn fbm_and_turbulence_stay_in_range() {
+        let perm = permutation(DEFAULT_SEED);
+        for i in 0..200 {
+            let p = point(i as f64 * 0.31, i as f64 * 0.11, 2.0);
+            let f = fbm(&perm, p, 5, DEFAULT_LACUNARITY, DEFAULT_GAIN);
+            let t = turbulence(&perm, p, 5, DEFAULT_LACUNARITY, DEFAULT_GAIN);
+            assert!((-1.0..=1.0).contains(&f));
+            assert!((0.0..=1.0).contains(&t));
+        }
+    }
+
+    #[test]
+    fn turbulence_follows_its_gain() {
+        let perm = permutation(DEFAULT_SEED);
+        let p = point(1.3, 0.4, -2.2);
+        // with no gain every octave past the first is silent
+        assert!(eq_f64(
+            noise(&perm, p).abs(),
+            turbulence(&perm, p, 6, DEFAULT_LACUNARITY, 0.0)
+        ));
+        assert!(!eq_f64(
+            turbulence(&perm, p, 6, DEFAULT_LACUNARITY, 0.9),
+            turbulence(&perm, p, 6, DEFAULT_LACUNARITY, 0.1)
+        ));
+    }
+
+    #[test]
+    fn zero_octaves_is_flat() {
+        let perm = permutation(DEFAULT_SEED);
+        let p = point(0.5, 0.5, 0.5);
+        assert!(eq_f64(
+            0.0,
+            fbm(&perm, p, 0, DEFAULT_LACUNARITY, DEFAULT_GAIN)
+        ));
+        assert!(eq_f64(
+            0.0,
+            turbulence(&perm, p, 0, DEFAULT_LACUNARITY, DEFAULT_GAIN)
+        ));
+    }
+
+    #[test]
+    fn perturbed_normal_is_normalized() {
+        let perm = permutation(DEFAULT_SEED);
+        let n = perturb_normal(&perm, vector(0.0, 1.0, 0.0), point(0.3, 0.2, 0.9), 0.4);
+        assert!(is_vector(n));
+        assert!(eq_f64(1.0, magnitude(n)))
+    }
+
+    #[test]
+    fn perturbed_point_is_a_point() {
+        let perm = permutation(DEFAULT_SEED);
+        let p = perturb_point(&perm, point(0.3, 0.2, 0.9), 0.4);
+        assert!(is_point(p))
+    }
+
+    #[test]
+    fn patterns_blend_between_their_colors() {
+        let perm = permutation(DEFAULT_SEED);
+        let (a, b) = (color(0.0, 0.0, 0.0), color(1.0, 1.0, 1.0));
+        for i in 0..100 {
+            let p = point(i as f64 * 0.37, 0.5, i as f64 * 0.19);
+            for c in [
+                marble(&perm, p, a, b),
+                wood(&perm, p, a, b),
+                clouds(&perm, p, a, b),
+            ] {
+                assert!((0.0..=1.0).contains(&c.0));
+                assert!(eq_f64(c.0, c.1) && eq_f64(c.1, c.2));
+            }
+        }
+    }
+}
@@ -0,0 +1,352 @@
+use crate::material::Perturbation;
+use crate::tuple::{
+    add_tup, cross_product, dot_product, magnitude, mul_tup, normalize, sub_tup, vector, Tuple,
+    EPSILON,
+};
+use image::{ImageBuffer, Luma, Rgb};
+use std::f64::consts::PI;
+
+pub type NormalMap = ImageBuffer<Rgb<u8>, Vec<u8>>;
+
+///
+/// A grayscale image of surface heights, brighter being higher
+///
+pub type HeightMap = ImageBuffer<Luma<f32>, Vec<f32>>;
+
+///
+/// (tangent, bitangent, normal): an orthonormal basis where tangent points along +u and
+/// bitangent along +v of the surface parameterization
+///
+pub type TangentFrame = (Tuple, Tuple, Tuple);
+
+///
+/// Texture coordinates, each in the range 0..1
+///
+pub type Uv = (f64, f64);
+
+const BUMP_DELTA: f64 = 0.001;
+
+///
+/// Decodes a tangent-space normal map texel, mapping each channel from 0..255 into -1..1
+///
+pub fn decode_normal(pixel: Rgb<u8>) -> Tuple {
+    let [r, g, b] = pixel.0;
+    let channel = |c: u8| c as f64 / 127.5 - 1.0;
+    normalize(vector(channel(r), channel(g), channel(b)))
+}
+
+///
+/// Looks up the tangent-space normal for (u, v), wrapping the coordinates and using
+/// the nearest texel. v = 0 is the bottom row of the image
+///
+pub fn sample_normal_map(map: &NormalMap, uv: Uv) -> Tuple {
+    let (w, h) = map.dimensions();
+    if w == 0 || h == 0 {
+        return vector(0.0, 0.0, 1.0);
+    }
+    let (u, v) = (uv.0.rem_euclid(1.0), uv.1.rem_euclid(1.0));
+    let x = ((u * w as f64) as u32).min(w - 1);
+    let y = (((1.0 - v) * h as f64) as u32).min(h - 1);
+    decode_normal(*map.get_pixel(x, y))
+}
+
+///
+/// Looks up the height at (u, v), wrapping the coordinates and blending the four nearest
+/// texels, so that the slope `bump` measures doesn't vanish inside a texel.
+/// v = 0 is the bottom row of the image
+///
+pub fn sample_height_map(map: &HeightMap, uv: Uv) -> f64 {
+    let (w, h) = map.dimensions();
+    if w == 0 || h == 0 {
+        return 0.0;
+    }
+    let x = uv.0.rem_euclid(1.0) * w as f64 - 0.5;
+    let y = (1.0 - uv.1.rem_euclid(1.0)) * h as f64 - 0.5;
+    let (x0, y0) = (x.floor(), y.floor());
+    let (fx, fy) = (x - x0, y - y0);
+    let texel = |x: f64, y: f64| {
+        let x = (x as i64).rem_euclid(w as i64) as u32;
+        let y = (y as i64).rem_euclid(h as i64) as u32;
+        map.get_pixel(x, y).0[0] as f64
+    };
+    let top = texel(x0, y0) * (1.0 - fx) + texel(x0 + 1.0, y0) * fx;
+    let bottom = texel(x0, y0 + 1.0) * (1.0 - fx) + texel(x0 + 1.0, y0 + 1.0) * fx;
+    top * (1.0 - fy) + bottom * fy
+}
+
+///
+/// Moves a tangent-space normal into the space of the frame, returning a normalized vector
+///
+pub fn apply_normal_map(frame: TangentFrame, tangent_normal: Tuple) -> Tuple {
+    let (t, b, n) = frame;
+    normalize(add_tup(
+        add_tup(mul_tup(t, tangent_normal.0), mul_tup(b, tangent_normal.1)),
+        mul_tup(n, tangent_normal.2),
+    ))
+}
+
+///
+/// Perturbs the frame's normal by the slope of a scalar height field at (u, v).
+/// `strength` scales the height before the slope is taken
+///
+pub fn bump<F>(frame: TangentFrame, height: F, uv: Uv, strength: f64) -> Tuple
+where
+    F: Fn(Uv) -> f64,
+{
+    let (t, b, n) = frame;
+    let h = height(uv);
+    let du = (height((uv.0 + BUMP_DELTA, uv.1)) - h) / BUMP_DELTA * strength;
+    let dv = (height((uv.0, uv.1 + BUMP_DELTA)) - h) / BUMP_DELTA * strength;
+    normalize(sub_tup(sub_tup(n, mul_tup(t, du)), mul_tup(b, dv)))
+}
+
+///
+/// The frame's normal tilted by a material's perturbation at (u, v)
+///
+pub fn apply_perturbation(frame: TangentFrame, perturbation: &Perturbation, uv: Uv) -> Tuple {
+    match perturbation {
+        Perturbation::NormalMap(map) => apply_normal_map(frame, sample_normal_map(map, uv)),
+        Perturbation::Bump(heights, strength) => {
+            bump(frame, |uv| sample_height_map(heights, uv), uv, *strength)
+        }
+    }
+}
+
+///
+/// Spherical (longitude, latitude) coordinates of a point on a unit sphere at the origin
+///
+pub fn spherical_uv(object_point: Tuple) -> Uv {
+    let (x, y, z) = (object_point.0, object_point.1, object_point.2);
+    let radius = (x * x + y * y + z * z).sqrt();
+    let theta = x.atan2(z);
+    let phi = (y / radius).clamp(-1.0, 1.0).acos();
+    (1.0 - (theta / (2.0 * PI) + 0.5), 1.0 - phi / PI)
+}
+
+///
+/// Planar coordinates of a point on the xz plane, repeating every unit
+///
+pub fn planar_uv(object_point: Tuple) -> Uv {
+    (
+        object_point.0.rem_euclid(1.0),
+        object_point.2.rem_euclid(1.0),
+    )
+}
+
+///
+/// Tangent frame of a unit sphere at the origin, with the tangent running around the y axis.
+/// At the poles the tangent falls back to +x
+///
+pub fn sphere_tangent_frame(object_point: Tuple) -> TangentFrame {
+    let n = normalize(vector(object_point.0, object_point.1, object_point.2));
+    let around = vector(object_point.2, 0.0, -object_point.0);
+    let t = if magnitude(around) < EPSILON {
+        vector(1.0, 0.0, 0.0)
+    } else {
+        normalize(around)
+    };
+    let t = mul_tup(t, -1.0);
+    // u runs clockwise when seen from above, so the frame is mirrored relative to the others
+    let b = cross_product(t, n);
+    (t, b, n)
+}
+
+///
+/// Tangent frame of the xz plane, matching the orientation of `planar_uv`
+///
+pub fn plane_tangent_frame() -> TangentFrame {
+    (
+        vector(1.0, 0.0, 0.0),
+        vector(0.0, 0.0, 1.0),
+        vector(0.0, 1.0, 0.0),
+    )
+}
+
+///
+/// Tangent frame of a triangle from its vertices and their texture coordinates.
+/// The tangent is made orthogonal to the face normal; degenerate UVs fall back to the first edge.
+/// Mirrored UVs give a left-handed frame, so the bitangent still points along +v
+///
+pub fn triangle_tangent_frame(
+    p1: Tuple,
+    p2: Tuple,
+    p3: Tuple,
+    uv1: Uv,
+    uv2: Uv,
+    uv3: Uv,
+) -> TangentFrame {
+    let e1 = sub_tup(p2, p1);
+    let e2 = sub_tup(p3, p1);
+    let n = normalize(cross_product(e1, e2));
+    let (du1, dv1) = (uv2.0 - uv1.0, uv2.1 - uv1.1);
+    let (du2, dv2) = (uv3.0 - uv1.0, uv3.1 - uv1.1);
+    let det = du1 * dv2 - du2 * dv1;
+
+    let t = if det.abs() < EPSILON {
+        e1
+    } else {
+        mul_tup(sub_tup(mul_tup(e1, dv2), mul_tup(e2, dv1)), 1.0 / det)
+    };
+    let t = normalize(sub_tup(t, mul_tup(n, dot_product(n, t))));
+    let handedness = if det < -EPSILON { -1.0 } else { 1.0 };
+    let b = mul_tup(cross_product(n, t), handedness);
+    (t, b, n)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tuple::{eq_f64, eq_tup, point};
+    use std::sync::Arc;
+
+    fn is_orthonormal(frame: TangentFrame) -> bool {
+        let (t, b, n) = frame;
+        eq_f64(magnitude(t), 1.0)
+            && eq_f64(magnitude(b), 1.0)
+            && eq_f64(magnitude(n), 1.0)
+            && eq_f64(dot_product(t, b), 0.0)
+            && eq_f64(dot_product(t, n), 0.0)
+            && eq_f64(dot_product(b, n), 0.0)
+    }
+
+    #[test]
+    fn flat_texel_decodes_to_up() {
+        let n = decode_normal(Rgb([128, 128, 255]));
+        assert!((n.2 - 1.0).abs() < 0.001);
+        assert!(n.0.abs() < 0.01 && n.1.abs() < 0.01);
+    }
+
+    #[test]
+    fn flat_normal_map_keeps_the_normal() {
+        let frame = sphere_tangent_frame(point(0.0, 0.0, 1.0));
+        let n = apply_normal_map(frame, vector(0.0, 0.0, 1.0));
+        assert!(eq_tup(n, vector(0.0, 0.0, 1.0)))
+    }
+
+    #[test]
+    fn sample_normal_map_wraps() {
+        let mut map = NormalMap::new(2, 1);
+        map.put_pixel(0, 0, Rgb([255, 128, 128]));
+        map.put_pixel(1, 0, Rgb([128, 128, 255]));
+        let left = sample_normal_map(&map, (0.25, 0.5));
+        let wrapped = sample_normal_map(&map, (1.25, 0.5));
+        assert!(eq_tup(left, wrapped));
+        assert!(left.0 > 0.9)
+    }
+
+    #[test]
+    fn height_maps_blend_between_texels() {
+        let mut map = HeightMap::new(2, 1);
+        map.put_pixel(1, 0, Luma([1.0]));
+        assert!(eq_f64(sample_height_map(&map, (0.25, 0.5)), 0.0));
+        assert!(eq_f64(sample_height_map(&map, (0.5, 0.5)), 0.5));
+        assert!(eq_f64(sample_height_map(&map, (0.75, 0.5)), 1.0));
+        // wrapping round from the last texel back to the first
+        assert!(eq_f64(sample_height_map(&map, (1.0, 0.5)), 0.5));
+        assert!(eq_f64(
+            sample_height_map(&HeightMap::new(0, 0), (0.5, 0.5)),
+            0.0
+        ));
+    }
+
+    #[test]
+    fn perturbations_tilt_the_normal() {
+        let frame = plane_tangent_frame();
+        let mut normals = NormalMap::new(1, 1);
+        normals.put_pixel(0, 0, Rgb([255, 128, 128]));
+        let n = apply_perturbation(
+            frame,
+            &Perturbation::NormalMap(Arc::new(normals)),
+            (0.5, 0.5),
+        );
+        assert!(n.0 > 0.9);
+
+        let ramp = HeightMap::from_fn(4, 1, |x, _| Luma([x as f32]));
+        let n = apply_perturbation(frame, &Perturbation::Bump(Arc::new(ramp), 0.01), (0.5, 0.5));
+        assert!(n.0 < 0.0 && n.1 > 0.0);
+    }
+
+    #[test]
+    fn constant_height_does_not_bump() {
+        let frame = plane_tangent_frame();
+        let n = bump(frame, |_| 3.0, (0.2, 0.7), 1.0);
+        assert!(eq_tup(n, vector(0.0, 1.0, 0.0)))
+    }
+
+    #[test]
+    fn sloped_height_tilts_against_the_slope() {
+        let frame = plane_tangent_frame();
+        let n = bump(frame, |(u, _)| u, (0.2, 0.7), 1.0);
+        assert!(n.0 < 0.0);
+        assert!(eq_f64(magnitude(n), 1.0))
+    }
+
+    #[test]
+    fn sphere_frames_are_orthonormal() {
+        for p in [
+            point(1.0, 0.0, 0.0),
+            point(0.0, 0.0, -1.0),
+            point(0.0, 1.0, 0.0),
+            normalize(vector(0.3, -0.5, 0.8)),
+        ] {
+            assert!(is_orthonormal(sphere_tangent_frame(p)));
+        }
+    }
+
+    #[test]
+    fn sphere_bitangent_points_north() {
+        let (_, b, _) = sphere_tangent_frame(point(1.0, 0.0, 0.0));
+        assert!(eq_tup(b, vector(0.0, 1.0, 0.0)))
+    }
+
+    #[test]
+    fn plane_frame_is_orthonormal() {
+        assert!(is_orthonormal(plane_tangent_frame()))
+    }
+
+    #[test]
+    fn triangle_frame_follows_uv() {
+        let frame = triangle_tangent_frame(
+            point(0.0, 0.0, 0.0),
+            point(1.0, 0.0, 0.0),
+            point(0.0, 1.0, 0.0),
+            (0.0, 0.0),
+            (1.0, 0.0),
+            (0.0, 1.0),
+        );
+        assert!(is_orthonormal(frame));
+        assert!(eq_tup(frame.0, vector(1.0, 0.0, 0.0)));
+        assert!(eq_tup(frame.1, vector(0.0, 1.0, 0.0)));
+        assert!(eq_tup(frame.2, vector(0.0, 0.0, 1.0)));
+    }
+
+    #[test]
+    fn mirrored_uvs_keep_the_bitangent_along_v() {
+        // u runs right to left across the same triangle
+        let frame = triangle_tangent_frame(
+            point(0.0, 0.0, 0.0),
+            point(1.0, 0.0, 0.0),
+            point(0.0, 1.0, 0.0),
+            (1.0, 0.0),
+            (0.0, 0.0),
+            (1.0, 1.0),
+        );
+        assert!(is_orthonormal(frame));
+        assert!(eq_tup(frame.0, vector(-1.0, 0.0, 0.0)));
+        assert!(eq_tup(frame.1, vector(0.0, 1.0, 0.0)));
+        assert!(eq_tup(frame.2, vector(0.0, 0.0, 1.0)));
+
+        // so a texel leaning toward +v tilts the normal the same way either way round
+        let tilted = apply_normal_map(frame, normalize(vector(0.0, 1.0, 1.0)));
+        assert!(tilted.1 > 0.5);
+    }
+
+    #[test]
+    fn spherical_uv_ranges() {
+        let (u, v) = spherical_uv(point(0.0, 1.0, 0.0));
+        assert!(eq_f64(v, 1.0));
+        assert!((0.0..=1.0).contains(&u));
+        let (_, v) = spherical_uv(point(0.0, -1.0, 0.0));
+        assert!(eq_f64(v, 0.0));
+    }
+}
@@ -0,0 +1,475 @@
+use crate::canvas::{HdrImage, Layers};
+use exr::prelude::{AnyChannel, AnyChannels, FlatSamples, Image, SmallVec, Vec2, WritableImage};
+use image::codecs::gif::{GifEncoder, Repeat};
+use image::codecs::hdr::HdrEncoder;
+use image::codecs::pnm::{PnmSubtype, SampleEncoding};
+use image::error::{EncodingError, ImageFormatHint, UnsupportedError, UnsupportedErrorKind};
+use image::{Delay, DynamicImage, Frame, ImageError, ImageFormat, ImageOutputFormat, ImageResult};
+use std::fs;
+use std::io::Cursor;
+use std::path::Path;
+
+///
+/// Every file extension that can be written, in the order shown to users
+///
+pub const EXTENSIONS: [&str; 10] = [
+    "ppm", "png", "jpg", "jpeg", "bmp", "gif", "tiff", "tif", "exr", "hdr",
+];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OutputFormat {
+    Ppm,
+    Png,
+    Jpeg,
+    Bmp,
+    /// Animated when given several frames
+    Gif,
+    /// 16 bits per channel
+    Tiff,
+    /// 32-bit float OpenEXR
+    Exr,
+    /// Radiance RGBE
+    Hdr,
+}
+
+///
+/// Maps a file extension (without the dot, any case) to the format it is written in
+///
+pub fn format_from_extension(ext: &str) -> Option<OutputFormat> {
+    match ext.to_ascii_lowercase().as_str() {
+        "ppm" => Some(OutputFormat::Ppm),
+        "png" => Some(OutputFormat::Png),
+        "jpg" | "jpeg" => Some(OutputFormat::Jpeg),
+        "bmp" => Some(OutputFormat::Bmp),
+        "gif" => Some(OutputFormat::Gif),
+        "tiff" | "tif" => Some(OutputFormat::Tiff),
+        "exr" => Some(OutputFormat::Exr),
+        "hdr" => Some(OutputFormat::Hdr),
+        _ => None,
+    }
+}
+
+///
+/// Derives the output format from the extension of a path
+///
+pub fn format_from_path<P: AsRef<Path>>(path: P) -> Option<OutputFormat> {
+    path.as_ref()
+        .extension()
+        .and_then(|e| e.to_str())
+        .and_then(format_from_extension)
+}
+
+///
+/// Determines if a format keeps values outside of 0..1 instead of clipping them
+///
+pub fn is_floating_point(format: OutputFormat) -> bool {
+    matches!(format, OutputFormat::Exr | OutputFormat::Hdr)
+}
+
+///
+/// Determines if a format can hold several frames, i.e. GIF and APNG
+///
+pub fn supports_animation(format: OutputFormat) -> bool {
+    matches!(format, OutputFormat::Gif | OutputFormat::Png)
+}
+
+///
+/// Encodes an image into the bytes of a file of the given format, converting the
+/// pixels to the bit depth the format stores
+///
+pub fn encode(image: &DynamicImage, format: OutputFormat) -> ImageResult<Vec<u8>> {
+    let mut bytes = Cursor::new(Vec::new());
+    match format {
+        OutputFormat::Ppm => DynamicImage::ImageRgb8(image.to_rgb8()).write_to(
+            &mut bytes,
+            ImageOutputFormat::Pnm(PnmSubtype::Pixmap(SampleEncoding::Binary)),
+        )?,
+        OutputFormat::Png => {
+            DynamicImage::ImageRgb8(image.to_rgb8()).write_to(&mut bytes, ImageOutputFormat::Png)?
+        }
+        OutputFormat::Jpeg => DynamicImage::ImageRgb8(image.to_rgb8())
+            .write_to(&mut bytes, ImageOutputFormat::Jpeg(90))?,
+        OutputFormat::Bmp => {
+            DynamicImage::ImageRgb8(image.to_rgb8()).write_to(&mut bytes, ImageOutputFormat::Bmp)?
+        }
+        OutputFormat::Gif => DynamicImage::ImageRgba8(image.to_rgba8())
+            .write_to(&mut bytes, ImageOutputFormat::Gif)?,
+        OutputFormat::Tiff => DynamicImage::ImageRgb16(image.to_rgb16())
+            .write_to(&mut bytes, ImageOutputFormat::Tiff)?,
+        OutputFormat::Exr => DynamicImage::ImageRgb32F(image.to_rgb32f())
+            .write_to(&mut bytes, ImageOutputFormat::OpenExr)?,
+        OutputFormat::Hdr => {
+            let hdr = image.to_rgb32f();
+            let (w, h) = hdr.dimensions();
+            let pixels: Vec<_> = hdr.pixels().copied().collect();
+            HdrEncoder::new(&mut bytes).encode(&pixels, w as usize, h as usize)?
+        }
+    }
+    Ok(bytes.into_inner())
+}
+
+///
+/// Encodes a render with its auxiliary buffers. OpenEXR gets them all in one file, with the
+/// color as the usual R, G and B channels, the albedo as albedo.R/G/B, the normal as
+/// normal.X/Y/Z, the depth as Z, the UVs as uv.U/V and the shadow mask and IDs as shadow,
+/// objectId and materialId, which compositing tools show as separate layers.
+/// Every other format only has room for the color
+///
+pub fn encode_layers(layers: &Layers, format: OutputFormat) -> ImageResult<Vec<u8>> {
+    if format != OutputFormat::Exr {
+        return encode(&DynamicImage::ImageRgb32F(layers.color.clone()), format);
+    }
+    let channel = |name: &str, image: &HdrImage, i: usize| {
+        let samples = image.pixels().map(|p| p.0[i]).collect();
+        AnyChannel::new(name, FlatSamples::F32(samples))
+    };
+    let mut channels: SmallVec<[AnyChannel<FlatSamples>; 4]> = SmallVec::new();
+    for (i, name) in ["R", "G", "B"].iter().enumerate() {
+        channels.push(channel(name, &layers.color, i));
+    }
+    if let Some(albedo) = &layers.albedo {
+        for (i, name) in ["albedo.R", "albedo.G", "albedo.B"].iter().enumerate() {
+            channels.push(channel(name, albedo, i));
+        }
+    }
+    if let Some(normal) = &layers.normal {
+        for (i, name) in ["normal.X", "normal.Y", "normal.Z"].iter().enumerate() {
+            channels.push(channel(name, normal, i));
+        }
+    }
+    if let Some(depth) = &layers.depth {
+        channels.push(channel("Z", depth, 0));
+    }
+    if let Some(uv) = &layers.uv {
+        channels.push(channel("uv.U", uv, 0));
+        channels.push(channel("uv.V", uv, 1));
+    }
+    let single = [
+        ("shadow", &layers.shadow),
+        ("objectId", &layers.object_id),
+        ("materialId", &layers.material_id),
+    ];
+    for (name, pass) in single {
+        if let Some(pass) = pass {
+            channels.push(channel(name, pass, 0));
+        }
+    }
+
+    let (w, h) = layers.color.dimensions();
+    let image = Image::from_channels(Vec2(w as usize, h as usize), AnyChannels::sort(channels));
+    let mut bytes = Cursor::new(Vec::new());
+    image.write().to_buffered(&mut bytes).map_err(|e| {
+        ImageError::Encoding(EncodingError::new(
+            ImageFormatHint::Exact(ImageFormat::OpenExr),
+            e,
+        ))
+    })?;
+    Ok(bytes.into_inner())
+}
+
+///
+/// Brings an auxiliary buffer into the 0..1 that formats which clip can hold, so that it can
+/// be looked at: normals are mapped from -1..1 and depths divided by the farthest one.
+/// The other buffers already fit
+///
+pub fn displayable_aov(name: &str, pass: &HdrImage) -> HdrImage {
+    let mut shown = pass.clone();
+    match name {
+        "normal" => shown
+            .pixels_mut()
+            .for_each(|p| p.0 = p.0.map(|v| v * 0.5 + 0.5)),
+        "depth" => {
+            let farthest = pass.pixels().map(|p| p.0[0]).fold(0.0, f32::max);
+            if farthest > 0.0 {
+                shown
+                    .pixels_mut()
+                    .for_each(|p| p.0 = p.0.map(|v| v / farthest));
+            }
+        }
+        _ => {}
+    }
+    shown
+}
+
+///
+/// Encodes frames of the same size into a looping animation shown at `fps` frames per second.
+/// Only GIF and PNG (as APNG) can be animated
+///
+pub fn encode_animation(
+    frames: &[DynamicImage],
+    format: OutputFormat,
+    fps: u32,
+) -> ImageResult<Vec<u8>> {
+    let mut bytes = Vec::new();
+    match format {
+        OutputFormat::Gif => {
+            let mut encoder = GifEncoder::new(&mut bytes);
+            encoder.set_repeat(Repeat::Infinite)?;
+            let delay = Delay::from_numer_denom_ms(1000, fps.max(1));
+            encoder.encode_frames(
+                frames
+                    .iter()
+                    .map(|f| Frame::from_parts(f.to_rgba8(), 0, 0, delay)),
+            )?;
+        }
+        OutputFormat::Png => {
+            let png_error = |e: png::EncodingError| {
+                ImageError::Encoding(EncodingError::new(
+                    ImageFormatHint::Exact(ImageFormat::Png),
+                    e,
+                ))
+            };
+            let (w, h) = frames
+                .first()
+                .map(|f| (f.width(), f.height()))
+                .unwrap_or((0, 0));
+            let mut encoder = png::Encoder::new(&mut bytes, w, h);
+            encoder.set_color(png::ColorType::Rgb);
+            encoder.set_depth(png::BitDepth::Eight);
+            encoder
+                .set_animated(frames.len() as u32, 0)
+                .map_err(png_error)?;
+            encoder
+                .set_frame_delay(1, fps.clamp(1, u16::MAX as u32) as u16)
+                .map_err(png_error)?;
+            let mut writer = encoder.write_header().map_err(png_error)?;
+            for frame in frames {
+                writer
+                    .write_image_data(frame.to_rgb8().as_raw())
+                    .map_err(png_error)?;
+            }
+            writer.finish().map_err(png_error)?;
+        }
+        _ => {
+            return Err(ImageError::Unsupported(
+                UnsupportedError::from_format_and_kind(
+                    ImageFormatHint::Unknown,
+                    UnsupportedErrorKind::GenericFeature(format!("{:?} can't be animated", format)),
+                ),
+            ))
+        }
+    }
+    Ok(bytes)
+}
+
+///
+/// Replaces each `{name}` in a template with its value; unknown placeholders are kept as-is
+///
+pub fn expand_template(template: &str, values: &[(&str, String)]) -> String {
+    values
+        .iter()
+        .fold(template.to_string(), |acc, (name, value)| {
+            acc.replace(&format!("{{{}}}", name), value)
+        })
+}
+
+///
+/// Writes an image to a path in the given format
+///
+pub fn save<P: AsRef<Path>>(
+    image: &DynamicImage,
+    path: P,
+    format: OutputFormat,
+) -> ImageResult<()> {
+    fs::write(path, encode(image, format)?)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{ImageBuffer, Rgb};
+
+    fn gradient() -> DynamicImage {
+        DynamicImage::ImageRgb8(ImageBuffer::from_fn(4, 3, |x, y| {
+            Rgb([(x * 60) as u8, (y * 100) as u8, 128])
+        }))
+    }
+
+    #[test]
+    fn every_extension_has_a_format() {
+        for ext in EXTENSIONS {
+            assert!(format_from_extension(ext).is_some(), "{}", ext);
+        }
+        assert!(format_from_extension("webp").is_none());
+    }
+
+    #[test]
+    fn extensions_are_case_insensitive() {
+        assert_eq!(format_from_extension("PNG"), Some(OutputFormat::Png));
+        assert_eq!(format_from_extension("Tif"), Some(OutputFormat::Tiff));
+    }
+
+    #[test]
+    fn format_from_paths() {
+        assert_eq!(format_from_path("renders/a.b.exr"), Some(OutputFormat::Exr));
+        assert_eq!(format_from_path("out.HDR"), Some(OutputFormat::Hdr));
+        assert_eq!(format_from_path("out"), None);
+    }
+
+    #[test]
+    fn templates_fill_in_placeholders() {
+        let values = [("scene", "arch".to_string()), ("width", "640".to_string())];
+        assert_eq!(
+            expand_template("renders/{scene}_{width}_{width}.png", &values),
+            "renders/arch_640_640.png"
+        );
+        assert_eq!(expand_template("{nope}.png", &values), "{nope}.png");
+    }
+
+    #[test]
+    fn only_exr_and_hdr_are_floating_point() {
+        assert!(is_floating_point(OutputFormat::Exr));
+        assert!(is_floating_point(OutputFormat::Hdr));
+        assert!(!is_floating_point(OutputFormat::Tiff));
+        assert!(!is_floating_point(OutputFormat::Png));
+    }
+
+    #[test]
+    fn every_format_round_trips() {
+        let image = gradient();
+        for ext in EXTENSIONS {
+            let bytes = encode(&image, format_from_extension(ext).unwrap()).unwrap();
+            let decoded = image::load_from_memory(&bytes).unwrap();
+            assert_eq!((decoded.width(), decoded.height()), (4, 3), "{}", ext);
+        }
+    }
+
+    fn animation_frames() -> Vec<DynamicImage> {
+        (0..3)
+            .map(|i| DynamicImage::ImageRgb8(ImageBuffer::from_pixel(4, 3, Rgb([i * 100, 0, 0]))))
+            .collect()
+    }
+
+    #[test]
+    fn gif_animations_keep_every_frame() {
+        use image::codecs::gif::GifDecoder;
+        use image::AnimationDecoder;
+
+        let bytes = encode_animation(&animation_frames(), OutputFormat::Gif, 10).unwrap();
+        let frames = GifDecoder::new(Cursor::new(bytes))
+            .unwrap()
+            .into_frames()
+            .collect_frames()
+            .unwrap();
+        assert_eq!(frames.len(), 3);
+        assert_eq!(frames[2].buffer().get_pixel(0, 0).0[0], 200);
+        assert_eq!(frames[0].delay().numer_denom_ms(), (100, 1));
+    }
+
+    #[test]
+    fn apng_animations_keep_every_frame() {
+        let bytes = encode_animation(&animation_frames(), OutputFormat::Png, 24).unwrap();
+        let decoder = png::Decoder::new(Cursor::new(bytes));
+        let reader = decoder.read_info().unwrap();
+        let animation = reader.info().animation_control().unwrap();
+        assert_eq!(animation.num_frames, 3);
+        assert_eq!(animation.num_plays, 0);
+    }
+
+    #[test]
+    fn only_gif_and_png_animate() {
+        assert!(supports_animation(OutputFormat::Gif));
+        assert!(supports_animation(OutputFormat::Png));
+        assert!(!supports_animation(OutputFormat::Jpeg));
+        assert!(encode_animation(&animation_frames(), OutputFormat::Bmp, 24).is_err());
+    }
+
+    #[test]
+    fn tiff_is_sixteen_bit() {
+        let bytes = encode(&gradient(), OutputFormat::Tiff).unwrap();
+        let decoded = image::load_from_memory(&bytes).unwrap();
+        assert!(matches!(decoded, DynamicImage::ImageRgb16(_)));
+    }
+
+    fn layered() -> Layers {
+        let image = |value: f32| ImageBuffer::from_pixel(3, 2, Rgb([value, value * 2.0, 0.0]));
+        Layers {
+            color: image(4.0),
+            albedo: Some(image(0.25)),
+            normal: Some(image(-1.0)),
+            depth: Some(image(7.5)),
+            shadow: Some(image(1.0)),
+            object_id: Some(image(3.0)),
+            material_id: Some(image(2.0)),
+            uv: Some(image(0.375)),
+        }
+    }
+
+    #[test]
+    fn exr_holds_every_layer() {
+        use exr::prelude::{read, ReadChannels, ReadLayers};
+
+        let bytes = encode_layers(&layered(), OutputFormat::Exr).unwrap();
+        let image = read()
+            .no_deep_data()
+            .largest_resolution_level()
+            .all_channels()
+            .first_valid_layer()
+            .all_attributes()
+            .from_buffered(Cursor::new(bytes))
+            .unwrap();
+        let value = |name: &str| {
+            let channel = image
+                .layer_data
+                .channel_data
+                .list
+                .iter()
+                .find(|c| c.name == *name)
+                .unwrap_or_else(|| panic!("no {} channel", name));
+            channel.sample_data.value_by_flat_index(0).to_f32()
+        };
+        assert_eq!(value("R"), 4.0);
+        assert_eq!(value("G"), 8.0);
+        assert_eq!(value("albedo.G"), 0.5);
+        assert_eq!(value("normal.X"), -1.0);
+        assert_eq!(value("Z"), 7.5);
+        assert_eq!(value("uv.V"), 0.75);
+        assert_eq!(value("shadow"), 1.0);
+        assert_eq!(value("objectId"), 3.0);
+        assert_eq!(value("materialId"), 2.0);
+    }
+
+    #[test]
+    fn aovs_are_brought_into_range_for_display() {
+        let normals = ImageBuffer::from_pixel(1, 1, Rgb([-1.0, 0.0, 1.0]));
+        let shown = displayable_aov("normal", &normals);
+        assert_eq!(shown.get_pixel(0, 0).0, [0.0, 0.5, 1.0]);
+        let depths = ImageBuffer::from_fn(2, 1, |x, _| Rgb([2.0 + 2.0 * x as f32; 3]));
+        let shown = displayable_aov("depth", &depths);
+        assert_eq!(shown.get_pixel(0, 0).0, [0.5; 3]);
+        assert_eq!(shown.get_pixel(1, 0).0, [1.0; 3]);
+        assert_eq!(displayable_aov("uv", &normals), normals);
+        // nothing hit leaves the depths as they are
+        let empty = ImageBuffer::new(2, 2);
+        assert_eq!(displayable_aov("depth", &empty), empty);
+    }
+
+    #[test]
+    fn layered_exr_reads_back_as_its_color() {
+        let bytes = encode_layers(&layered(), OutputFormat::Exr).unwrap();
+        let decoded = image::load_from_memory(&bytes).unwrap().into_rgb32f();
+        assert_eq!(decoded.get_pixel(2, 1).0, [4.0, 8.0, 0.0]);
+    }
+
+    #[test]
+    fn other_formats_only_get_the_color() {
+        use image::codecs::hdr::HdrDecoder;
+
+        let bytes = encode_layers(&layered(), OutputFormat::Hdr).unwrap();
+        let pixels = HdrDecoder::new(Cursor::new(bytes))
+            .unwrap()
+            .read_image_hdr()
+            .unwrap();
+        assert_eq!(pixels.len(), 6);
+        assert!((pixels[0].0[0] - 4.0).abs() < 0.1);
+    }
+
+    #[test]
+    fn exr_keeps_values_above_one() {
+        let image = DynamicImage::ImageRgb32F(ImageBuffer::from_pixel(2, 2, Rgb([4.0, 0.5, 0.0])));
+        let bytes = encode(&image, OutputFormat::Exr).unwrap();
+        let decoded = image::load_from_memory(&bytes).unwrap().into_rgb32f();
+        assert!((decoded.get_pixel(1, 1).0[0] - 4.0).abs() < 0.001);
+    }
+}
@@ -0,0 +1,25 @@
+//!
+//! The commonly used types and functions, for glob importing:
+//!
+//! ```
+//! use ray_tracer::prelude::*;
+//!
+//! let p = add_tup(point(1.0, 2.0, 3.0), vector(1.0, 0.0, 0.0));
+//! assert!(eq_tup(p, point(2.0, 2.0, 3.0)));
+//! ```
+//!
+
+pub use crate::canvas::{new_image_buffer, HdrImage};
+pub use crate::color::{
+    add_colors, color, color_from_hex, color_into_rgb, multiply_by_color, multiply_by_scalar,
+    subtract_colors, Color,
+};
+pub use crate::matrix::{Matrix2x2, Matrix3x3, Matrix4x1, Matrix4x4};
+pub use crate::output::{format_from_extension, format_from_path, save, OutputFormat};
+pub use crate::transform::{
+    chain, rotation_x, rotation_y, rotation_z, scaling, shearing, transform, translation,
+};
+pub use crate::tuple::{
+    add_tup, cross_product, div_tup, dot_product, eq_f64, eq_tup, is_point, is_vector, magnitude,
+    mul_tup, neg_tup, normalize, point, sub_tup, vector, Tuple, EPSILON,
+};
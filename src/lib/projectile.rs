@@ -0,0 +1,277 @@
+use crate::tuple::{add_tup, magnitude, mul_tup, sub_tup, vector, Tuple};
+
+pub const INTEGRATORS: [&str; 4] = ["euler", "semi-implicit", "verlet", "rk4"];
+
+///
+/// How a projectile is stepped forward in time
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Integrator {
+    /// Moves with the old velocity, then updates it. Gains energy over time
+    Euler,
+    /// Updates the velocity, then moves with the new one
+    SemiImplicit,
+    /// Velocity Verlet, which averages the acceleration at both ends of the step
+    Verlet,
+    /// Classic fourth order Runge-Kutta
+    Rk4,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Projectile {
+    pub position: Tuple,
+    pub velocity: Tuple,
+}
+
+///
+/// The forces acting on a projectile. Gravity is a constant acceleration and wind is the
+/// velocity of the air. Drag opposes the projectile's velocity relative to the air, linearly
+/// for slow objects and quadratically for fast ones, so it's only through drag that the wind
+/// pushes the projectile along
+///
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Environment {
+    pub gravity: Tuple,
+    pub wind: Tuple,
+    pub linear_drag: f64,
+    pub quadratic_drag: f64,
+}
+
+///
+/// Parses one of `INTEGRATORS`
+///
+pub fn integrator_from_name(name: &str) -> Option<Integrator> {
+    match name {
+        "euler" => Some(Integrator::Euler),
+        "semi-implicit" => Some(Integrator::SemiImplicit),
+        "verlet" => Some(Integrator::Verlet),
+        "rk4" => Some(Integrator::Rk4),
+        _ => None,
+    }
+}
+
+///
+/// Creates a projectile at a point moving along a vector
+///
+pub fn projectile(position: Tuple, velocity: Tuple) -> Projectile {
+    Projectile { position, velocity }
+}
+
+///
+/// Creates an environment with gravity and wind but no air drag, so the wind has nothing to
+/// push with until some drag is added
+///
+pub fn environment(gravity: Tuple, wind: Tuple) -> Environment {
+    Environment {
+        gravity,
+        wind,
+        linear_drag: 0.0,
+        quadratic_drag: 0.0,
+    }
+}
+
+impl Default for Environment {
+    fn default() -> Self {
+        environment(vector(0.0, -0.1, 0.0), vector(0.0, 0.0, 0.0))
+    }
+}
+
+///
+/// The acceleration a projectile moving at `velocity` feels in the environment
+///
+pub fn acceleration(env: &Environment, velocity: Tuple) -> Tuple {
+    let airspeed = sub_tup(velocity, env.wind);
+    let speed = magnitude(airspeed);
+    let drag = mul_tup(airspeed, env.linear_drag + env.quadratic_drag * speed);
+    sub_tup(env.gravity, drag)
+}
+
+///
+/// Advances the projectile by one tick, updating the velocity before the position
+///
+pub fn tick(env: &Environment, p: &Projectile) -> Projectile {
+    step(env, p, 1.0, Integrator::SemiImplicit)
+}
+
+///
+/// Advances the projectile by `dt` ticks with the given integrator
+///
+pub fn step(env: &Environment, p: &Projectile, dt: f64, integrator: Integrator) -> Projectile {
+    let a = acceleration(env, p.velocity);
+    match integrator {
+        Integrator::Euler => projectile(
+            add_tup(p.position, mul_tup(p.velocity, dt)),
+            add_tup(p.velocity, mul_tup(a, dt)),
+        ),
+        Integrator::SemiImplicit => {
+            let velocity = add_tup(p.velocity, mul_tup(a, dt));
+            projectile(add_tup(p.position, mul_tup(velocity, dt)), velocity)
+        }
+        Integrator::Verlet => {
+            let position = add_tup(
+                p.position,
+                add_tup(mul_tup(p.velocity, dt), mul_tup(a, 0.5 * dt * dt)),
+            );
+            // drag depends on the velocity, so the acceleration at the end of the step is
+            // estimated from an Euler step of the velocity
+            let next = acceleration(env, add_tup(p.velocity, mul_tup(a, dt)));
+            let velocity = add_tup(p.velocity, mul_tup(add_tup(a, next), 0.5 * dt));
+            projectile(position, velocity)
+        }
+        Integrator::Rk4 => {
+            // the acceleration only depends on the velocity, so each stage is a
+            // (velocity, acceleration) pair
+            let v1 = p.velocity;
+            let a1 = a;
+            let v2 = add_tup(p.velocity, mul_tup(a1, dt / 2.0));
+            let a2 = acceleration(env, v2);
+            let v3 = add_tup(p.velocity, mul_tup(a2, dt / 2.0));
+            let a3 = acceleration(env, v3);
+            let v4 = add_tup(p.velocity, mul_tup(a3, dt));
+            let a4 = acceleration(env, v4);
+            let weighted = |k1, k2, k3, k4| {
+                mul_tup(
+                    add_tup(add_tup(k1, mul_tup(add_tup(k2, k3), 2.0)), k4),
+                    dt / 6.0,
+                )
+            };
+            projectile(
+                add_tup(p.position, weighted(v1, v2, v3, v4)),
+                add_tup(p.velocity, weighted(a1, a2, a3, a4)),
+            )
+        }
+    }
+}
+
+///
+/// Where a projectile launched from `start` is after `t` ticks if there were no drag, i.e.
+/// the exact parabola under gravity alone, as without drag the wind can't reach it
+///
+pub fn analytic_position(env: &Environment, start: &Projectile, t: f64) -> Tuple {
+    add_tup(
+        start.position,
+        add_tup(
+            mul_tup(start.velocity, t),
+            mul_tup(env.gravity, 0.5 * t * t),
+        ),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tuple::{eq_f64, eq_tup, point};
+
+    #[test]
+    fn gravity_accelerates_every_tick() {
+        let env = environment(vector(0.0, -0.1, 0.0), vector(-0.01, 0.0, 0.0));
+        let p = tick(
+            &env,
+            &projectile(point(0.0, 1.0, 0.0), vector(1.0, 1.0, 0.0)),
+        );
+        // without drag the wind doesn't touch the projectile
+        assert!(eq_tup(p.velocity, vector(1.0, 0.9, 0.0)));
+        assert!(eq_tup(p.position, point(1.0, 1.9, 0.0)));
+    }
+
+    #[test]
+    fn drag_acts_on_the_velocity_through_the_air() {
+        let mut env = environment(vector(0.0, 0.0, 0.0), vector(2.0, 0.0, 0.0));
+        env.linear_drag = 0.1;
+        // at rest, the wind drags the projectile along
+        let a = acceleration(&env, vector(0.0, 0.0, 0.0));
+        assert!(eq_tup(a, vector(0.2, 0.0, 0.0)));
+        // moving with the wind, there's no drag at all
+        let a = acceleration(&env, vector(2.0, 0.0, 0.0));
+        assert!(eq_tup(a, vector(0.0, 0.0, 0.0)));
+
+        // and the wind can't push it past its own speed
+        let mut p = projectile(point(0.0, 0.0, 0.0), vector(0.0, 0.0, 0.0));
+        for _ in 0..500 {
+            p = tick(&env, &p);
+        }
+        assert!(p.velocity.0 < 2.0 && p.velocity.0 > 1.99);
+    }
+
+    #[test]
+    fn linear_drag_opposes_velocity() {
+        let mut env = environment(vector(0.0, 0.0, 0.0), vector(0.0, 0.0, 0.0));
+        env.linear_drag = 0.1;
+        let a = acceleration(&env, vector(2.0, 0.0, 0.0));
+        assert!(eq_tup(a, vector(-0.2, 0.0, 0.0)));
+    }
+
+    #[test]
+    fn quadratic_drag_grows_with_speed() {
+        let mut env = environment(vector(0.0, 0.0, 0.0), vector(0.0, 0.0, 0.0));
+        env.quadratic_drag = 0.1;
+        let slow = magnitude(acceleration(&env, vector(1.0, 0.0, 0.0)));
+        let fast = magnitude(acceleration(&env, vector(3.0, 0.0, 0.0)));
+        assert!(eq_f64(slow, 0.1));
+        assert!(eq_f64(fast, 0.9));
+    }
+
+    #[test]
+    fn integrator_names_parse() {
+        for name in INTEGRATORS {
+            assert!(integrator_from_name(name).is_some());
+        }
+        assert!(integrator_from_name("leapfrog").is_none());
+    }
+
+    fn error_after(integrator: Integrator, dt: f64) -> f64 {
+        let env = Environment::default();
+        let start = projectile(point(0.0, 0.0, 0.0), vector(3.0, 4.0, 0.0));
+        let mut p = start;
+        let steps = (10.0 / dt).round() as usize;
+        for _ in 0..steps {
+            p = step(&env, &p, dt, integrator);
+        }
+        magnitude(sub_tup(p.position, analytic_position(&env, &start, 10.0)))
+    }
+
+    #[test]
+    fn verlet_and_rk4_are_exact_without_drag() {
+        assert!(error_after(Integrator::Verlet, 1.0) < 1e-9);
+        assert!(error_after(Integrator::Rk4, 1.0) < 1e-9);
+    }
+
+    #[test]
+    fn euler_error_shrinks_with_dt() {
+        let coarse = error_after(Integrator::Euler, 1.0);
+        let fine = error_after(Integrator::Euler, 0.1);
+        assert!(coarse > 0.1);
+        assert!(fine < coarse / 5.0);
+        assert!(eq_f64(coarse, error_after(Integrator::SemiImplicit, 1.0)));
+    }
+
+    #[test]
+    fn rk4_beats_euler_with_drag() {
+        let env = Environment {
+            quadratic_drag: 0.05,
+            ..Default::default()
+        };
+        let start = projectile(point(0.0, 0.0, 0.0), vector(3.0, 4.0, 0.0));
+        let run = |integrator, dt: f64| {
+            let mut p = start;
+            for _ in 0..(5.0 / dt).round() as usize {
+                p = step(&env, &p, dt, integrator);
+            }
+            p.position
+        };
+        let reference = run(Integrator::Rk4, 0.001);
+        let rk4 = magnitude(sub_tup(run(Integrator::Rk4, 0.5), reference));
+        let euler = magnitude(sub_tup(run(Integrator::Euler, 0.5), reference));
+        assert!(rk4 < euler / 10.0);
+    }
+
+    #[test]
+    fn drag_slows_the_projectile_down() {
+        let mut env = Environment::default();
+        let start = projectile(point(0.0, 0.0, 0.0), vector(5.0, 5.0, 0.0));
+        let free = tick(&env, &start);
+        env.linear_drag = 0.05;
+        let dragged = tick(&env, &start);
+        assert!(magnitude(dragged.velocity) < magnitude(free.velocity));
+    }
+}
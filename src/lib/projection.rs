@@ -0,0 +1,213 @@
+use crate::environment::equirectangular_to_direction;
+use crate::ray::{ray, Ray};
+use crate::tuple::{normalize, point, vector, Tuple};
+
+///
+/// How a fisheye lens spreads angles from the view axis across the image circle
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FisheyeMapping {
+    /// Distance from the center grows linearly with the angle
+    Equidistant,
+    /// Every part of the image circle covers the same solid angle
+    Equisolid,
+}
+
+///
+/// How a camera turns a point on its image into a ray. Rays are in camera space, looking down
+/// -z with +x to the right and +y up, and angles are in radians
+///
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Projection {
+    Perspective {
+        field_of_view: f64,
+    },
+    /// Parallel rays over a view `width` units across, for technical drawings
+    Orthographic {
+        width: f64,
+    },
+    /// A circular image inscribed in the shorter side, covering `field_of_view` edge to edge
+    Fisheye {
+        field_of_view: f64,
+        mapping: FisheyeMapping,
+    },
+    /// Every direction, laid out like the environment maps so a render can be loaded as one
+    Equirectangular,
+}
+
+///
+/// Half the width and height of the view, with `half` across the longer side
+///
+fn half_extents(half: f64, aspect: f64) -> (f64, f64) {
+    if aspect >= 1.0 {
+        (half, half / aspect)
+    } else {
+        (half * aspect, half)
+    }
+}
+
+///
+/// The ray through (x, y) on an image with the given aspect (width / height), where x and y
+/// run from 0 at the top left to 1 at the bottom right. Returns None for points a fisheye
+/// image circle doesn't cover
+///
+pub fn camera_ray(projection: &Projection, aspect: f64, x: f64, y: f64) -> Option<Ray> {
+    let (sx, sy) = (2.0 * x - 1.0, 1.0 - 2.0 * y);
+    match *projection {
+        Projection::Perspective { field_of_view } => {
+            let (hw, hh) = half_extents((field_of_view / 2.0).tan(), aspect);
+            Some(ray(
+                point(0.0, 0.0, 0.0),
+                normalize(vector(sx * hw, sy * hh, -1.0)),
+            ))
+        }
+        Projection::Orthographic { width } => {
+            let (hw, hh) = half_extents(width / 2.0, aspect);
+            Some(ray(point(sx * hw, sy * hh, 0.0), vector(0.0, 0.0, -1.0)))
+        }
+        Projection::Fisheye {
+            field_of_view,
+            mapping,
+        } => {
+            let (px, py) = if aspect >= 1.0 {
+                (sx * aspect, sy)
+            } else {
+                (sx, sy / aspect)
+            };
+            let r = (px * px + py * py).sqrt();
+            if r > 1.0 {
+                return None;
+            }
+            let theta = match mapping {
+                FisheyeMapping::Equidistant => r * field_of_view / 2.0,
+                FisheyeMapping::Equisolid => 2.0 * (r * (field_of_view / 4.0).sin()).asin(),
+            };
+            let direction = if r == 0.0 {
+                vector(0.0, 0.0, -1.0)
+            } else {
+                let s = theta.sin() / r;
+                vector(px * s, py * s, -theta.cos())
+            };
+            Some(ray(point(0.0, 0.0, 0.0), direction))
+        }
+        Projection::Equirectangular => Some(ray(
+            point(0.0, 0.0, 0.0),
+            equirectangular_to_direction(x, y),
+        )),
+    }
+}
+
+///
+/// The direction through (u, v) on one face of a cube map, the inverse of
+/// `environment::direction_to_cube_face`, so rendering each face with a 90° perspective view
+/// gives six images that load back as an environment
+///
+pub fn cube_face_direction(face: usize, u: f64, v: f64) -> Tuple {
+    let (s, t) = (2.0 * u - 1.0, 2.0 * v - 1.0);
+    let (x, y, z) = match face {
+        0 => (1.0, -t, -s),
+        1 => (-1.0, -t, s),
+        2 => (s, 1.0, t),
+        3 => (s, -1.0, -t),
+        4 => (s, -t, 1.0),
+        _ => (-s, -t, -1.0),
+    };
+    normalize(vector(x, y, z))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::environment::{direction_to_cube_face, direction_to_equirectangular};
+    use crate::tuple::{dot_product, eq_f64, eq_tup};
+    use std::f64::consts::PI;
+
+    const FORWARD: Tuple = (0.0, 0.0, -1.0, 0.0);
+
+    #[test]
+    fn every_projection_looks_ahead_from_the_center() {
+        for projection in [
+            Projection::Perspective {
+                field_of_view: PI / 2.0,
+            },
+            Projection::Orthographic { width: 2.0 },
+            Projection::Fisheye {
+                field_of_view: PI,
+                mapping: FisheyeMapping::Equisolid,
+            },
+            Projection::Equirectangular,
+        ] {
+            let r = camera_ray(&projection, 1.5, 0.5, 0.5).unwrap();
+            assert!(
+                eq_tup(r.direction, vector(0.0, 0.0, -1.0)),
+                "{:?}",
+                projection
+            );
+        }
+    }
+
+    #[test]
+    fn perspective_edges_are_half_the_field_of_view_off_axis() {
+        let p = Projection::Perspective {
+            field_of_view: PI / 2.0,
+        };
+        let r = camera_ray(&p, 2.0, 1.0, 0.5).unwrap();
+        assert!(eq_f64(dot_product(r.direction, FORWARD), (PI / 4.0).cos()));
+        assert!(r.direction.0 > 0.0);
+        // the shorter side gets the narrower view
+        let r = camera_ray(&p, 2.0, 0.5, 0.0).unwrap();
+        assert!(eq_tup(r.direction, normalize(vector(0.0, 0.5, -1.0))));
+    }
+
+    #[test]
+    fn orthographic_rays_are_parallel() {
+        let p = Projection::Orthographic { width: 4.0 };
+        let r = camera_ray(&p, 2.0, 0.0, 1.0).unwrap();
+        assert!(eq_tup(r.origin, point(-2.0, -1.0, 0.0)));
+        assert!(eq_tup(r.direction, vector(0.0, 0.0, -1.0)));
+    }
+
+    #[test]
+    fn fisheye_edges_reach_half_the_field_of_view() {
+        for mapping in [FisheyeMapping::Equidistant, FisheyeMapping::Equisolid] {
+            let p = Projection::Fisheye {
+                field_of_view: PI,
+                mapping,
+            };
+            let r = camera_ray(&p, 1.0, 0.5, 0.0).unwrap();
+            assert!(eq_tup(r.direction, vector(0.0, 1.0, 0.0)), "{:?}", mapping);
+            assert!(camera_ray(&p, 1.0, 0.0, 0.0).is_none());
+        }
+        // halfway out, equidistant is at a quarter of the view and equisolid nearer the axis
+        let ray_at = |mapping| {
+            let p = Projection::Fisheye {
+                field_of_view: PI,
+                mapping,
+            };
+            camera_ray(&p, 1.0, 0.75, 0.5).unwrap().direction
+        };
+        let equidistant = ray_at(FisheyeMapping::Equidistant);
+        assert!(eq_f64(dot_product(equidistant, FORWARD), (PI / 4.0).cos()));
+        assert!(dot_product(ray_at(FisheyeMapping::Equisolid), FORWARD) > (PI / 4.0).cos());
+    }
+
+    #[test]
+    fn equirectangular_matches_the_environment_maps() {
+        for (x, y) in [(0.1, 0.2), (0.5, 0.5), (0.8, 0.9)] {
+            let r = camera_ray(&Projection::Equirectangular, 2.0, x, y).unwrap();
+            let (u, v) = direction_to_equirectangular(r.direction);
+            assert!(eq_f64(u, x) && eq_f64(v, y));
+        }
+    }
+
+    #[test]
+    fn cube_faces_match_the_environment_maps() {
+        for face in 0..6 {
+            for (u, v) in [(0.5, 0.5), (0.1, 0.8), (0.9, 0.3)] {
+                let (f, fu, fv) = direction_to_cube_face(cube_face_direction(face, u, v));
+                assert_eq!(f, face);
+                assert!(eq_f64(fu, u) && eq_f64(fv, v));
+            }
+        }
+    }
+}
@@ -0,0 +1,89 @@
+use crate::matrix::Matrix4x4;
+use crate::transform::transform;
+use crate::tuple::{add_tup, mul_tup, Tuple};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Ray {
+    pub origin: Tuple,
+    pub direction: Tuple,
+    /// When the ray is cast, which objects that move while the shutter is open are seen at
+    pub time: f64,
+}
+
+///
+/// Creates a ray starting at a point and travelling along a vector, cast at time 0
+///
+pub fn ray(origin: Tuple, direction: Tuple) -> Ray {
+    ray_at_time(origin, direction, 0.0)
+}
+
+///
+/// Creates a ray cast at a point in time
+///
+pub fn ray_at_time(origin: Tuple, direction: Tuple, time: f64) -> Ray {
+    Ray {
+        origin,
+        direction,
+        time,
+    }
+}
+
+///
+/// Finds the point at distance t along the ray
+///
+pub fn position(r: &Ray, t: f64) -> Tuple {
+    add_tup(r.origin, mul_tup(r.direction, t))
+}
+
+///
+/// Applies a transformation matrix to both the origin and direction of a ray, keeping its time
+///
+pub fn transform_ray(r: &Ray, m: &Matrix4x4) -> Ray {
+    ray_at_time(transform(m, r.origin), transform(m, r.direction), r.time)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transform::{scaling, translation};
+    use crate::tuple::{eq_tup, point, vector};
+
+    #[test]
+    fn create_a_ray() {
+        let r = ray(point(1.0, 2.0, 3.0), vector(4.0, 5.0, 6.0));
+        assert!(eq_tup(r.origin, point(1.0, 2.0, 3.0)));
+        assert!(eq_tup(r.direction, vector(4.0, 5.0, 6.0)));
+    }
+
+    #[test]
+    fn point_from_a_distance() {
+        let r = ray(point(2.0, 3.0, 4.0), vector(1.0, 0.0, 0.0));
+        assert!(eq_tup(position(&r, 0.0), point(2.0, 3.0, 4.0)));
+        assert!(eq_tup(position(&r, 1.0), point(3.0, 3.0, 4.0)));
+        assert!(eq_tup(position(&r, -1.0), point(1.0, 3.0, 4.0)));
+        assert!(eq_tup(position(&r, 2.5), point(4.5, 3.0, 4.0)));
+    }
+
+    #[test]
+    fn translating_a_ray() {
+        let r = ray(point(1.0, 2.0, 3.0), vector(0.0, 1.0, 0.0));
+        let r2 = transform_ray(&r, &translation(3.0, 4.0, 5.0));
+        assert!(eq_tup(r2.origin, point(4.0, 6.0, 8.0)));
+        assert!(eq_tup(r2.direction, vector(0.0, 1.0, 0.0)));
+    }
+
+    #[test]
+    fn scaling_a_ray() {
+        let r = ray(point(1.0, 2.0, 3.0), vector(0.0, 1.0, 0.0));
+        let r2 = transform_ray(&r, &scaling(2.0, 3.0, 4.0));
+        assert!(eq_tup(r2.origin, point(2.0, 6.0, 12.0)));
+        assert!(eq_tup(r2.direction, vector(0.0, 3.0, 0.0)));
+    }
+
+    #[test]
+    fn transforming_a_ray_keeps_its_time() {
+        let r = ray_at_time(point(1.0, 2.0, 3.0), vector(0.0, 1.0, 0.0), 0.25);
+        assert_eq!(transform_ray(&r, &translation(3.0, 4.0, 5.0)).time, 0.25);
+        assert_eq!(ray(point(0.0, 0.0, 0.0), vector(0.0, 0.0, 1.0)).time, 0.0);
+    }
+}
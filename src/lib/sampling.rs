@@ -0,0 +1,79 @@
+///
+/// The bases of the Halton sequence along each dimension; more dimensions reuse them
+///
+const BASES: [u32; 4] = [2, 3, 5, 7];
+
+///
+/// The radical inverse of `index` in `base`: its digits mirrored around the point. Taken over
+/// 0, 1, 2, ... it's the Halton sequence, which spreads evenly over [0, 1) however many of its
+/// points are used
+///
+pub fn radical_inverse(mut index: u32, base: u32) -> f64 {
+    let (mut inverse, mut scale) = (0.0, 1.0);
+    while index > 0 {
+        scale /= base as f64;
+        inverse += (index % base) as f64 * scale;
+        index /= base;
+    }
+    inverse
+}
+
+///
+/// A well mixed 32-bit hash, so that nearby inputs give unrelated outputs
+///
+fn hash(mut v: u32) -> u32 {
+    v ^= v >> 16;
+    v = v.wrapping_mul(0x7feb_352d);
+    v ^= v >> 15;
+    v = v.wrapping_mul(0x846c_a68b);
+    v ^ (v >> 16)
+}
+
+///
+/// The `k`th sample in [0, 1) along one dimension of a pixel, such as the time a ray is cast
+/// at or where it leaves a lens. A pixel's samples are spread evenly, and every pixel's are
+/// shifted by a different amount so that neighbours don't share a pattern
+///
+pub fn pixel_sample(x: u32, y: u32, k: u32, dimension: u32) -> f64 {
+    let base = BASES[dimension as usize % BASES.len()];
+    let shift = hash(x ^ hash(y ^ hash(dimension))) as f64 / (u32::MAX as f64 + 1.0);
+    (radical_inverse(k, base) + shift).fract()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tuple::eq_f64;
+
+    #[test]
+    fn radical_inverses_mirror_the_digits() {
+        assert_eq!(radical_inverse(0, 2), 0.0);
+        assert_eq!(radical_inverse(1, 2), 0.5);
+        assert_eq!(radical_inverse(2, 2), 0.25);
+        assert_eq!(radical_inverse(3, 2), 0.75);
+        assert!(eq_f64(radical_inverse(5, 3), 7.0 / 9.0));
+    }
+
+    #[test]
+    fn a_pixels_samples_are_spread_evenly() {
+        for dimension in 0..3 {
+            let mut samples: Vec<f64> = (0..8).map(|k| pixel_sample(4, 9, k, dimension)).collect();
+            assert!(samples.iter().all(|s| (0.0..1.0).contains(s)));
+            samples.sort_by(f64::total_cmp);
+            // shifted and wrapped, eight points a base 2 sequence spreads evenly stay 1/8 apart
+            if dimension == 0 {
+                assert!(samples.windows(2).all(|w| eq_f64(w[1] - w[0], 0.125)));
+            }
+            assert!(samples.windows(2).all(|w| w[1] - w[0] > 0.01));
+        }
+    }
+
+    #[test]
+    fn neighbouring_pixels_are_shifted_differently() {
+        let first = pixel_sample(0, 0, 0, 0);
+        assert_eq!(first, pixel_sample(0, 0, 0, 0));
+        assert_ne!(first, pixel_sample(1, 0, 0, 0));
+        assert_ne!(first, pixel_sample(0, 1, 0, 0));
+        assert_ne!(first, pixel_sample(0, 0, 0, 1));
+    }
+}
@@ -0,0 +1,311 @@
+use crate::intersection::{intersection, Intersection};
+use crate::keyframe::{Track, TransformKey};
+use crate::material::{material, Material};
+use crate::matrix::Matrix4x4;
+use crate::normal_map::{apply_perturbation, sphere_tangent_frame, spherical_uv, Uv};
+use crate::ray::{transform_ray, Ray};
+use crate::transform::transform;
+use crate::tuple::{dot_product, normalize, point, sub_tup, vector, Tuple};
+
+///
+/// A unit sphere at the origin, moved into the world by its transform
+///
+#[derive(Clone, Debug, PartialEq)]
+pub struct Sphere {
+    pub transform: Matrix4x4,
+    /// How the sphere moves while the shutter is open. Each ray sees it where the track is at
+    /// the ray's time, in place of `transform`
+    pub motion: Option<Track<TransformKey>>,
+    pub material: Material,
+}
+
+///
+/// Creates a unit sphere with the identity transform and the default material
+///
+pub fn sphere() -> Sphere {
+    Sphere {
+        transform: Matrix4x4::identity(),
+        motion: None,
+        material: material(),
+    }
+}
+
+///
+/// The sphere's transform at a point in time: where its motion has taken it, or its fixed
+/// transform if it doesn't move
+///
+pub fn transform_at(s: &Sphere, time: f64) -> Matrix4x4 {
+    match s.motion.as_ref().and_then(|motion| motion.sample(time)) {
+        Some(key) => key.to_matrix(),
+        None => s.transform,
+    }
+}
+
+///
+/// Finds every t at which the ray crosses the sphere's surface, in increasing order, with the
+/// sphere where it is at the ray's time.
+/// A ray that misses, or a sphere whose transform can't be inverted, yields no intersections
+///
+pub fn intersect<'a>(s: &'a Sphere, r: &Ray) -> Vec<Intersection<'a>> {
+    let inverse = match transform_at(s, r.time).try_inverse() {
+        Some(inverse) => inverse,
+        None => return vec![],
+    };
+    let r = transform_ray(r, &inverse);
+    let sphere_to_ray = sub_tup(r.origin, point(0.0, 0.0, 0.0));
+
+    let a = dot_product(r.direction, r.direction);
+    let b = 2.0 * dot_product(r.direction, sphere_to_ray);
+    let c = dot_product(sphere_to_ray, sphere_to_ray) - 1.0;
+    let discriminant = b * b - 4.0 * a * c;
+
+    if discriminant < 0.0 {
+        return vec![];
+    }
+
+    let root = discriminant.sqrt();
+    vec![
+        intersection((-b - root) / (2.0 * a), s),
+        intersection((-b + root) / (2.0 * a), s),
+    ]
+}
+
+///
+/// Finds the world-space surface normal at a world-space point on the sphere, tilted by the
+/// material's normal or bump map if it has one. A moving sphere is taken where it is at time 0
+///
+pub fn normal_at(s: &Sphere, world_point: Tuple) -> Tuple {
+    normal_at_time(s, world_point, 0.0)
+}
+
+///
+/// Like `normal_at`, with a moving sphere where it is at `time`, such as the time of the ray
+/// that hit it
+///
+pub fn normal_at_time(s: &Sphere, world_point: Tuple, time: f64) -> Tuple {
+    let inverse = transform_at(s, time)
+        .try_inverse()
+        .unwrap_or_else(Matrix4x4::identity);
+    let object_point = transform(&inverse, world_point);
+    let object_normal = match &s.material.perturbation {
+        Some(perturbation) => apply_perturbation(
+            sphere_tangent_frame(object_point),
+            perturbation,
+            spherical_uv(object_point),
+        ),
+        None => sub_tup(object_point, point(0.0, 0.0, 0.0)),
+    };
+    let world_normal = transform(&inverse.transpose(), object_normal);
+    normalize(vector(world_normal.0, world_normal.1, world_normal.2))
+}
+
+///
+/// The texture coordinates at a world-space point on the sphere, where it is at `time`
+///
+pub fn uv_at_time(s: &Sphere, world_point: Tuple, time: f64) -> Uv {
+    let inverse = transform_at(s, time)
+        .try_inverse()
+        .unwrap_or_else(Matrix4x4::identity);
+    spherical_uv(transform(&inverse, world_point))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::keyframe::{keyframe, rotation_from_euler, track, Easing};
+    use crate::material::Perturbation;
+    use crate::normal_map::{HeightMap, NormalMap};
+    use crate::ray::{ray, ray_at_time};
+    use crate::transform::{chain, rotation_z, scaling, translation};
+    use crate::tuple::{eq_f64, eq_tup};
+    use image::{Luma, Rgb};
+    use std::f64::consts::{FRAC_1_SQRT_2, PI};
+    use std::sync::Arc;
+
+    #[test]
+    fn ray_intersects_at_two_points() {
+        let r = ray(point(0.0, 0.0, -5.0), vector(0.0, 0.0, 1.0));
+        let s = sphere();
+        let xs = intersect(&s, &r);
+        assert_eq!(xs.len(), 2);
+        assert!(eq_f64(xs[0].t, 4.0));
+        assert!(eq_f64(xs[1].t, 6.0));
+    }
+
+    #[test]
+    fn ray_intersects_at_a_tangent() {
+        let r = ray(point(0.0, 1.0, -5.0), vector(0.0, 0.0, 1.0));
+        let s = sphere();
+        let xs = intersect(&s, &r);
+        assert_eq!(xs.len(), 2);
+        assert!(eq_f64(xs[0].t, 5.0));
+        assert!(eq_f64(xs[1].t, 5.0));
+    }
+
+    #[test]
+    fn ray_misses() {
+        let r = ray(point(0.0, 2.0, -5.0), vector(0.0, 0.0, 1.0));
+        assert!(intersect(&sphere(), &r).is_empty());
+    }
+
+    #[test]
+    fn ray_originates_inside() {
+        let r = ray(point(0.0, 0.0, 0.0), vector(0.0, 0.0, 1.0));
+        let s = sphere();
+        let xs = intersect(&s, &r);
+        assert!(eq_f64(xs[0].t, -1.0));
+        assert!(eq_f64(xs[1].t, 1.0));
+    }
+
+    #[test]
+    fn sphere_behind_ray() {
+        let r = ray(point(0.0, 0.0, 5.0), vector(0.0, 0.0, 1.0));
+        let s = sphere();
+        let xs = intersect(&s, &r);
+        assert!(eq_f64(xs[0].t, -6.0));
+        assert!(eq_f64(xs[1].t, -4.0));
+    }
+
+    #[test]
+    fn intersect_sets_the_object() {
+        let r = ray(point(0.0, 0.0, -5.0), vector(0.0, 0.0, 1.0));
+        let s = sphere();
+        let xs = intersect(&s, &r);
+        assert!(std::ptr::eq(xs[0].object, &s));
+        assert!(std::ptr::eq(xs[1].object, &s));
+    }
+
+    #[test]
+    fn intersect_scaled_sphere() {
+        let r = ray(point(0.0, 0.0, -5.0), vector(0.0, 0.0, 1.0));
+        let mut s = sphere();
+        s.transform = scaling(2.0, 2.0, 2.0);
+        let xs = intersect(&s, &r);
+        assert!(eq_f64(xs[0].t, 3.0));
+        assert!(eq_f64(xs[1].t, 7.0));
+    }
+
+    #[test]
+    fn intersect_translated_sphere() {
+        let r = ray(point(0.0, 0.0, -5.0), vector(0.0, 0.0, 1.0));
+        let mut s = sphere();
+        s.transform = translation(5.0, 0.0, 0.0);
+        assert!(intersect(&s, &r).is_empty());
+    }
+
+    #[test]
+    fn degenerate_transform_has_no_intersections() {
+        let r = ray(point(0.0, 0.0, -5.0), vector(0.0, 0.0, 1.0));
+        let mut s = sphere();
+        s.transform = scaling(0.0, 1.0, 1.0);
+        assert!(intersect(&s, &r).is_empty());
+    }
+
+    #[test]
+    fn moving_spheres_are_where_the_ray_sees_them() {
+        let at = |x: f64| TransformKey {
+            scale: vector(1.0, 1.0, 1.0),
+            rotation: rotation_from_euler(0.0, 0.0, 0.0),
+            translation: vector(x, 0.0, 0.0),
+        };
+        let mut s = sphere();
+        s.motion = Some(track(vec![
+            keyframe(0.0, at(-2.0), Easing::Linear),
+            keyframe(1.0, at(2.0), Easing::Linear),
+        ]));
+        let cast = |time| ray_at_time(point(0.0, 0.0, -5.0), vector(0.0, 0.0, 1.0), time);
+        assert!(intersect(&s, &cast(0.0)).is_empty());
+        assert!(eq_f64(intersect(&s, &cast(0.5))[0].t, 4.0));
+        assert!(intersect(&s, &cast(1.0)).is_empty());
+        assert!(eq_tup(
+            normal_at_time(&s, point(3.0, 0.0, 0.0), 1.0),
+            vector(1.0, 0.0, 0.0)
+        ));
+        let moved = transform_at(&s, 0.75);
+        assert!(moved.relative_eq(&translation(1.0, 0.0, 0.0), 1e-9, 1e-9));
+    }
+
+    #[test]
+    fn normals_on_the_axes() {
+        let s = sphere();
+        assert!(eq_tup(
+            normal_at(&s, point(1.0, 0.0, 0.0)),
+            vector(1.0, 0.0, 0.0)
+        ));
+        assert!(eq_tup(
+            normal_at(&s, point(0.0, 1.0, 0.0)),
+            vector(0.0, 1.0, 0.0)
+        ));
+        assert!(eq_tup(
+            normal_at(&s, point(0.0, 0.0, 1.0)),
+            vector(0.0, 0.0, 1.0)
+        ));
+    }
+
+    #[test]
+    fn normal_is_normalized() {
+        let r = 3.0_f64.sqrt() / 3.0;
+        let n = normal_at(&sphere(), point(r, r, r));
+        assert!(eq_tup(n, normalize(n)));
+        assert!(eq_tup(n, vector(r, r, r)));
+    }
+
+    #[test]
+    fn normal_on_translated_sphere() {
+        let mut s = sphere();
+        s.transform = translation(0.0, 1.0, 0.0);
+        let n = normal_at(&s, point(0.0, 1.0 + FRAC_1_SQRT_2, -FRAC_1_SQRT_2));
+        assert!(eq_tup(n, vector(0.0, FRAC_1_SQRT_2, -FRAC_1_SQRT_2)));
+    }
+
+    #[test]
+    fn normal_on_transformed_sphere() {
+        let mut s = sphere();
+        s.transform = chain(&[rotation_z(PI / 5.0), scaling(1.0, 0.5, 1.0)]);
+        let r = 2.0_f64.sqrt() / 2.0;
+        let n = normal_at(&s, point(0.0, r, -r));
+        assert!(eq_tup(n, vector(0.0, 0.97014, -0.24254)));
+    }
+
+    #[test]
+    fn flat_normal_maps_keep_the_normal() {
+        let mut s = sphere();
+        s.transform = translation(0.0, 1.0, 0.0);
+        let flat = NormalMap::from_pixel(4, 4, Rgb([128, 128, 255]));
+        s.material.perturbation = Some(Perturbation::NormalMap(Arc::new(flat)));
+        let n = normal_at(&s, point(0.0, 1.0 + FRAC_1_SQRT_2, -FRAC_1_SQRT_2));
+        assert!(dot_product(n, vector(0.0, FRAC_1_SQRT_2, -FRAC_1_SQRT_2)) > 0.999);
+    }
+
+    #[test]
+    fn normal_maps_tilt_the_normal_along_the_surface() {
+        let mut s = sphere();
+        // every texel leans fully toward +v, which runs north on a sphere
+        let north = NormalMap::from_pixel(4, 4, Rgb([128, 255, 128]));
+        s.material.perturbation = Some(Perturbation::NormalMap(Arc::new(north)));
+        let n = normal_at(&s, point(0.0, 0.0, -1.0));
+        assert!(eq_tup(n, normalize(n)));
+        assert!(n.1 > 0.99);
+    }
+
+    #[test]
+    fn bumps_tilt_the_normal_away_from_rising_ground() {
+        let mut s = sphere();
+        // the heights rise toward the top of the map, which is the north pole
+        let ramp = HeightMap::from_fn(8, 8, |_, y| Luma([(8 - y) as f32]));
+        s.material.perturbation = Some(Perturbation::Bump(Arc::new(ramp), 0.01));
+        let n = normal_at(&s, point(0.0, 0.0, -1.0));
+        assert!(n.1 < -0.01);
+        assert!(n.2 < 0.0);
+    }
+
+    #[test]
+    fn uvs_follow_the_sphere() {
+        let mut s = sphere();
+        s.transform = translation(0.0, 1.0, 0.0);
+        let (_, v) = uv_at_time(&s, point(0.0, 2.0, 0.0), 0.0);
+        assert!(eq_f64(v, 1.0));
+        let (u, v) = uv_at_time(&s, point(0.0, 1.0, -1.0), 0.0);
+        assert!(eq_f64(u, 0.0) && eq_f64(v, 0.5));
+    }
+}
@@ -0,0 +1,233 @@
+use crate::canvas::{HdrImage, Layers};
+use crate::tuple::{add_tup, mul_tup, normalize, sub_tup, Tuple};
+use image::{imageops, ImageBuffer, Rgb};
+use std::fmt::{Display, Formatter};
+
+pub const DEFAULT_INTEROCULAR: f64 = 0.065;
+
+pub const STEREO_LAYOUTS: [&str; 3] = ["side-by-side", "top-bottom", "anaglyph"];
+
+///
+/// How the two eyes are packed into one image
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StereoLayout {
+    SideBySide,
+    TopBottom,
+    Anaglyph,
+}
+
+///
+/// Left and right eye renders that can't be packed together because their sizes differ
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct EyeMismatch {
+    pub left: (u32, u32),
+    pub right: (u32, u32),
+}
+
+impl Display for EyeMismatch {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "the left eye is {}x{} but the right eye is {}x{}",
+            self.left.0, self.left.1, self.right.0, self.right.1
+        )
+    }
+}
+
+impl std::error::Error for EyeMismatch {}
+
+///
+/// Parses one of `STEREO_LAYOUTS`
+///
+pub fn stereo_layout_from_name(name: &str) -> Option<StereoLayout> {
+    match name {
+        "side-by-side" => Some(StereoLayout::SideBySide),
+        "top-bottom" => Some(StereoLayout::TopBottom),
+        "anaglyph" => Some(StereoLayout::Anaglyph),
+        _ => None,
+    }
+}
+
+///
+/// Positions of the (left, right) eyes, half the interocular distance either side of `center`
+///
+pub fn eye_positions(center: Tuple, right: Tuple, interocular: f64) -> (Tuple, Tuple) {
+    let offset = mul_tup(normalize(right), interocular / 2.0);
+    (sub_tup(center, offset), add_tup(center, offset))
+}
+
+///
+/// Directions for the (left, right) eyes toed in so that they meet `convergence` units ahead
+///
+pub fn eye_directions(
+    center: Tuple,
+    forward: Tuple,
+    right: Tuple,
+    interocular: f64,
+    convergence: f64,
+) -> (Tuple, Tuple) {
+    let target = add_tup(center, mul_tup(normalize(forward), convergence));
+    let (left, right) = eye_positions(center, right, interocular);
+    (
+        normalize(sub_tup(target, left)),
+        normalize(sub_tup(target, right)),
+    )
+}
+
+///
+/// Packs a left and right eye render into one image; both must have the same dimensions
+///
+pub fn compose(
+    left: &HdrImage,
+    right: &HdrImage,
+    layout: StereoLayout,
+) -> Result<HdrImage, EyeMismatch> {
+    if left.dimensions() != right.dimensions() {
+        return Err(EyeMismatch {
+            left: left.dimensions(),
+            right: right.dimensions(),
+        });
+    }
+    let (w, h) = left.dimensions();
+    Ok(match layout {
+        StereoLayout::SideBySide => {
+            let mut out = ImageBuffer::new(w * 2, h);
+            imageops::replace(&mut out, left, 0, 0);
+            imageops::replace(&mut out, right, w as i64, 0);
+            out
+        }
+        StereoLayout::TopBottom => {
+            let mut out = ImageBuffer::new(w, h * 2);
+            imageops::replace(&mut out, left, 0, 0);
+            imageops::replace(&mut out, right, 0, h as i64);
+            out
+        }
+        StereoLayout::Anaglyph => ImageBuffer::from_fn(w, h, |x, y| {
+            let l = left.get_pixel(x, y);
+            let r = right.get_pixel(x, y);
+            Rgb([l.0[0], r.0[1], r.0[2]])
+        }),
+    })
+}
+
+///
+/// Packs every layer of a left and right eye render. An anaglyph mixes the eyes in each
+/// pixel, so it keeps only the color: the auxiliary buffers don't mix that way
+///
+pub fn compose_layers(
+    left: &Layers,
+    right: &Layers,
+    layout: StereoLayout,
+) -> Result<Layers, EyeMismatch> {
+    let pass = |l: &Option<HdrImage>, r: &Option<HdrImage>| match (l, r) {
+        (Some(l), Some(r)) if layout != StereoLayout::Anaglyph => compose(l, r, layout).map(Some),
+        _ => Ok(None),
+    };
+    Ok(Layers {
+        color: compose(&left.color, &right.color, layout)?,
+        albedo: pass(&left.albedo, &right.albedo)?,
+        normal: pass(&left.normal, &right.normal)?,
+        depth: pass(&left.depth, &right.depth)?,
+        shadow: pass(&left.shadow, &right.shadow)?,
+        object_id: pass(&left.object_id, &right.object_id)?,
+        material_id: pass(&left.material_id, &right.material_id)?,
+        uv: pass(&left.uv, &right.uv)?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::canvas::layers;
+    use crate::tuple::{dot_product, eq_f64, eq_tup, point, vector};
+
+    fn solid(v: f32) -> HdrImage {
+        ImageBuffer::from_pixel(3, 2, Rgb([v, v, v]))
+    }
+
+    #[test]
+    fn eyes_are_interocular_apart() {
+        let (l, r) = eye_positions(point(0.0, 1.0, 0.0), vector(2.0, 0.0, 0.0), 0.1);
+        assert!(eq_tup(l, point(-0.05, 1.0, 0.0)));
+        assert!(eq_tup(r, point(0.05, 1.0, 0.0)));
+    }
+
+    #[test]
+    fn eyes_converge() {
+        let (l, r) = eye_directions(
+            point(0.0, 0.0, 0.0),
+            vector(0.0, 0.0, -1.0),
+            vector(1.0, 0.0, 0.0),
+            0.2,
+            1.0,
+        );
+        assert!(l.0 > 0.0 && r.0 < 0.0);
+        assert!(eq_f64(l.0, -r.0));
+        assert!(eq_f64(dot_product(l, l), 1.0));
+    }
+
+    #[test]
+    fn side_by_side() {
+        let out = compose(&solid(0.1), &solid(2.0), StereoLayout::SideBySide).unwrap();
+        assert_eq!(out.dimensions(), (6, 2));
+        assert_eq!(out.get_pixel(2, 1).0, [0.1, 0.1, 0.1]);
+        assert_eq!(out.get_pixel(3, 0).0, [2.0, 2.0, 2.0]);
+    }
+
+    #[test]
+    fn top_bottom() {
+        let out = compose(&solid(0.1), &solid(2.0), StereoLayout::TopBottom).unwrap();
+        assert_eq!(out.dimensions(), (3, 4));
+        assert_eq!(out.get_pixel(0, 1).0, [0.1, 0.1, 0.1]);
+        assert_eq!(out.get_pixel(0, 2).0, [2.0, 2.0, 2.0]);
+    }
+
+    #[test]
+    fn anaglyph_takes_red_from_the_left_eye() {
+        let out = compose(&solid(0.1), &solid(2.0), StereoLayout::Anaglyph).unwrap();
+        assert_eq!(out.dimensions(), (3, 2));
+        assert_eq!(out.get_pixel(1, 1).0, [0.1, 2.0, 2.0]);
+    }
+
+    #[test]
+    fn mismatched_eyes_are_rejected() {
+        let result = compose(
+            &solid(0.1),
+            &ImageBuffer::new(1, 1),
+            StereoLayout::SideBySide,
+        );
+        let expected = EyeMismatch {
+            left: (3, 2),
+            right: (1, 1),
+        };
+        assert_eq!(result.unwrap_err(), expected);
+        assert_eq!(
+            expected.to_string(),
+            "the left eye is 3x2 but the right eye is 1x1"
+        );
+    }
+
+    #[test]
+    fn layers_are_packed_like_the_color() {
+        let eye = |v| Layers {
+            depth: Some(solid(v)),
+            ..layers(solid(v))
+        };
+        let packed = compose_layers(&eye(1.0), &eye(3.0), StereoLayout::TopBottom).unwrap();
+        assert_eq!(packed.depth.unwrap().get_pixel(0, 3).0, [3.0; 3]);
+        assert!(packed.albedo.is_none());
+        let mixed = compose_layers(&eye(1.0), &eye(3.0), StereoLayout::Anaglyph).unwrap();
+        assert_eq!(mixed.color.get_pixel(0, 0).0, [1.0, 3.0, 3.0]);
+        assert!(mixed.depth.is_none());
+    }
+
+    #[test]
+    fn layouts_are_named() {
+        for name in STEREO_LAYOUTS {
+            assert!(stereo_layout_from_name(name).is_some(), "{}", name);
+        }
+        assert_eq!(stereo_layout_from_name("over-under"), None);
+    }
+}
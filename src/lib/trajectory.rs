@@ -0,0 +1,415 @@
+use crate::projectile::{step, Environment, Integrator, Projectile};
+use crate::tuple::{add_tup, magnitude, mul_tup, sub_tup, Tuple};
+use std::fmt::Write;
+use std::path::Path;
+
+///
+/// Every data file extension that can be written
+///
+pub const DATA_EXTENSIONS: [&str; 2] = ["csv", "json"];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DataFormat {
+    Csv,
+    Json,
+}
+
+///
+/// The state of a projectile at one step of a simulation
+///
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Sample {
+    pub step: usize,
+    /// The time since launch, in ticks
+    pub tick: f64,
+    pub position: Tuple,
+    pub velocity: Tuple,
+}
+
+///
+/// The samples of a projectile's flight while it was above the ground, and the moment it
+/// came back down if it did. Long flights keep an evenly thinned subset of their steps, always
+/// including the first and the last one above the ground
+///
+#[derive(Clone, Debug, PartialEq)]
+pub struct Trajectory {
+    pub samples: Vec<Sample>,
+    pub impact: Option<Sample>,
+    /// The greatest height of every step above the ground, including the ones thinned out of
+    /// `samples`, or None if it started below the ground
+    pub max_height: Option<f64>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Summary {
+    /// Ticks from launch to impact, or to the last sample if it never landed
+    pub flight_time: f64,
+    pub max_height: f64,
+    /// The horizontal distance from the launch point to the impact, or to the last sample
+    pub range: f64,
+    pub impact_velocity: Option<Tuple>,
+}
+
+///
+/// Maps a file extension (without the dot, any case) to the data format it is written in
+///
+pub fn data_format_from_path(path: &Path) -> Option<DataFormat> {
+    let ext = path.extension()?.to_str()?.to_ascii_lowercase();
+    match ext.as_str() {
+        "csv" => Some(DataFormat::Csv),
+        "json" => Some(DataFormat::Json),
+        _ => None,
+    }
+}
+
+///
+/// Steps the projectile until it falls below the ground at y = 0 or `max_steps` have been
+/// taken. The impact is interpolated between the last step above the ground and the first one
+/// below it.
+///
+/// At most `max_samples` steps are kept, plus the last one: whenever they fill up every other
+/// one is dropped and from then on only every other step is kept, so memory doesn't grow with
+/// the length of the flight
+///
+pub fn simulate(
+    env: &Environment,
+    start: &Projectile,
+    dt: f64,
+    integrator: Integrator,
+    max_steps: usize,
+    max_samples: usize,
+) -> Trajectory {
+    let max_samples = max_samples.max(2);
+    let mut samples: Vec<Sample> = vec![];
+    let mut stride = 1;
+    let mut last = None;
+    let mut impact = None;
+    let mut max_height: Option<f64> = None;
+    let mut p = *start;
+    for i in 0..max_steps {
+        let sample = Sample {
+            step: i,
+            tick: i as f64 * dt,
+            position: p.position,
+            velocity: p.velocity,
+        };
+        if p.position.1 < 0.0 {
+            impact = last.map(|last| interpolate_impact(&last, &sample));
+            break;
+        }
+        if i % stride == 0 && samples.len() == max_samples {
+            samples = samples.into_iter().step_by(2).collect();
+            stride *= 2;
+        }
+        if i % stride == 0 {
+            samples.push(sample);
+        }
+        max_height = Some(max_height.map_or(p.position.1, |h| h.max(p.position.1)));
+        last = Some(sample);
+        p = step(env, &p, dt, integrator);
+    }
+    if let Some(last) = last.filter(|l| samples.last().map(|s| s.step) != Some(l.step)) {
+        samples.push(last);
+    }
+    Trajectory {
+        samples,
+        impact,
+        max_height,
+    }
+}
+
+fn interpolate_impact(above: &Sample, below: &Sample) -> Sample {
+    let f = above.position.1 / (above.position.1 - below.position.1);
+    let lerp = |a: Tuple, b: Tuple| add_tup(a, mul_tup(sub_tup(b, a), f));
+    Sample {
+        step: below.step,
+        tick: above.tick + (below.tick - above.tick) * f,
+        position: lerp(above.position, below.position),
+        velocity: lerp(above.velocity, below.velocity),
+    }
+}
+
+///
+/// Flight time, maximum height, range and impact velocity of a trajectory, or None if it has no
+/// samples because it started below the ground
+///
+pub fn summarize(trajectory: &Trajectory) -> Option<Summary> {
+    let first = *trajectory.samples.first()?;
+    let last = trajectory.impact.or(trajectory.samples.last().copied())?;
+    Some(Summary {
+        flight_time: last.tick - first.tick,
+        max_height: trajectory.max_height?,
+        range: (last.position.0 - first.position.0).abs(),
+        impact_velocity: trajectory.impact.map(|s| s.velocity),
+    })
+}
+
+///
+/// One row per sample of every trajectory, with a header
+///
+pub fn samples_to_csv(trajectories: &[Trajectory]) -> String {
+    let mut out = String::from("bullet,step,tick,x,y,z,vx,vy,vz,speed\n");
+    for (i, t) in trajectories.iter().enumerate() {
+        for s in &t.samples {
+            let (p, v) = (s.position, s.velocity);
+            let _ = writeln!(
+                out,
+                "{},{},{},{},{},{},{},{},{},{}",
+                i,
+                s.step,
+                s.tick,
+                p.0,
+                p.1,
+                p.2,
+                v.0,
+                v.1,
+                v.2,
+                magnitude(v)
+            );
+        }
+    }
+    out
+}
+
+///
+/// One row per trajectory with its summary, with a header. Missing impacts and summaries of
+/// empty trajectories are left empty
+///
+pub fn summaries_to_csv(trajectories: &[Trajectory]) -> String {
+    let mut out = String::from(
+        "bullet,flight_time,max_height,range,impact_vx,impact_vy,impact_vz,impact_speed\n",
+    );
+    for (i, t) in trajectories.iter().enumerate() {
+        let Some(s) = summarize(t) else {
+            let _ = writeln!(out, "{},,,,,,,", i);
+            continue;
+        };
+        let impact = match s.impact_velocity {
+            Some(v) => format!("{},{},{},{}", v.0, v.1, v.2, magnitude(v)),
+            None => ",,,".to_string(),
+        };
+        let _ = writeln!(
+            out,
+            "{},{},{},{},{}",
+            i, s.flight_time, s.max_height, s.range, impact
+        );
+    }
+    out
+}
+
+///
+/// Every trajectory with its summary and samples, as a JSON document
+///
+pub fn trajectories_to_json(trajectories: &[Trajectory]) -> String {
+    let bullets: Vec<String> = trajectories
+        .iter()
+        .map(|t| {
+            let summary = match summarize(t) {
+                Some(s) => format!(
+                    "{{\"flight_time\":{},\"max_height\":{},\"range\":{},\"impact\":{}}}",
+                    json_number(s.flight_time),
+                    json_number(s.max_height),
+                    json_number(s.range),
+                    json_impact(s.impact_velocity)
+                ),
+                None => "null".to_string(),
+            };
+            let samples: Vec<String> = t
+                .samples
+                .iter()
+                .map(|s| {
+                    format!(
+                        "{{\"step\":{},\"tick\":{},\"position\":{},\"velocity\":{},\"speed\":{}}}",
+                        s.step,
+                        json_number(s.tick),
+                        json_vector(s.position),
+                        json_vector(s.velocity),
+                        json_number(magnitude(s.velocity))
+                    )
+                })
+                .collect();
+            format!(
+                "{{\"summary\":{},\"samples\":[{}]}}",
+                summary,
+                samples.join(",")
+            )
+        })
+        .collect();
+    format!("{{\"bullets\":[{}]}}\n", bullets.join(","))
+}
+
+fn json_impact(velocity: Option<Tuple>) -> String {
+    match velocity {
+        Some(v) => format!(
+            "{{\"velocity\":{},\"speed\":{}}}",
+            json_vector(v),
+            json_number(magnitude(v))
+        ),
+        None => "null".to_string(),
+    }
+}
+
+fn json_vector(t: Tuple) -> String {
+    format!(
+        "[{},{},{}]",
+        json_number(t.0),
+        json_number(t.1),
+        json_number(t.2)
+    )
+}
+
+// JSON has no infinities or NaN, so a diverging simulation writes null instead
+fn json_number(f: f64) -> String {
+    if f.is_finite() {
+        f.to_string()
+    } else {
+        "null".to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::projectile::{environment, projectile};
+    use crate::tuple::{eq_f64, point, vector};
+
+    fn drop_from(height: f64) -> Trajectory {
+        // falls 1, 3, 5, ... per tick under a gravity of 2 with semi-implicit steps
+        let env = environment(vector(0.0, -2.0, 0.0), vector(0.0, 0.0, 0.0));
+        let start = projectile(point(0.0, height, 0.0), vector(1.0, 1.0, 0.0));
+        simulate(&env, &start, 1.0, Integrator::SemiImplicit, 100, 100)
+    }
+
+    #[test]
+    fn data_formats_follow_the_extension() {
+        assert_eq!(
+            data_format_from_path(Path::new("a/b.CSV")),
+            Some(DataFormat::Csv)
+        );
+        assert_eq!(
+            data_format_from_path(Path::new("b.json")),
+            Some(DataFormat::Json)
+        );
+        assert_eq!(data_format_from_path(Path::new("b.txt")), None);
+    }
+
+    #[test]
+    fn simulation_stops_below_the_ground() {
+        let t = drop_from(4.0);
+        // heights 4, 3, 0 then -5
+        assert_eq!(t.samples.len(), 3);
+        let impact = t.impact.unwrap();
+        assert!(eq_f64(impact.position.1, 0.0));
+        assert!(eq_f64(impact.tick, 2.0));
+    }
+
+    #[test]
+    fn impact_is_interpolated() {
+        let t = drop_from(5.0);
+        // heights 5, 4, 1 then -4, crossing 1/5 of the way through the last step
+        let impact = t.impact.unwrap();
+        assert!(eq_f64(impact.tick, 2.2));
+        assert!(eq_f64(impact.position.0, 2.2));
+        assert!(eq_f64(impact.velocity.1, -3.4));
+    }
+
+    #[test]
+    fn summary_of_a_flight() {
+        let s = summarize(&drop_from(5.0)).unwrap();
+        assert!(eq_f64(s.flight_time, 2.2));
+        assert!(eq_f64(s.max_height, 5.0));
+        assert!(eq_f64(s.range, 2.2));
+        assert!(s.impact_velocity.is_some());
+    }
+
+    #[test]
+    fn flights_that_never_land_have_no_impact() {
+        let env = environment(vector(0.0, 0.0, 0.0), vector(0.0, 0.0, 0.0));
+        let start = projectile(point(0.0, 1.0, 0.0), vector(1.0, 0.0, 0.0));
+        let t = simulate(&env, &start, 0.5, Integrator::Euler, 10, 10);
+        assert_eq!(t.samples.len(), 10);
+        let s = summarize(&t).unwrap();
+        assert!(s.impact_velocity.is_none());
+        assert!(eq_f64(s.flight_time, 4.5));
+    }
+
+    #[test]
+    fn flights_that_start_underground_have_no_summary() {
+        let t = drop_from(-1.0);
+        assert!(t.samples.is_empty());
+        assert_eq!(summarize(&t), None);
+        assert_eq!(
+            summaries_to_csv(std::slice::from_ref(&t)).lines().nth(1),
+            Some("0,,,,,,,")
+        );
+        assert!(trajectories_to_json(&[t]).contains("{\"summary\":null,\"samples\":[]}"));
+    }
+
+    #[test]
+    fn long_flights_are_thinned_evenly() {
+        let env = environment(vector(0.0, 0.0, 0.0), vector(0.0, 0.0, 0.0));
+        let start = projectile(point(0.0, 1.0, 0.0), vector(1.0, 0.0, 0.0));
+        let t = simulate(&env, &start, 1.0, Integrator::Euler, 1000, 10);
+        let steps: Vec<_> = t.samples.iter().map(|s| s.step).collect();
+        assert_eq!(steps, vec![0, 128, 256, 384, 512, 640, 768, 896, 999]);
+        assert!(eq_f64(t.samples[8].position.0, 999.0));
+        // thinning doesn't move the impact
+        let landing = drop_from(5.0);
+        let env = environment(vector(0.0, -2.0, 0.0), vector(0.0, 0.0, 0.0));
+        let start = projectile(point(0.0, 5.0, 0.0), vector(1.0, 1.0, 0.0));
+        let thinned = simulate(&env, &start, 1.0, Integrator::SemiImplicit, 100, 2);
+        assert_eq!(thinned.impact, landing.impact);
+        assert_eq!(thinned.samples.len(), 2);
+        assert_eq!(thinned.samples[1], landing.samples[2]);
+    }
+
+    #[test]
+    fn thinning_keeps_the_peak() {
+        // rises 9, 8, 7, ... per tick, peaking at 45 on step 9
+        let env = environment(vector(0.0, -1.0, 0.0), vector(0.0, 0.0, 0.0));
+        let start = projectile(point(0.0, 0.0, 0.0), vector(0.0, 10.0, 0.0));
+        let fly = |max_samples| {
+            simulate(
+                &env,
+                &start,
+                1.0,
+                Integrator::SemiImplicit,
+                100,
+                max_samples,
+            )
+        };
+        let (full, thinned) = (fly(100), fly(3));
+        let sampled_peak = |t: &Trajectory| {
+            t.samples
+                .iter()
+                .map(|s| s.position.1)
+                .fold(f64::NEG_INFINITY, f64::max)
+        };
+        assert!(eq_f64(sampled_peak(&full), 45.0));
+        assert!(sampled_peak(&thinned) < 45.0);
+        assert_eq!(thinned.max_height, Some(45.0));
+        assert!(eq_f64(summarize(&thinned).unwrap().max_height, 45.0));
+    }
+
+    #[test]
+    fn csv_has_a_row_per_sample() {
+        let trajectories = [drop_from(4.0), drop_from(5.0)];
+        let csv = samples_to_csv(&trajectories);
+        assert_eq!(csv.lines().count(), 1 + 3 + 3);
+        assert_eq!(
+            csv.lines().nth(1),
+            Some("0,0,0,0,4,0,1,1,0,1.4142135623730951")
+        );
+        let summaries = summaries_to_csv(&trajectories);
+        assert_eq!(summaries.lines().count(), 3);
+        assert!(summaries.lines().nth(1).unwrap().starts_with("0,2,4,2,"));
+    }
+
+    #[test]
+    fn json_has_summaries_and_samples() {
+        let json = trajectories_to_json(&[drop_from(4.0)]);
+        assert!(json.starts_with("{\"bullets\":[{\"summary\":{\"flight_time\":2,"));
+        assert_eq!(json.matches("\"step\":").count(), 3);
+        assert_eq!(json_number(f64::NAN), "null");
+    }
+}
@@ -0,0 +1,180 @@
+use crate::matrix::{Matrix4x1, Matrix4x4};
+use crate::tuple::Tuple;
+
+///
+/// Creates a matrix that moves points by (x, y, z); vectors are unaffected
+///
+pub fn translation(x: f64, y: f64, z: f64) -> Matrix4x4 {
+    let mut m = Matrix4x4::identity();
+    m[(0, 3)] = x;
+    m[(1, 3)] = y;
+    m[(2, 3)] = z;
+    m
+}
+
+///
+/// Creates a matrix that scales each component by (x, y, z)
+///
+pub fn scaling(x: f64, y: f64, z: f64) -> Matrix4x4 {
+    let mut m = Matrix4x4::identity();
+    m[(0, 0)] = x;
+    m[(1, 1)] = y;
+    m[(2, 2)] = z;
+    m
+}
+
+///
+/// Creates a matrix that rotates `radians` around the x axis (left-handed)
+///
+pub fn rotation_x(radians: f64) -> Matrix4x4 {
+    let (sin, cos) = radians.sin_cos();
+    let mut m = Matrix4x4::identity();
+    m[(1, 1)] = cos;
+    m[(1, 2)] = -sin;
+    m[(2, 1)] = sin;
+    m[(2, 2)] = cos;
+    m
+}
+
+///
+/// Creates a matrix that rotates `radians` around the y axis (left-handed)
+///
+pub fn rotation_y(radians: f64) -> Matrix4x4 {
+    let (sin, cos) = radians.sin_cos();
+    let mut m = Matrix4x4::identity();
+    m[(0, 0)] = cos;
+    m[(0, 2)] = sin;
+    m[(2, 0)] = -sin;
+    m[(2, 2)] = cos;
+    m
+}
+
+///
+/// Creates a matrix that rotates `radians` around the z axis (left-handed)
+///
+pub fn rotation_z(radians: f64) -> Matrix4x4 {
+    let (sin, cos) = radians.sin_cos();
+    let mut m = Matrix4x4::identity();
+    m[(0, 0)] = cos;
+    m[(0, 1)] = -sin;
+    m[(1, 0)] = sin;
+    m[(1, 1)] = cos;
+    m
+}
+
+///
+/// Creates a matrix that moves each component in proportion to the other two,
+/// e.g. `xy` moves x in proportion to y
+///
+pub fn shearing(xy: f64, xz: f64, yx: f64, yz: f64, zx: f64, zy: f64) -> Matrix4x4 {
+    let mut m = Matrix4x4::identity();
+    m[(0, 1)] = xy;
+    m[(0, 2)] = xz;
+    m[(1, 0)] = yx;
+    m[(1, 2)] = yz;
+    m[(2, 0)] = zx;
+    m[(2, 1)] = zy;
+    m
+}
+
+///
+/// Multiplies a transformation matrix by a point or vector
+///
+pub fn transform(m: &Matrix4x4, t: Tuple) -> Tuple {
+    let r = m * Matrix4x1::new(t.0, t.1, t.2, t.3);
+    (r[0], r[1], r[2], r[3])
+}
+
+///
+/// Combines transformations so they apply in the order given, i.e. the first one applies first
+///
+pub fn chain(transforms: &[Matrix4x4]) -> Matrix4x4 {
+    transforms
+        .iter()
+        .fold(Matrix4x4::identity(), |acc, m| m * acc)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tuple::{eq_tup, point, vector};
+    use std::f64::consts::PI;
+
+    #[test]
+    fn translation_moves_points() {
+        let p = transform(&translation(5.0, -3.0, 2.0), point(-3.0, 4.0, 5.0));
+        assert!(eq_tup(p, point(2.0, 1.0, 7.0)))
+    }
+
+    #[test]
+    fn translation_does_not_move_vectors() {
+        let v = transform(&translation(5.0, -3.0, 2.0), vector(-3.0, 4.0, 5.0));
+        assert!(eq_tup(v, vector(-3.0, 4.0, 5.0)))
+    }
+
+    #[test]
+    fn scaling_a_point() {
+        let p = transform(&scaling(2.0, 3.0, 4.0), point(-4.0, 6.0, 8.0));
+        assert!(eq_tup(p, point(-8.0, 18.0, 32.0)))
+    }
+
+    #[test]
+    fn reflection_is_negative_scaling() {
+        let p = transform(&scaling(-1.0, 1.0, 1.0), point(2.0, 3.0, 4.0));
+        assert!(eq_tup(p, point(-2.0, 3.0, 4.0)))
+    }
+
+    #[test]
+    fn rotating_around_x() {
+        let p = point(0.0, 1.0, 0.0);
+        let half = transform(&rotation_x(PI / 4.0), p);
+        let full = transform(&rotation_x(PI / 2.0), p);
+        let r = 2.0_f64.sqrt() / 2.0;
+        assert!(eq_tup(half, point(0.0, r, r)));
+        assert!(eq_tup(full, point(0.0, 0.0, 1.0)));
+    }
+
+    #[test]
+    fn rotating_around_y() {
+        let p = point(0.0, 0.0, 1.0);
+        let half = transform(&rotation_y(PI / 4.0), p);
+        let full = transform(&rotation_y(PI / 2.0), p);
+        let r = 2.0_f64.sqrt() / 2.0;
+        assert!(eq_tup(half, point(r, 0.0, r)));
+        assert!(eq_tup(full, point(1.0, 0.0, 0.0)));
+    }
+
+    #[test]
+    fn rotating_around_z() {
+        let p = point(0.0, 1.0, 0.0);
+        let half = transform(&rotation_z(PI / 4.0), p);
+        let full = transform(&rotation_z(PI / 2.0), p);
+        let r = 2.0_f64.sqrt() / 2.0;
+        assert!(eq_tup(half, point(-r, r, 0.0)));
+        assert!(eq_tup(full, point(-1.0, 0.0, 0.0)));
+    }
+
+    #[test]
+    fn shearing_moves_each_component_in_proportion() {
+        let p = point(2.0, 3.0, 4.0);
+        assert!(eq_tup(
+            transform(&shearing(1.0, 0.0, 0.0, 0.0, 0.0, 0.0), p),
+            point(5.0, 3.0, 4.0)
+        ));
+        assert!(eq_tup(
+            transform(&shearing(0.0, 0.0, 0.0, 0.0, 0.0, 1.0), p),
+            point(2.0, 3.0, 7.0)
+        ));
+    }
+
+    #[test]
+    fn chained_transformations_apply_in_order() {
+        let p = point(1.0, 0.0, 1.0);
+        let m = chain(&[
+            rotation_x(PI / 2.0),
+            scaling(5.0, 5.0, 5.0),
+            translation(10.0, 5.0, 7.0),
+        ]);
+        assert!(eq_tup(transform(&m, p), point(15.0, 0.0, 7.0)))
+    }
+}
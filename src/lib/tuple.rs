@@ -165,6 +165,13 @@ pub fn cross_product(left: Tuple, right: Tuple) -> Tuple {
     )
 }
 
+///
+/// Reflects a vector around a normal
+///
+pub fn reflect(v: Tuple, normal: Tuple) -> Tuple {
+    sub_tup(v, mul_tup(normal, 2.0 * dot_product(v, normal)))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -567,4 +574,19 @@ mod tests {
         assert!(eq_f64(-2.0, v3.1));
         assert!(eq_f64(1.0, v3.2));
     }
+
+    #[test]
+    fn reflect_at_45_degrees() {
+        let v = vector(1.0, -1.0, 0.0);
+        let n = vector(0.0, 1.0, 0.0);
+        assert!(eq_tup(reflect(v, n), vector(1.0, 1.0, 0.0)))
+    }
+
+    #[test]
+    fn reflect_off_a_slanted_surface() {
+        let v = vector(0.0, -1.0, 0.0);
+        let r = 2.0_f64.sqrt() / 2.0;
+        let n = vector(r, r, 0.0);
+        assert!(eq_tup(reflect(v, n), vector(1.0, 0.0, 0.0)))
+    }
 }
@@ -1,10 +1,12 @@
 use crate::commands::{get_subcommands, global_args, run};
-use crate::programs::MakeImage;
+use crate::error::Error;
 use clap::Command;
 
 mod commands;
-mod lib;
+mod error;
 mod programs;
+#[cfg(test)]
+mod test_util;
 
 fn main() {
     let matches = Command::new("ray-tracer")
@@ -15,7 +17,16 @@ fn main() {
         .arg_required_else_help(true)
         .args(global_args())
         .subcommands(get_subcommands())
-        .get_matches();
+        .try_get_matches();
 
-    run(matches)
+    if let Err(e) = matches.map_err(Error::from).and_then(run) {
+        match &e {
+            // clap formats its own messages, with the usage line, and sends help to stdout
+            Error::Usage(usage) => {
+                let _ = usage.print();
+            }
+            _ => eprintln!("error: {}", e),
+        }
+        std::process::exit(e.exit_code());
+    }
 }
@@ -1,12 +1,30 @@
-use crate::commands::width_and_height;
-use crate::lib::canvas::{
-    convert_to_bottom_up_coordinates, neighbors, new_image_buffer, point_is_in_rectangle,
+use crate::commands::{
+    parse_arg, parse_range, parse_triple, parse_triples, validate_f64, validate_non_negative_f64,
+    validate_positive_f64, validate_range, validate_triple, width_and_height, MAX_RANGE_LEN,
 };
-use crate::lib::color::{color, color_into_rgb};
-use crate::lib::tuple::{add_tup, mul_tup, normalize, point, sub_tup, vector};
+use crate::error::{Error, Result};
 use crate::programs::MakeImage;
-use clap::{Arg, ArgMatches, Command};
-use image::{ImageBuffer, Rgb};
+use clap::{Arg, ArgMatches};
+use image::{ImageBuffer, Pixel, Rgb};
+use ray_tracer::canvas::{hdr_from_rgb8, layers, new_image_buffer, Layers};
+use ray_tracer::chart::{
+    axes, clear_outside, draw_axes, draw_grid, fit_viewport, plot_area, rect, scaled_viewport,
+    Rect, Viewport,
+};
+use ray_tracer::color::{color, color_into_rgb, palette_color, Color};
+use ray_tracer::draw::{line_aa, polyline};
+use ray_tracer::font::{draw_text, text_size};
+use ray_tracer::projectile::{
+    analytic_position, integrator_from_name, projectile, Environment, Integrator, Projectile,
+    INTEGRATORS,
+};
+use ray_tracer::trajectory::{
+    data_format_from_path, samples_to_csv, simulate, summaries_to_csv, trajectories_to_json,
+    DataFormat, Trajectory, DATA_EXTENSIONS,
+};
+use ray_tracer::tuple::{magnitude, mul_tup, normalize, point, vector, Tuple};
+use rayon::prelude::*;
+use std::path::Path;
 
 pub struct Arch {}
 
@@ -18,81 +36,844 @@ pub const YL: &str = "y";
 pub const Y: char = 'y';
 pub const ML: &str = "m";
 pub const M: char = 'm';
+pub const GRAVITYL: &str = "gravity";
+pub const WINDL: &str = "wind";
+pub const DRAGL: &str = "drag";
+pub const QUADRATIC_DRAGL: &str = "quadratic-drag";
+pub const LAUNCHL: &str = "launch";
+pub const DTL: &str = "dt";
+pub const INTEGRATORL: &str = "integrator";
+pub const ANALYTICL: &str = "analytic";
+pub const VELOCITYL: &str = "velocity";
+pub const ANGLESL: &str = "angles";
+pub const LEGENDL: &str = "legend";
+pub const AXESL: &str = "axes";
+pub const FITL: &str = "fit";
+pub const SCALEL: &str = "scale";
+pub const DATA_OUTL: &str = "data-out";
+
+const SWATCH: u32 = 10;
+const PATH_WIDTH: f64 = 5.0;
+const GRID: Color = (0.25, 0.25, 0.25);
+
+///
+/// The room --fit leaves between the flights and the edge of the plot, in pixels
+///
+const FIT_MARGIN: f64 = 10.0;
+
+///
+/// Stops a simulation that never leaves the canvas, e.g. one without gravity
+///
+const MAX_STEPS: usize = 1_000_000;
+
+///
+/// The samples kept across all bullets; each bullet gets an equal share, and longer flights
+/// are thinned to fit it
+///
+const MAX_SAMPLES: usize = 1_000_000;
 
 impl MakeImage for Arch {
-    fn subcommand() -> Command<'static> {
-        Command::new(ARCH)
-            .about(
-                "Fires a bullet in an arching trajectory and writes the trajectory to the canvas",
-            )
-            .arg(
-                Arg::new(XL)
-                    .long(XL)
-                    .short(X)
-                    .help("The X component of the initial velocity")
-                    .required(false)
-                    .default_value("5"),
-            )
-            .arg(
-                Arg::new(YL)
-                    .long(YL)
-                    .short(Y)
-                    .help("The Y component of the initial velocity")
-                    .required(false)
-                    .default_value("15"),
-            )
-            .arg(
-                Arg::new(ML)
-                    .long(ML)
-                    .short(M)
-                    .help("The magnitude to fire the trajectory 'bullet' at")
-                    .required(false)
-                    .default_value("14.5"),
-            )
-    }
-
-    fn make(matches: &ArgMatches) -> ImageBuffer<Rgb<u8>, Vec<u8>> {
-        let (mut w, mut h) = width_and_height(matches);
-        let (mut x, mut y) = x_and_y(matches);
-        let m = magnitude(matches);
-        let color = color_into_rgb(color(0.1, 0.8, 0.2));
-        let mut canvas = new_image_buffer(w, h);
-
-        let mut velocity = mul_tup(normalize(vector(x as f64, y as f64, 0.0)), m);
-        let mut start = point(10.0, 10.0, 0.0);
-        let drag = vector(-0.01, -0.1, 0.0);
-
-        while start.1 >= 0.0 {
-            let (x, y) = convert_to_bottom_up_coordinates(
-                start.0.round() as i32,
-                start.1.round() as i32,
-                w as i32,
-                h as i32,
-            );
+    fn name(&self) -> &'static str {
+        ARCH
+    }
+
+    fn about(&self) -> &'static str {
+        "Fires a bullet in an arching trajectory and writes the trajectory to the canvas"
+    }
+
+    fn args(&self) -> Vec<Arg<'static>> {
+        vec![
+            Arg::new(XL)
+                .long(XL)
+                .short(X)
+                .help("The X component of the initial velocity's direction")
+                .required(false)
+                .validator(validate_f64)
+                .allow_hyphen_values(true)
+                .default_value("5"),
+            Arg::new(YL)
+                .long(YL)
+                .short(Y)
+                .help("The Y component of the initial velocity's direction")
+                .required(false)
+                .validator(validate_f64)
+                .allow_hyphen_values(true)
+                .default_value("15"),
+            Arg::new(ANGLESL)
+                .long(ANGLESL)
+                .help(
+                    "Launch angles in degrees above the horizon instead of -x and -y, \
+                    either one angle or a sweep like 15:75:15",
+                )
+                .required(false)
+                .takes_value(true)
+                .validator(validate_range)
+                .allow_hyphen_values(true),
+            Arg::new(ML)
+                .long(ML)
+                .short(M)
+                .help(
+                    "The magnitude to fire the trajectory 'bullet' at, \
+                    either one magnitude or a sweep like 10:15:2.5",
+                )
+                .required(false)
+                .validator(validate_range)
+                .default_value("14.5"),
+            Arg::new(VELOCITYL)
+                .long(VELOCITYL)
+                .help("Fire a bullet with exactly this x,y,z velocity. Can be given many times")
+                .required(false)
+                .takes_value(true)
+                .multiple_occurrences(true)
+                .validator(validate_triple)
+                .allow_hyphen_values(true)
+                .conflicts_with_all(&[XL, YL, ANGLESL, ML]),
+            Arg::new(DATA_OUTL)
+                .long(DATA_OUTL)
+                .help(
+                    "Also write every simulation step to a .csv or .json file. JSON includes \
+                    each bullet's flight time, max height, range and impact velocity; for CSV \
+                    those go to <name>.summary.csv. Takes the same placeholders as --output",
+                )
+                .required(false)
+                .takes_value(true),
+            Arg::new(LEGENDL)
+                .long(LEGENDL)
+                .help(
+                    "Draw a swatch of each bullet's color in the top left, in firing order, \
+                    labelled with its launch angle and speed",
+                )
+                .required(false),
+            Arg::new(FITL)
+                .long(FITL)
+                .help(
+                    "Scale and pan so every flight fits the canvas with a margin, \
+                    keeping x and y at the same scale",
+                )
+                .required(false)
+                .conflicts_with(SCALEL),
+            Arg::new(SCALEL)
+                .long(SCALEL)
+                .help("The pixels per simulation unit, with the origin at the bottom left")
+                .required(false)
+                .validator(validate_positive_f64)
+                .default_value("1"),
+            Arg::new(AXESL)
+                .long(AXESL)
+                .help("Draw x and y axes with gridlines, ticks and labels in simulation units")
+                .required(false),
+            Arg::new(GRAVITYL)
+                .long(GRAVITYL)
+                .help("The acceleration due to gravity, as x,y,z per tick")
+                .required(false)
+                .validator(validate_triple)
+                .allow_hyphen_values(true)
+                .default_value("0,-0.1,0"),
+            Arg::new(WINDL)
+                .long(WINDL)
+                .help(
+                    "The velocity of the air, as x,y,z per tick. It pushes the bullet along \
+                    through --drag and --quadratic-drag, which act on the bullet's velocity \
+                    relative to the air",
+                )
+                .required(false)
+                .validator(validate_triple)
+                .allow_hyphen_values(true)
+                .default_value("0,0,0"),
+            Arg::new(DRAGL)
+                .long(DRAGL)
+                .help("The air drag proportional to the velocity")
+                .required(false)
+                .validator(validate_non_negative_f64)
+                .default_value("0"),
+            Arg::new(QUADRATIC_DRAGL)
+                .long(QUADRATIC_DRAGL)
+                .help("The air drag proportional to the square of the velocity")
+                .required(false)
+                .validator(validate_non_negative_f64)
+                .default_value("0"),
+            Arg::new(LAUNCHL)
+                .long(LAUNCHL)
+                .help("Where the bullet is fired from, as x,y,z with the origin at the bottom left")
+                .required(false)
+                .validator(validate_triple)
+                .allow_hyphen_values(true)
+                .default_value("10,10,0"),
+            Arg::new(DTL)
+                .long(DTL)
+                .help("The size of each simulation step, in ticks")
+                .required(false)
+                .validator(validate_positive_f64)
+                .default_value("1"),
+            Arg::new(INTEGRATORL)
+                .long(INTEGRATORL)
+                .help("How each simulation step is integrated")
+                .required(false)
+                .possible_values(INTEGRATORS)
+                .default_value("semi-implicit"),
+            Arg::new(ANALYTICL)
+                .long(ANALYTICL)
+                .help("Overlay the exact parabola the bullet would follow without drag")
+                .required(false),
+        ]
+    }
+
+    fn make(&self, matches: &ArgMatches) -> Result<Layers> {
+        self.make_frame(matches, 0, 1)
+    }
+
+    fn make_frame(&self, matches: &ArgMatches, frame: u32, frames: u32) -> Result<Layers> {
+        let canvas = draw(matches, &fly(matches)?, frame, frames)?;
+        Ok(layers(hdr_from_rgb8(canvas)))
+    }
+
+    ///
+    /// Each frame draws the bullets a little further along, until the last frame shows the
+    /// whole of the longest flight. The flights are simulated once for all of them
+    ///
+    fn make_frames(
+        &self,
+        matches: &ArgMatches,
+        frames: u32,
+        emit: &mut dyn FnMut(Layers) -> Result<()>,
+    ) -> Result<Vec<String>> {
+        let flights = fly(matches)?;
+        for frame in 0..frames {
+            let canvas = draw(matches, &flights, frame, frames)?;
+            emit(layers(hdr_from_rgb8(canvas)))?;
+        }
+        Ok(match data_outputs(matches)? {
+            Some((format, _)) => data_contents(format, &flights.trajectories),
+            None => vec![],
+        })
+    }
+
+    fn extra_outputs(&self, matches: &ArgMatches) -> Result<Vec<String>> {
+        Ok(data_outputs(matches)?
+            .map(|(_, paths)| paths)
+            .unwrap_or_default())
+    }
+}
+
+///
+/// Everything about a run that stays the same from frame to frame
+///
+struct Flights {
+    velocities: Vec<Tuple>,
+    trajectories: Vec<Trajectory>,
+    /// The analytic path of each bullet, if --analytic was given, as ticks and positions
+    exact: Vec<Vec<(f64, Tuple)>>,
+    /// The tick the longest flight ends at
+    longest: f64,
+    area: Rect,
+    view: Viewport,
+}
+
+///
+/// Simulates every bullet and works out where on the canvas the flights go
+///
+fn fly(matches: &ArgMatches) -> Result<Flights> {
+    let (w, h) = width_and_height(matches)?;
+    let velocities = velocities(matches)?;
+    let env = environment(matches)?;
+    let (dt, integrator) = dt_and_integrator(matches)?;
+    let (lx, ly, lz) = parse_triple(matches, LAUNCHL)?;
+    if ly < 0.0 {
+        return Err(Error::invalid_argument(
+            LAUNCHL,
+            matches.value_of(LAUNCHL).unwrap_or_default(),
+            "the launch point can't be below the ground at y = 0",
+        ));
+    }
+
+    let starts: Vec<Projectile> = velocities
+        .iter()
+        .map(|v| projectile(point(lx, ly, lz), *v))
+        .collect();
+    let max_samples = MAX_SAMPLES / starts.len().max(1);
+    let trajectories: Vec<Trajectory> = starts
+        .par_iter()
+        .map(|start| simulate(&env, start, dt, integrator, MAX_STEPS, max_samples))
+        .collect();
+    let longest = trajectories
+        .iter()
+        .filter_map(|t| t.samples.last())
+        .map(|s| s.tick)
+        .fold(0.0, f64::max);
+    // the exact path needs no more points than the simulation took steps
+    let steps = (longest / dt).ceil() as usize + 1;
+    let exact: Vec<Vec<(f64, Tuple)>> = if matches.is_present(ANALYTICL) {
+        starts
+            .iter()
+            .map(|start| analytic_path(&env, start, longest, steps.min(max_samples)))
+            .collect()
+    } else {
+        vec![]
+    };
+
+    // axes take their margins out of the canvas
+    let area = if matches.is_present(AXESL) {
+        plot_area(w, h)
+    } else {
+        rect(0.0, 0.0, w as f64, h as f64)
+    };
+    // fitting looks at everything the last frame draws, so the view holds still across frames
+    let view = if matches.is_present(FITL) {
+        let points: Vec<_> = trajectories
+            .iter()
+            .flat_map(|t| t.samples.iter().map(|s| s.position))
+            .chain(exact.iter().flatten().map(|(_, position)| *position))
+            .map(|p| (p.0, p.1))
+            .collect();
+        fit_viewport(&points, area, FIT_MARGIN).unwrap_or_else(|| scaled_viewport(area, 1.0))
+    } else {
+        scaled_viewport(area, parse_arg(matches, SCALEL)?)
+    };
 
-            if !point_is_in_rectangle(x, y, w as i32, h as i32) {
-                break;
+    Ok(Flights {
+        velocities,
+        trajectories,
+        exact,
+        longest,
+        area,
+        view,
+    })
+}
+
+///
+/// Draws one frame of the flights, `frame` counting from 0 up to `frames - 1`
+///
+fn draw(
+    matches: &ArgMatches,
+    flights: &Flights,
+    frame: u32,
+    frames: u32,
+) -> Result<ImageBuffer<Rgb<u8>, Vec<u8>>> {
+    let (w, h) = width_and_height(matches)?;
+    let analytic_color = color_into_rgb(color(0.9, 0.9, 0.9)).to_rgba();
+    let mut canvas = new_image_buffer(w, h);
+    let (area, view) = (flights.area, flights.view);
+    let until = flights.longest * (frame + 1) as f64 / frames as f64;
+
+    if matches.is_present(AXESL) {
+        draw_grid(&mut canvas, &view, color_into_rgb(GRID).to_rgba());
+    }
+
+    for (i, trajectory) in flights.trajectories.iter().enumerate() {
+        let color = color_into_rgb(palette_color(i)).to_rgba();
+        let path: Vec<_> = trajectory
+            .samples
+            .iter()
+            .take_while(|s| s.tick <= until)
+            .map(|s| view.to_canvas(s.position.0, s.position.1))
+            .collect();
+        polyline(&mut canvas, &path, PATH_WIDTH, color);
+
+        if let Some(exact) = flights.exact.get(i) {
+            // drawn on top and thinner, so the simulation's drift shows around it
+            let points: Vec<_> = exact
+                .iter()
+                .take_while(|(tick, _)| *tick <= until)
+                .map(|(_, position)| view.to_canvas(position.0, position.1))
+                .collect();
+            for segment in points.windows(2) {
+                line_aa(&mut canvas, segment[0], segment[1], analytic_color);
             }
+        }
+    }
 
-            for pt in neighbors(x, y, w as i32, h as i32, 2) {
-                canvas.put_pixel(pt.0 as u32, pt.1 as u32, color);
+    if matches.is_present(AXESL) {
+        clear_outside(&mut canvas, &area, Rgb([0, 0, 0]));
+        let white = color_into_rgb(color(1.0, 1.0, 1.0)).to_rgba();
+        draw_axes(&mut canvas, &view, &axes("x", "y", white));
+    }
+
+    if matches.is_present(LEGENDL) {
+        draw_legend(&mut canvas, &area, &flights.velocities);
+    }
+
+    Ok(canvas)
+}
+
+///
+/// The exact position at `count` evenly spaced ticks up to `until`, with their ticks, until
+/// the bullet falls below the ground
+///
+fn analytic_path(
+    env: &Environment,
+    start: &Projectile,
+    until: f64,
+    count: usize,
+) -> Vec<(f64, Tuple)> {
+    let count = count.max(2);
+    (0..count)
+        .map(|i| until * i as f64 / (count - 1) as f64)
+        .map(|tick| (tick, analytic_position(env, start, tick)))
+        .take_while(|(_, position)| position.1 >= 0.0)
+        .collect()
+}
+
+///
+/// The format of --data-out, following its extension, and the files it's written to: the
+/// samples, and for CSV the summaries next to them in `<name>.summary.csv`
+///
+fn data_outputs(matches: &ArgMatches) -> Result<Option<(DataFormat, Vec<String>)>> {
+    let Some(value) = matches.value_of(DATA_OUTL) else {
+        return Ok(None);
+    };
+    let path = Path::new(value);
+    let format = data_format_from_path(path).ok_or_else(|| {
+        Error::invalid_argument(
+            DATA_OUTL,
+            value,
+            &format!(
+                "the extension must be one of {}",
+                DATA_EXTENSIONS.join(", ")
+            ),
+        )
+    })?;
+    let paths = match format {
+        DataFormat::Csv => vec![
+            value.to_string(),
+            path.with_extension("summary.csv").display().to_string(),
+        ],
+        DataFormat::Json => vec![value.to_string()],
+    };
+    Ok(Some((format, paths)))
+}
+
+///
+/// The contents of each file in `data_outputs`, in the same order
+///
+fn data_contents(format: DataFormat, trajectories: &[Trajectory]) -> Vec<String> {
+    match format {
+        DataFormat::Csv => vec![samples_to_csv(trajectories), summaries_to_csv(trajectories)],
+        DataFormat::Json => vec![trajectories_to_json(trajectories)],
+    }
+}
+
+fn dt_and_integrator(matches: &ArgMatches) -> Result<(f64, Integrator)> {
+    let dt = parse_arg(matches, DTL)?;
+    let name = matches.value_of(INTEGRATORL).unwrap_or_default();
+    let integrator = integrator_from_name(name).ok_or_else(|| {
+        Error::invalid_argument(
+            INTEGRATORL,
+            name,
+            &format!("expected one of {}", INTEGRATORS.join(", ")),
+        )
+    })?;
+    Ok((dt, integrator))
+}
+
+///
+/// Stacks a square of each bullet's color down the top left corner of the plot area, each
+/// labelled with the bullet's launch angle and speed, as far as the area allows
+///
+fn draw_legend(canvas: &mut ImageBuffer<Rgb<u8>, Vec<u8>>, area: &Rect, velocities: &[Tuple]) {
+    let (left, top) = (area.left as u32, area.top as u32);
+    let (right, bottom) = (left + area.width as u32, top + area.height as u32);
+    let gap = SWATCH / 2;
+    let white = color_into_rgb(color(1.0, 1.0, 1.0)).to_rgba();
+    for (i, v) in velocities.iter().enumerate() {
+        let (x, y) = (left + gap, top + gap + i as u32 * (SWATCH + gap));
+        if y + SWATCH > bottom || x + SWATCH > right {
+            break;
+        }
+        let color = color_into_rgb(palette_color(i));
+        for py in y..y + SWATCH {
+            for px in x..x + SWATCH {
+                canvas.put_pixel(px, py, color);
             }
+        }
+        let label = format!("{:.0}° {:.1}", v.1.atan2(v.0).to_degrees(), magnitude(*v));
+        let (_, th) = text_size(&label, 1);
+        let origin = (
+            (x + SWATCH + gap) as i32,
+            (y + SWATCH / 2) as i32 - th as i32 / 2,
+        );
+        draw_text(canvas, origin, &label, 1, white);
+    }
+}
+
+///
+/// The initial velocity of every bullet to fire: the --velocity list if given, otherwise each
+/// direction (from --angles, or -x and -y) at each magnitude
+///
+fn velocities(matches: &ArgMatches) -> Result<Vec<Tuple>> {
+    if matches.is_present(VELOCITYL) {
+        let velocities = parse_triples(matches, VELOCITYL)?;
+        return Ok(velocities
+            .into_iter()
+            .map(|(x, y, z)| vector(x, y, z))
+            .collect());
+    }
 
-            canvas.put_pixel(x as u32, y as u32, color);
-            velocity = add_tup(velocity, drag);
-            start = add_tup(start, velocity);
+    let directions = if matches.is_present(ANGLESL) {
+        parse_range(matches, ANGLESL)?
+            .into_iter()
+            .map(|a| vector(a.to_radians().cos(), a.to_radians().sin(), 0.0))
+            .collect()
+    } else {
+        let (x, y) = x_and_y(matches)?;
+        if x == 0.0 && y == 0.0 {
+            return Err(Error::invalid_argument(
+                XL,
+                "0",
+                "the initial velocity needs a direction, so x and y can't both be zero",
+            ));
         }
+        vec![normalize(vector(x, y, 0.0))]
+    };
+    let magnitudes = parse_range(matches, ML)?;
 
-        canvas
+    if directions.len() * magnitudes.len() > MAX_RANGE_LEN {
+        return Err(Error::invalid_argument(
+            ML,
+            matches.value_of(ML).unwrap_or_default(),
+            &format!("can't fire more than {} bullets at once", MAX_RANGE_LEN),
+        ));
     }
+    Ok(directions
+        .iter()
+        .flat_map(|d| magnitudes.iter().map(move |m| mul_tup(*d, *m)))
+        .collect())
+}
+
+fn x_and_y(matches: &ArgMatches) -> Result<(f64, f64)> {
+    let x = parse_arg(matches, XL)?;
+    let y = parse_arg(matches, YL)?;
+    Ok((x, y))
 }
 
-fn x_and_y(matches: &ArgMatches) -> (u32, u32) {
-    let x = matches.value_of(XL).unwrap().parse::<u32>().unwrap();
-    let y = matches.value_of(YL).unwrap().parse::<u32>().unwrap();
-    (x, y)
+fn environment(matches: &ArgMatches) -> Result<Environment> {
+    let (gx, gy, gz) = parse_triple(matches, GRAVITYL)?;
+    let (wx, wy, wz) = parse_triple(matches, WINDL)?;
+    Ok(Environment {
+        gravity: vector(gx, gy, gz),
+        wind: vector(wx, wy, wz),
+        linear_drag: parse_arg(matches, DRAGL)?,
+        quadratic_drag: parse_arg(matches, QUADRATIC_DRAGL)?,
+    })
 }
 
-fn magnitude(matches: &ArgMatches) -> f64 {
-    matches.value_of(ML).unwrap().parse::<f64>().unwrap()
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{program_matches, try_matches};
+    use ray_tracer::canvas::quantize;
+
+    fn render(args: &[&str]) -> ImageBuffer<Rgb<u8>, Vec<u8>> {
+        let matches = program_matches(args);
+        quantize(&Arch {}.make(&matches).unwrap().color)
+    }
+
+    fn count(canvas: &ImageBuffer<Rgb<u8>, Vec<u8>>, color: Rgb<u8>) -> usize {
+        canvas.pixels().filter(|p| **p == color).count()
+    }
+
+    #[test]
+    fn analytic_overlay_is_drawn_on_request() {
+        let args = ["test", "-w", "200", "-h", "200", "arch", "-m", "5"];
+        let plain = render(&args);
+        let overlaid = render(&[&args[..], &["--analytic", "--integrator", "euler"]].concat());
+        // the overlay is lighter than the path it is drawn over
+        let lighter = plain
+            .pixels()
+            .zip(overlaid.pixels())
+            .filter(|(a, b)| b.0[0] > a.0[0])
+            .count();
+        assert!(lighter > 10);
+    }
+
+    #[test]
+    fn fast_bullets_leave_a_connected_path() {
+        let canvas = render(&[
+            "test",
+            "-w",
+            "200",
+            "-h",
+            "200",
+            "arch",
+            "--velocity",
+            "40,0,0",
+            "--gravity",
+            "0,0,0",
+            "--wind",
+            "0,0,0",
+            "--launch",
+            "0,100,0",
+        ]);
+        let green = color_into_rgb(palette_color(0));
+        assert!((0..200).all(|x| *canvas.get_pixel(x, 100) == green));
+    }
+
+    #[test]
+    fn sweeps_fire_one_bullet_per_combination() {
+        let matches = program_matches(&["test", "arch", "--angles", "30:60:15", "-m", "5:10:5"]);
+        let v = velocities(&matches).unwrap();
+        assert_eq!(v.len(), 6);
+        assert!(v[0].0 > v[2].0 && v[0].1 < v[2].1);
+    }
+
+    #[test]
+    fn each_bullet_gets_its_own_color() {
+        let canvas = render(&[
+            "test",
+            "-w",
+            "200",
+            "-h",
+            "200",
+            "arch",
+            "--velocity",
+            "3,4,0",
+            "--velocity",
+            "-1.5,6,0",
+            "--launch",
+            "100,10,0",
+            "--legend",
+        ]);
+        for i in 0..2 {
+            assert!(
+                count(&canvas, color_into_rgb(palette_color(i)))
+                    > SWATCH as usize * SWATCH as usize
+            );
+        }
+        assert_eq!(count(&canvas, color_into_rgb(palette_color(2))), 0);
+    }
+
+    #[test]
+    fn axes_move_the_flight_into_the_plot_area() {
+        let args = [
+            "test", "-w", "200", "-h", "200", "arch", "--launch", "0,0,0",
+        ];
+        let green = color_into_rgb(palette_color(0));
+        // the launch point sits in the bottom left corner of whichever area is plotted in
+        assert_eq!(*render(&args).get_pixel(1, 198), green);
+        let charted = render(&[&args[..], &["--axes"]].concat());
+        let area = plot_area(200, 200);
+        let corner = (area.left as u32 + 1, (area.top + area.height) as u32 - 2);
+        assert_eq!(*charted.get_pixel(corner.0, corner.1), green);
+        assert_ne!(*charted.get_pixel(1, 198), green);
+        // tick labels and titles are drawn in the margins
+        let white = Rgb([255, 255, 255]);
+        assert!(count(&charted, white) > 100);
+    }
+
+    // the leftmost, rightmost, top and bottom pixels of a color
+    fn extent(canvas: &ImageBuffer<Rgb<u8>, Vec<u8>>, color: Rgb<u8>) -> (u32, u32, u32, u32) {
+        canvas
+            .enumerate_pixels()
+            .filter(|(_, _, p)| **p == color)
+            .fold((u32::MAX, 0, u32::MAX, 0), |(l, r, t, b), (x, y, _)| {
+                (l.min(x), r.max(x), t.min(y), b.max(y))
+            })
+    }
+
+    #[test]
+    fn fit_brings_a_flight_far_off_the_canvas_into_view() {
+        let args = [
+            "test",
+            "-w",
+            "100",
+            "-h",
+            "100",
+            "arch",
+            "--launch",
+            "5000,5000,0",
+        ];
+        let green = color_into_rgb(palette_color(0));
+        assert_eq!(count(&render(&args), green), 0);
+        let fitted = render(&[&args[..], &["--fit"]].concat());
+        let (left, right, top, bottom) = extent(&fitted, green);
+        // the fall is taller than it is wide, so it fills the height between the margins
+        let margin = (FIT_MARGIN - PATH_WIDTH) as u32;
+        assert!(top >= margin && bottom <= 100 - margin);
+        assert!(bottom - top > 70);
+        assert!(left > margin && right < 100 - margin);
+    }
+
+    #[test]
+    fn scale_sets_the_pixels_per_unit() {
+        let args = [
+            "test", "-w", "400", "-h", "200", "arch", "--angles", "10", "-m", "8", "--launch",
+            "0,10,0",
+        ];
+        let green = color_into_rgb(palette_color(0));
+        let (_, right, ..) = extent(&render(&[&args[..], &["--scale", "0.5"]].concat()), green);
+        let (_, full, ..) = extent(&render(&args), green);
+        assert!(full > 100);
+        assert!(
+            (right as f64 - full as f64 / 2.0).abs() < 4.0,
+            "{} {}",
+            right,
+            full
+        );
+    }
+
+    #[test]
+    fn fit_and_scale_conflict() {
+        let result = try_matches(&["test", "arch", "--fit", "--scale", "2"]);
+        assert!(result.is_err());
+    }
+
+    fn data_of(path: &str) -> (Vec<String>, Vec<String>) {
+        let matches = program_matches(&[
+            "test",
+            "-w",
+            "50",
+            "-h",
+            "50",
+            "arch",
+            "--angles",
+            "30:60:30",
+            "--data-out",
+            path,
+        ]);
+        let paths = Arch {}.extra_outputs(&matches).unwrap();
+        let contents = Arch {}.make_frames(&matches, 2, &mut |_| Ok(())).unwrap();
+        (paths, contents)
+    }
+
+    #[test]
+    fn data_files_follow_the_extension() {
+        let (paths, contents) = data_of("out/{scene}.csv");
+        assert_eq!(paths, vec!["out/{scene}.csv", "out/{scene}.summary.csv"]);
+        assert!(contents[0].starts_with("bullet,step,tick,"));
+        assert!(contents[0].lines().any(|l| l.starts_with("1,")));
+        assert_eq!(contents[1].lines().count(), 3);
+
+        let (paths, contents) = data_of("flight.json");
+        assert_eq!(paths, vec!["flight.json"]);
+        assert_eq!(contents.len(), 1);
+        assert_eq!(contents[0].matches("\"summary\"").count(), 2);
+    }
+
+    #[test]
+    fn launches_below_the_ground_are_rejected() {
+        let matches = program_matches(&["test", "arch", "--launch", "10,-5,0"]);
+        let err = Arch {}.make(&matches).unwrap_err();
+        assert_eq!(err.exit_code(), crate::error::EXIT_USAGE);
+    }
+
+    #[test]
+    fn data_needs_a_known_extension() {
+        let matches = program_matches(&["test", "arch", "--data-out", "flight.txt"]);
+        assert!(Arch {}.extra_outputs(&matches).is_err());
+    }
+
+    #[test]
+    fn frames_from_one_simulation_match_single_frames() {
+        let matches = program_matches(&[
+            "test",
+            "-w",
+            "80",
+            "-h",
+            "80",
+            "arch",
+            "--fit",
+            "--analytic",
+        ]);
+        let mut images = vec![];
+        Arch {}
+            .make_frames(&matches, 3, &mut |image| {
+                images.push(image);
+                Ok(())
+            })
+            .unwrap();
+        assert_eq!(images.len(), 3);
+        for (f, image) in images.iter().enumerate() {
+            assert_eq!(*image, Arch {}.make_frame(&matches, f as u32, 3).unwrap());
+        }
+    }
+
+    #[test]
+    fn frames_draw_more_of_the_flight() {
+        let matches = program_matches(&["test", "-w", "200", "-h", "200", "arch", "-m", "5"]);
+        let green = color_into_rgb(palette_color(0));
+        let drawn: Vec<usize> = (0..4)
+            .map(|f| {
+                let frame = Arch {}.make_frame(&matches, f, 4).unwrap();
+                count(&quantize(&frame.color), green)
+            })
+            .collect();
+        assert!(drawn.windows(2).all(|w| w[0] < w[1]));
+        let still = Arch {}.make(&matches).unwrap();
+        assert_eq!(drawn[3], count(&quantize(&still.color), green));
+    }
+
+    fn flights_of(args: &[&str]) -> Flights {
+        let matches = program_matches(&[&["test", "arch"], args].concat());
+        fly(&matches).unwrap()
+    }
+
+    #[test]
+    fn environment_options_reach_the_simulation() {
+        let matches = program_matches(&[
+            "test",
+            "arch",
+            "--gravity",
+            "0,-0.2,0",
+            "--wind",
+            "-1,0,0.5",
+            "--drag",
+            "0.1",
+            "--quadratic-drag",
+            "0.01",
+        ]);
+        let env = environment(&matches).unwrap();
+        assert_eq!(env.gravity, vector(0.0, -0.2, 0.0));
+        assert_eq!(env.wind, vector(-1.0, 0.0, 0.5));
+        assert_eq!(env.linear_drag, 0.1);
+        assert_eq!(env.quadratic_drag, 0.01);
+    }
+
+    #[test]
+    fn launch_sets_where_the_flight_starts() {
+        let flights = flights_of(&["--launch", "40,25,0"]);
+        let first = flights.trajectories[0].samples[0].position;
+        assert_eq!(first, point(40.0, 25.0, 0.0));
+    }
+
+    #[test]
+    fn wind_carries_the_bullet_only_through_drag() {
+        let range = |args: &[&str]| {
+            let flights = flights_of(&[&["--velocity", "0,5,0"], args].concat());
+            flights.trajectories[0].samples.last().unwrap().position.0
+        };
+        let still = range(&[]);
+        assert_eq!(range(&["--wind", "3,0,0"]), still);
+        let downwind = range(&["--wind", "3,0,0", "--drag", "0.05"]);
+        let upwind = range(&["--wind", "-3,0,0", "--drag", "0.05"]);
+        assert!(downwind > still && upwind < still);
+        let quadratic = range(&["--wind", "3,0,0", "--quadratic-drag", "0.05"]);
+        assert!(quadratic > still);
+    }
+
+    #[test]
+    fn drag_shortens_the_flight() {
+        let peak = |args: &[&str]| {
+            let flights = flights_of(&[&["--velocity", "0,5,0"], args].concat());
+            flights.trajectories[0]
+                .samples
+                .iter()
+                .map(|s| s.position.1)
+                .fold(f64::MIN, f64::max)
+        };
+        assert!(peak(&["--drag", "0.1"]) < peak(&[]));
+        assert!(peak(&["--quadratic-drag", "0.1"]) < peak(&[]));
+    }
+
+    #[test]
+    fn negative_drag_is_rejected() {
+        for flag in ["--drag=-0.1", "--quadratic-drag=-0.1"] {
+            let result = try_matches(&["test", "arch", flag]);
+            assert!(result.is_err());
+        }
+    }
+
+    #[test]
+    fn velocities_conflict_with_sweeps() {
+        let result = try_matches(&["test", "arch", "--velocity", "1,1,0", "--angles", "45"]);
+        assert!(result.is_err());
+    }
 }
@@ -0,0 +1,924 @@
+//!
+//! What the programs that cast rays at a single sphere share: the arguments that place the
+//! sphere and animate it, the environment rays see when they miss, and the wall of pixels
+//! the rays are cast through.
+//!
+
+use crate::commands::{
+    parse_arg, parse_triple, validate_color, validate_f64, validate_non_negative_f64,
+    validate_positive_f64, validate_positive_u32, validate_triple, width_and_height, Triple,
+};
+use crate::error::{Error, Result};
+use clap::{Arg, ArgMatches};
+use image::ImageBuffer;
+use ray_tracer::canvas::{HdrImage, Layers};
+use ray_tracer::color::{add_colors, color_from_hex, color_into_rgb32f, multiply_by_scalar, Color};
+use ray_tracer::environment::{load_equirectangular, sample_environment, Environment};
+use ray_tracer::intersection::{hit, Intersection};
+use ray_tracer::keyframe::{
+    ease, easing_from_name, keyframe, rotation_from_euler, track, Easing, Interpolate, Track,
+    TransformKey, EASINGS,
+};
+use ray_tracer::lens::{lens, thin_lens_ray, Aperture, Lens};
+use ray_tracer::projection::{camera_ray, cube_face_direction, FisheyeMapping, Projection};
+use ray_tracer::ray::{position, ray, ray_at_time, Ray};
+use ray_tracer::sampling::pixel_sample;
+use ray_tracer::sphere::{intersect, normal_at_time, sphere, uv_at_time, Sphere};
+use ray_tracer::stereo::{
+    compose_layers, eye_positions, stereo_layout_from_name, StereoLayout, STEREO_LAYOUTS,
+};
+use ray_tracer::tuple::{
+    add_tup, magnitude, mul_tup, normalize, point, sub_tup, vector, Tuple, EPSILON,
+};
+use rayon::prelude::*;
+
+pub const ORIGINL: &str = "origin";
+pub const WALL_ZL: &str = "wall-z";
+pub const WALL_SIZEL: &str = "wall-size";
+pub const SCALEL: &str = "scale";
+pub const ROTATEL: &str = "rotate";
+pub const TRANSLATEL: &str = "translate";
+pub const TO_SCALEL: &str = "to-scale";
+pub const TO_ROTATEL: &str = "to-rotate";
+pub const TO_TRANSLATEL: &str = "to-translate";
+pub const EASINGL: &str = "easing";
+pub const BACKGROUNDL: &str = "background";
+pub const ENVIRONMENTL: &str = "environment";
+pub const SAMPLESL: &str = "samples";
+pub const SHUTTER_OPENL: &str = "shutter-open";
+pub const SHUTTER_CLOSEL: &str = "shutter-close";
+pub const APERTUREL: &str = "aperture";
+pub const FOCUSL: &str = "focus";
+pub const BLADESL: &str = "blades";
+pub const PROJECTIONL: &str = "projection";
+pub const FIELD_OF_VIEWL: &str = "field-of-view";
+pub const FISHEYE_MAPPINGL: &str = "fisheye-mapping";
+pub const FACEL: &str = "face";
+pub const STEREOL: &str = "stereo";
+pub const INTEROCULARL: &str = "interocular";
+
+pub const PERSPECTIVE: &str = "perspective";
+pub const ORTHOGRAPHIC: &str = "orthographic";
+pub const FISHEYE: &str = "fisheye";
+pub const EQUIRECTANGULAR: &str = "equirectangular";
+pub const CUBE_FACE: &str = "cube-face";
+pub const PROJECTIONS: [&str; 5] = [
+    PERSPECTIVE,
+    ORTHOGRAPHIC,
+    FISHEYE,
+    EQUIRECTANGULAR,
+    CUBE_FACE,
+];
+pub const FISHEYE_MAPPINGS: [&str; 2] = ["equidistant", "equisolid"];
+///
+/// The faces of a cube map, in the order the environment loads them
+///
+pub const CUBE_FACES: [&str; 6] = ["+x", "-x", "+y", "-y", "+z", "-z"];
+
+// the library's default as clap needs it, which a test holds to DEFAULT_INTEROCULAR
+const DEFAULT_INTEROCULAR_ARG: &str = "0.065";
+
+///
+/// The field of view of a fisheye unless --field-of-view says otherwise, in degrees
+///
+const DEFAULT_FISHEYE_DEGREES: f64 = 180.0;
+
+///
+/// The furthest --rotate and --to-rotate may turn around each axis, a hundred full turns
+///
+const MAX_DEGREES: f64 = 36_000.0;
+
+///
+/// Rotations are keyed every quarter turn, so the most keys a track can need
+///
+const MAX_KEYS: usize = (2.0 * MAX_DEGREES / 90.0) as usize;
+
+///
+/// The most rays --samples may cast through each pixel
+///
+const MAX_SAMPLES: u32 = 4096;
+
+///
+/// The ID the sphere gets in the object and material ID buffers: it's the only object, with
+/// the only material
+///
+const SPHERE_ID: f64 = 1.0;
+
+///
+/// The `pixel_sample` dimension the time of each ray is drawn from
+///
+const TIME_DIMENSION: u32 = 0;
+
+///
+/// The `pixel_sample` dimensions where on the lens each ray leaves from is drawn from
+///
+const LENS_DIMENSIONS: (u32, u32) = (1, 2);
+
+///
+/// The arguments shared by every program that casts rays at a single sphere
+///
+pub fn sphere_args() -> Vec<Arg<'static>> {
+    vec![
+        Arg::new(ORIGINL)
+            .long(ORIGINL)
+            .help("Where the rays start, as x,y,z")
+            .required(false)
+            .validator(validate_triple)
+            .allow_hyphen_values(true)
+            .default_value("0,0,-5"),
+        Arg::new(WALL_ZL)
+            .long(WALL_ZL)
+            .help("The z coordinate of the wall the rays are cast at, which the view faces")
+            .required(false)
+            .validator(validate_f64)
+            .allow_hyphen_values(true)
+            .default_value("10"),
+        Arg::new(WALL_SIZEL)
+            .long(WALL_SIZEL)
+            .help(
+                "The size of the wall along the shorter side of the canvas, which sets how \
+                much a perspective or orthographic view covers",
+            )
+            .required(false)
+            .validator(validate_positive_f64)
+            .default_value("7"),
+        Arg::new(SCALEL)
+            .long(SCALEL)
+            .help("Scales the sphere by x,y,z")
+            .required(false)
+            .validator(validate_triple)
+            .allow_hyphen_values(true)
+            .default_value("1,1,1"),
+        Arg::new(ROTATEL)
+            .long(ROTATEL)
+            .help("Rotates the sphere by x,y,z degrees, around x first and z last")
+            .required(false)
+            .validator(validate_rotation)
+            .allow_hyphen_values(true)
+            .default_value("0,0,0"),
+        Arg::new(TRANSLATEL)
+            .long(TRANSLATEL)
+            .help("Moves the sphere by x,y,z after scaling and rotating it")
+            .required(false)
+            .validator(validate_triple)
+            .allow_hyphen_values(true)
+            .default_value("0,0,0"),
+        to_arg(TO_SCALEL, "The scale to animate to over --frames"),
+        to_arg(
+            TO_ROTATEL,
+            "The rotation to animate to over --frames, e.g. 0,360,0 to spin",
+        )
+        .validator(validate_rotation),
+        to_arg(TO_TRANSLATEL, "The translation to animate to over --frames"),
+        Arg::new(EASINGL)
+            .long(EASINGL)
+            .help("How the animation speeds up and slows down")
+            .required(false)
+            .possible_values(EASINGS)
+            .default_value("linear"),
+        Arg::new(BACKGROUNDL)
+            .long(BACKGROUNDL)
+            .help("The color rays that miss the sphere see")
+            .required(false)
+            .validator(validate_color)
+            .default_value("#000000"),
+        Arg::new(ENVIRONMENTL)
+            .long(ENVIRONMENTL)
+            .help(
+                "A latitude/longitude panorama, such as an .hdr or .exr, that rays which miss \
+                the sphere see instead of --background. Its center looks down -z",
+            )
+            .required(false)
+            .takes_value(true),
+        Arg::new(SAMPLESL)
+            .long(SAMPLESL)
+            .help("The rays cast through each pixel, spread over the time the shutter is open")
+            .required(false)
+            .validator(validate_samples)
+            .default_value("1"),
+        Arg::new(SHUTTER_OPENL)
+            .long(SHUTTER_OPENL)
+            .help(
+                "When the shutter opens, as a fraction of a frame. A still is one frame \
+                covering the whole animation",
+            )
+            .required(false)
+            .validator(validate_shutter)
+            .default_value("0"),
+        Arg::new(SHUTTER_CLOSEL)
+            .long(SHUTTER_CLOSEL)
+            .help(
+                "When the shutter closes, as a fraction of a frame. Whatever moves while it's \
+                open is blurred along its path",
+            )
+            .required(false)
+            .validator(validate_shutter)
+            .default_value("0"),
+        Arg::new(APERTUREL)
+            .long(APERTUREL)
+            .help(
+                "The radius of the lens the rays leave from, for perspective and orthographic \
+                views. Anything off the --focus distance is blurred; 0 is a pinhole that keeps \
+                everything sharp",
+            )
+            .required(false)
+            .validator(validate_non_negative_f64)
+            .default_value("0"),
+        Arg::new(FOCUSL)
+            .long(FOCUSL)
+            .help("How far ahead of the --origin the lens is focused")
+            .required(false)
+            .validator(validate_positive_f64)
+            .default_value("5"),
+        Arg::new(BLADESL)
+            .long(BLADESL)
+            .help("Makes the aperture a polygon with this many sides instead of round")
+            .required(false)
+            .takes_value(true)
+            .validator(validate_blades),
+        Arg::new(PROJECTIONL)
+            .long(PROJECTIONL)
+            .help(
+                "How pixels become rays. An equirectangular panorama or a cube face sees all \
+                around the --origin, laid out so that --environment can load the render",
+            )
+            .required(false)
+            .possible_values(PROJECTIONS)
+            .default_value(PERSPECTIVE),
+        Arg::new(FIELD_OF_VIEWL)
+            .long(FIELD_OF_VIEWL)
+            .help(
+                "The angle a perspective view covers across the longer side of the canvas, or \
+                a fisheye across its image circle, in degrees. Perspective defaults to the \
+                wall's, fisheye to 180",
+            )
+            .required(false)
+            .takes_value(true)
+            .validator(validate_positive_f64),
+        Arg::new(FISHEYE_MAPPINGL)
+            .long(FISHEYE_MAPPINGL)
+            .help("How a fisheye spreads angles across its image circle")
+            .required(false)
+            .possible_values(FISHEYE_MAPPINGS)
+            .default_value("equidistant"),
+        Arg::new(FACEL)
+            .long(FACEL)
+            .help("The face of the cube a cube-face view renders")
+            .required(false)
+            .possible_values(CUBE_FACES)
+            .allow_hyphen_values(true)
+            .default_value("+z"),
+        Arg::new(STEREOL)
+            .long(STEREOL)
+            .help(
+                "Renders a left and a right eye and packs them into one image, the canvas size \
+                each. A perspective view's eyes converge on the wall",
+            )
+            .required(false)
+            .takes_value(true)
+            .possible_values(STEREO_LAYOUTS),
+        Arg::new(INTEROCULARL)
+            .long(INTEROCULARL)
+            .help("How far apart the --stereo eyes are, either side of the --origin along x")
+            .required(false)
+            .validator(validate_positive_f64)
+            .default_value(DEFAULT_INTEROCULAR_ARG),
+    ]
+}
+
+///
+/// What the rays that miss the sphere see: the --environment map if there is one,
+/// otherwise the --background color
+///
+pub fn environment_from_args(matches: &ArgMatches) -> Result<Environment> {
+    match matches.value_of(ENVIRONMENTL) {
+        Some(path) => load_equirectangular(path)
+            .map_err(|e| Error::invalid_argument(ENVIRONMENTL, path, &e.to_string())),
+        None => Ok(Environment::Solid(
+            color_from_hex(matches.value_of(BACKGROUNDL).unwrap()).unwrap(),
+        )),
+    }
+}
+
+///
+/// An optional x,y,z triple that a value is animated towards
+///
+pub fn to_arg(name: &'static str, help: &'static str) -> Arg<'static> {
+    Arg::new(name)
+        .long(name)
+        .help(help)
+        .required(false)
+        .takes_value(true)
+        .validator(validate_triple)
+        .allow_hyphen_values(true)
+}
+
+///
+/// clap validator for x,y,z rotations in degrees of at most `MAX_DEGREES` around each axis
+///
+fn validate_rotation(v: &str) -> std::result::Result<(), String> {
+    validate_triple(v)?;
+    let within = v
+        .split(',')
+        .filter_map(|d| d.trim().parse::<f64>().ok())
+        .all(|d| d.abs() <= MAX_DEGREES);
+    if !within {
+        return Err(format!("rotations must be within {} degrees", MAX_DEGREES));
+    }
+    Ok(())
+}
+
+///
+/// clap validator for --samples: greater than zero and at most `MAX_SAMPLES`
+///
+fn validate_samples(v: &str) -> std::result::Result<(), String> {
+    validate_positive_u32(v)?;
+    match v.parse::<u32>() {
+        Ok(n) if n > MAX_SAMPLES => Err(format!("must be at most {}", MAX_SAMPLES)),
+        _ => Ok(()),
+    }
+}
+
+///
+/// clap validator for --blades: a polygon needs at least three sides
+///
+fn validate_blades(v: &str) -> std::result::Result<(), String> {
+    match v.parse::<u32>() {
+        Ok(n) if n < 3 => Err("must be at least 3".to_string()),
+        Ok(_) => Ok(()),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+///
+/// clap validator for --shutter-open and --shutter-close: a fraction of a frame, 0 to 1
+///
+fn validate_shutter(v: &str) -> std::result::Result<(), String> {
+    validate_non_negative_f64(v)?;
+    match v.parse::<f64>() {
+        Ok(f) if f > 1.0 => Err("must be at most 1".to_string()),
+        _ => Ok(()),
+    }
+}
+
+///
+/// Builds the sphere described by `sphere_args` while the shutter is open, from `exposure.0`
+/// to `exposure.1` in its animation, which runs from 0 at the start values to 1 at the --to
+/// values. A sphere that moves in that time gets the animation as its motion.
+/// Transforms that flatten it are rejected
+///
+pub fn sphere_from_args(matches: &ArgMatches, exposure: (f64, f64)) -> Result<Sphere> {
+    let (open, close) = exposure;
+    let motion = transform_track(matches)?;
+    for time in [open, close] {
+        let scale = motion.sample(time).unwrap().scale;
+        if scale.0 == 0.0 || scale.1 == 0.0 || scale.2 == 0.0 {
+            return Err(Error::invalid_argument(
+                SCALEL,
+                matches.value_of(SCALEL).unwrap_or_default(),
+                "scaling by zero flattens the sphere away",
+            ));
+        }
+    }
+
+    let mut s = sphere();
+    s.transform = motion.sample(open).unwrap().to_matrix();
+    if close > open {
+        s.motion = Some(motion);
+    }
+    Ok(s)
+}
+
+///
+/// The eased animation times the shutter opens and closes at in a frame, given as fractions
+/// of a frame. Frames start at 0 on the first frame and 1 on the last; a still counts as a
+/// single frame covering the whole animation, and the animation holds its last values after
+/// the last frame
+///
+pub fn exposure(
+    matches: &ArgMatches,
+    shutter: (f64, f64),
+    frame: u32,
+    frames: u32,
+) -> Result<(f64, f64)> {
+    let name = matches.value_of(EASINGL).unwrap_or_default();
+    let easing = easing_from_name(name).ok_or_else(|| {
+        Error::invalid_argument(
+            EASINGL,
+            name,
+            &format!("expected one of {}", EASINGS.join(", ")),
+        )
+    })?;
+    let span = if frames < 2 {
+        1.0
+    } else {
+        1.0 / (frames - 1) as f64
+    };
+    let start = frame as f64 * span;
+    Ok((
+        ease(easing, start + shutter.0 * span),
+        ease(easing, start + shutter.1 * span),
+    ))
+}
+
+///
+/// Parses a triple that is animated from `from` to `to`, where `to` defaults to `from`
+///
+pub fn parse_animated_triple(
+    matches: &ArgMatches,
+    from: &str,
+    to: &str,
+) -> Result<(Triple, Triple)> {
+    let start = parse_triple(matches, from)?;
+    let end = if matches.is_present(to) {
+        parse_triple(matches, to)?
+    } else {
+        start
+    };
+    Ok((start, end))
+}
+
+// keyed every quarter turn at most, so that slerping between the keys follows the whole
+// rotation instead of the shortest way round, e.g. for a full turntable spin
+fn transform_track(matches: &ArgMatches) -> Result<Track<TransformKey>> {
+    let (s0, s1) = parse_animated_triple(matches, SCALEL, TO_SCALEL)?;
+    let (r0, r1) = parse_animated_triple(matches, ROTATEL, TO_ROTATEL)?;
+    let (t0, t1) = parse_animated_triple(matches, TRANSLATEL, TO_TRANSLATEL)?;
+    let turn = [r1.0 - r0.0, r1.1 - r0.1, r1.2 - r0.2]
+        .iter()
+        .fold(0.0_f64, |acc, d| acc.max(d.abs()));
+    let keys = ((turn / 90.0).ceil() as usize).clamp(1, MAX_KEYS);
+
+    let lerp = |a: Triple, b: Triple, t: f64| {
+        (
+            f64::interpolate(a.0, b.0, t),
+            f64::interpolate(a.1, b.1, t),
+            f64::interpolate(a.2, b.2, t),
+        )
+    };
+    Ok(track(
+        (0..=keys)
+            .map(|k| {
+                let t = k as f64 / keys as f64;
+                let (s, r, tr) = (lerp(s0, s1, t), lerp(r0, r1, t), lerp(t0, t1, t));
+                let key = TransformKey {
+                    scale: vector(s.0, s.1, s.2),
+                    rotation: rotation_from_euler(
+                        r.0.to_radians(),
+                        r.1.to_radians(),
+                        r.2.to_radians(),
+                    ),
+                    translation: vector(tr.0, tr.1, tr.2),
+                };
+                keyframe(t, key, Easing::Linear)
+            })
+            .collect(),
+    ))
+}
+
+///
+/// How the pixels of the canvas are turned into rays from the origin
+///
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum View {
+    /// A camera facing the wall with +y up. A perspective view is aimed through the wall,
+    /// which stays centered on the z axis; the others are centered on the origin.
+    /// Equirectangular panoramas are the exception: like the environment maps, their center
+    /// looks down -z whichever way the wall is
+    Camera(Projection),
+    /// One face of a cube around the origin, in the order of `CUBE_FACES`
+    CubeFace(usize),
+}
+
+///
+/// Where the rays start, the wall of pixels they're cast through and what they see when they
+/// miss: everything about a cast that stays the same from frame to frame, so that it's parsed
+/// and loaded once per run
+///
+pub struct Wall {
+    pub width: u32,
+    pub height: u32,
+    pub origin: Tuple,
+    pub z: f64,
+    pub view: View,
+    pub environment: Environment,
+    /// The rays cast through each pixel
+    pub samples: u32,
+    /// When the shutter opens and closes, as fractions of a frame
+    pub shutter: (f64, f64),
+    pub lens: Lens,
+    /// How the two eyes are packed and how far apart they are, for a stereo pair
+    pub stereo: Option<(StereoLayout, f64)>,
+}
+
+///
+/// Reads the wall described by `sphere_args` and the global canvas size, loading the
+/// --environment map if there is one
+///
+pub fn wall_from_args(matches: &ArgMatches) -> Result<Wall> {
+    let (width, height) = width_and_height(matches)?;
+    let (ox, oy, oz) = parse_triple(matches, ORIGINL)?;
+    let shutter: (f64, f64) = (
+        parse_arg(matches, SHUTTER_OPENL)?,
+        parse_arg(matches, SHUTTER_CLOSEL)?,
+    );
+    if shutter.1 < shutter.0 {
+        return Err(Error::invalid_argument(
+            SHUTTER_CLOSEL,
+            matches.value_of(SHUTTER_CLOSEL).unwrap_or_default(),
+            "the shutter must not close before it opens",
+        ));
+    }
+    let wall_z: f64 = parse_arg(matches, WALL_ZL)?;
+    Ok(Wall {
+        width,
+        height,
+        origin: point(ox, oy, oz),
+        z: wall_z,
+        view: view_from_args(matches, (width, height), (wall_z - oz).abs())?,
+        environment: environment_from_args(matches)?,
+        samples: parse_arg(matches, SAMPLESL)?,
+        shutter,
+        lens: lens_from_args(matches)?,
+        stereo: stereo_from_args(matches)?,
+    })
+}
+
+///
+/// The eyes described by --stereo and --interocular, if there are two
+///
+fn stereo_from_args(matches: &ArgMatches) -> Result<Option<(StereoLayout, f64)>> {
+    let name = match matches.value_of(STEREOL) {
+        Some(name) => name,
+        None => return Ok(None),
+    };
+    let layout = stereo_layout_from_name(name).ok_or_else(|| {
+        Error::invalid_argument(
+            STEREOL,
+            name,
+            &format!("expected one of {}", STEREO_LAYOUTS.join(", ")),
+        )
+    })?;
+    Ok(Some((layout, parse_arg(matches, INTEROCULARL)?)))
+}
+
+///
+/// The view described by --projection and its options. Perspective and orthographic views
+/// take how much they cover from a wall of --wall-size `distance` away
+///
+fn view_from_args(
+    matches: &ArgMatches,
+    (width, height): (u32, u32),
+    distance: f64,
+) -> Result<View> {
+    let size: f64 = parse_arg(matches, WALL_SIZEL)?;
+    // the wall's size is along the shorter side, the projections' along the longer one
+    let longer = width.max(height) as f64 / width.min(height) as f64;
+    // perspective views can't reach `max`, fisheyes can
+    let degrees = |default: f64, max: f64, reaches: bool| -> Result<f64> {
+        let degrees = match matches.value_of(FIELD_OF_VIEWL) {
+            Some(_) => parse_arg(matches, FIELD_OF_VIEWL)?,
+            None => default,
+        };
+        if degrees > max || (degrees == max && !reaches) {
+            let bound = if reaches { "at most" } else { "less than" };
+            return Err(Error::invalid_argument(
+                FIELD_OF_VIEWL,
+                &degrees.to_string(),
+                &format!("must be {} {} degrees", bound, max),
+            ));
+        }
+        Ok(degrees)
+    };
+    let name = matches.value_of(PROJECTIONL).unwrap_or(PERSPECTIVE);
+    let projection = match name {
+        PERSPECTIVE => {
+            let wall = (size / 2.0 * longer / distance).atan().to_degrees() * 2.0;
+            Projection::Perspective {
+                field_of_view: degrees(wall, 180.0, false)?.to_radians(),
+            }
+        }
+        ORTHOGRAPHIC => Projection::Orthographic {
+            width: size * longer,
+        },
+        FISHEYE => Projection::Fisheye {
+            field_of_view: degrees(DEFAULT_FISHEYE_DEGREES, 360.0, true)?.to_radians(),
+            mapping: match matches.value_of(FISHEYE_MAPPINGL) {
+                Some("equisolid") => FisheyeMapping::Equisolid,
+                _ => FisheyeMapping::Equidistant,
+            },
+        },
+        EQUIRECTANGULAR => Projection::Equirectangular,
+        CUBE_FACE => {
+            let face = matches.value_of(FACEL).unwrap_or_default();
+            let index = CUBE_FACES.iter().position(|f| *f == face).ok_or_else(|| {
+                Error::invalid_argument(
+                    FACEL,
+                    face,
+                    &format!("expected one of {}", CUBE_FACES.join(", ")),
+                )
+            })?;
+            return Ok(View::CubeFace(index));
+        }
+        _ => {
+            return Err(Error::invalid_argument(
+                PROJECTIONL,
+                name,
+                &format!("expected one of {}", PROJECTIONS.join(", ")),
+            ))
+        }
+    };
+    Ok(View::Camera(projection))
+}
+
+///
+/// The way the view faces, along the z axis toward the wall
+///
+fn forward(wall: &Wall) -> Tuple {
+    vector(0.0, 0.0, if wall.z < wall.origin.2 { -1.0 } else { 1.0 })
+}
+
+///
+/// The ray from `origin` through the middle of a pixel, or None where the view doesn't
+/// cover the canvas, such as outside a fisheye's image circle
+///
+fn pixel_ray(wall: &Wall, origin: Tuple, x: u32, y: u32) -> Option<Ray> {
+    let (w, h) = (wall.width as f64, wall.height as f64);
+    let (u, v) = ((x as f64 + 0.5) / w, (y as f64 + 0.5) / h);
+    let projection = match wall.view {
+        View::CubeFace(face) => return Some(ray(origin, cube_face_direction(face, u, v))),
+        View::Camera(projection) => projection,
+    };
+    let r = camera_ray(&projection, w / h, u, v)?;
+    // cameras look down -z, which turns to face the wall; x and y stay put
+    let facing = match projection {
+        Projection::Equirectangular => 1.0,
+        _ => -forward(wall).2,
+    };
+    let to_world = |t: Tuple| (t.0, t.1, t.2 * facing, t.3);
+    match projection {
+        // aimed through the wall, which stays centered on the z axis wherever the origin is
+        Projection::Perspective { .. } => {
+            let distance = (wall.z - origin.2).abs();
+            let on_wall = to_world(mul_tup(r.direction, distance / -r.direction.2));
+            let center = sub_tup(point(0.0, 0.0, wall.z), origin);
+            let direction = add_tup(on_wall, vector(center.0, center.1, 0.0));
+            Some(ray(origin, normalize(direction)))
+        }
+        _ => {
+            let offset = sub_tup(r.origin, point(0.0, 0.0, 0.0));
+            Some(ray(
+                add_tup(origin, to_world(offset)),
+                to_world(r.direction),
+            ))
+        }
+    }
+}
+
+///
+/// Whether a thin lens can be put in front of the view: only when every ray faces forward
+///
+fn takes_a_lens(view: View) -> bool {
+    matches!(
+        view,
+        View::Camera(Projection::Perspective { .. } | Projection::Orthographic { .. })
+    )
+}
+
+///
+/// The lens described by --aperture, --focus and --blades
+///
+fn lens_from_args(matches: &ArgMatches) -> Result<Lens> {
+    let mut lens = lens(parse_arg(matches, APERTUREL)?, parse_arg(matches, FOCUSL)?);
+    if matches.is_present(BLADESL) {
+        lens.aperture = Aperture::Polygon(parse_arg(matches, BLADESL)?);
+    }
+    Ok(lens)
+}
+
+///
+/// What a ray that hits the sphere adds to each auxiliary buffer: the albedo, normal, depth,
+/// shadow, object ID, material ID and UVs, in that order
+///
+type Surface = [Color; 7];
+
+///
+/// What one ray sees: its color, and the surface if it hits the sphere
+///
+type Sample = (Color, Option<Surface>);
+
+///
+/// Casts `wall.samples` rays from the origin through every pixel of the wall, painting the
+/// pixel with the average of `shade` wherever the rays hit the sphere and of the environment
+/// wherever they miss. `shade` is given the ray, the hit and the surface normal there.
+/// Each ray is cast at a time between the eased animation times in `exposure`, so a sphere
+/// that moves in between is blurred along its path, and through a point on the wall's lens,
+/// so a sphere off its focus is blurred too.
+/// The rays leave the origin as the wall's view projects them. Alongside the color it fills
+/// in the average of every auxiliary buffer, counting 0 for every ray that misses, with a
+/// shadow mask only for programs with a `light`. Pixels the view doesn't cover are left black.
+/// A stereo wall is cast from both eyes, and the two renders are packed into one
+///
+pub fn cast_at_wall<F>(
+    wall: &Wall,
+    s: &Sphere,
+    exposure: (f64, f64),
+    light: Option<Tuple>,
+    shade: F,
+) -> Result<Layers>
+where
+    F: Fn(&Ray, &Intersection, Tuple) -> Color + Sync,
+{
+    let cast = |origin| cast_from(wall, origin, s, exposure, light, &shade);
+    match wall.stereo {
+        None => Ok(cast(wall.origin)),
+        Some((layout, interocular)) => {
+            let (left, right) = eye_positions(wall.origin, vector(1.0, 0.0, 0.0), interocular);
+            Ok(compose_layers(&cast(left), &cast(right), layout)?)
+        }
+    }
+}
+
+///
+/// Whether the sphere stands between a point on its surface and the light
+///
+fn shadowed(s: &Sphere, point: Tuple, light: Tuple, time: f64) -> bool {
+    let to_light = sub_tup(light, point);
+    let distance = magnitude(to_light);
+    let r = ray_at_time(point, normalize(to_light), time);
+    // the point itself is a crossing at about 0, which doesn't block anything
+    intersect(s, &r)
+        .iter()
+        .any(|i| i.t > EPSILON && i.t < distance)
+}
+
+///
+/// Casts the wall's rays from `origin`, which is the wall's own or one of its eyes
+///
+fn cast_from<F>(
+    wall: &Wall,
+    origin: Tuple,
+    s: &Sphere,
+    exposure: (f64, f64),
+    light: Option<Tuple>,
+    shade: &F,
+) -> Layers
+where
+    F: Fn(&Ray, &Intersection, Tuple) -> Color + Sync,
+{
+    let (w, h) = (wall.width, wall.height);
+    let (open, close) = exposure;
+    let lens = Some(&wall.lens).filter(|l| l.radius > 0.0 && takes_a_lens(wall.view));
+    let (forward, right, up) = (forward(wall), vector(1.0, 0.0, 0.0), vector(0.0, 1.0, 0.0));
+
+    let rows: Vec<Vec<Vec<Sample>>> = (0..h)
+        .into_par_iter()
+        .map(|y| {
+            (0..w)
+                .map(|x| {
+                    let through = match pixel_ray(wall, origin, x, y) {
+                        Some(r) => r,
+                        None => return vec![((0.0, 0.0, 0.0), None); wall.samples as usize],
+                    };
+                    (0..wall.samples)
+                        .map(|k| {
+                            let time =
+                                open + (close - open) * pixel_sample(x, y, k, TIME_DIMENSION);
+                            let pinhole = ray_at_time(through.origin, through.direction, time);
+                            let r = match lens {
+                                Some(lens) => {
+                                    let (du, dv) = LENS_DIMENSIONS;
+                                    let (r1, r2) =
+                                        (pixel_sample(x, y, k, du), pixel_sample(x, y, k, dv));
+                                    thin_lens_ray(&pinhole, forward, right, up, lens, r1, r2)
+                                }
+                                None => pinhole,
+                            };
+                            let xs = intersect(s, &r);
+                            match hit(&xs) {
+                                Some(i) => {
+                                    let p = position(&r, i.t);
+                                    let normal = normal_at_time(i.object, p, r.time);
+                                    let blocked = light.filter(|l| shadowed(s, p, *l, r.time));
+                                    let shadow = if blocked.is_some() { 1.0 } else { 0.0 };
+                                    let (u, v) = uv_at_time(i.object, p, r.time);
+                                    let surface = [
+                                        i.object.material.color,
+                                        (normal.0, normal.1, normal.2),
+                                        (i.t, i.t, i.t),
+                                        (shadow, shadow, shadow),
+                                        (SPHERE_ID, SPHERE_ID, SPHERE_ID),
+                                        (SPHERE_ID, SPHERE_ID, SPHERE_ID),
+                                        (u, v, 0.0),
+                                    ];
+                                    (shade(&r, i, normal), Some(surface))
+                                }
+                                None => (sample_environment(&wall.environment, r.direction), None),
+                            }
+                        })
+                        .collect()
+                })
+                .collect()
+        })
+        .collect();
+
+    let mut color: HdrImage = ImageBuffer::new(w, h);
+    let mut aux: [HdrImage; 7] = std::array::from_fn(|_| ImageBuffer::new(w, h));
+    let average =
+        |sum: Color| color_into_rgb32f(multiply_by_scalar(sum, 1.0 / wall.samples as f64));
+    for (y, row) in rows.into_iter().enumerate() {
+        for (x, samples) in row.into_iter().enumerate() {
+            let (x, y) = (x as u32, y as u32);
+            let black = (0.0, 0.0, 0.0);
+            let (mut c_sum, mut aux_sums) = (black, [black; 7]);
+            for (c, surface) in samples {
+                c_sum = add_colors(c_sum, c);
+                for (sum, value) in aux_sums.iter_mut().zip(surface.iter().flatten()) {
+                    *sum = add_colors(*sum, *value);
+                }
+            }
+            color.put_pixel(x, y, average(c_sum));
+            for (image, sum) in aux.iter_mut().zip(aux_sums) {
+                image.put_pixel(x, y, average(sum));
+            }
+        }
+    }
+    let [albedo, normal, depth, shadow, object_id, material_id, uv] = aux;
+    Layers {
+        color,
+        albedo: Some(albedo),
+        normal: Some(normal),
+        depth: Some(depth),
+        shadow: light.map(|_| shadow),
+        object_id: Some(object_id),
+        material_id: Some(material_id),
+        uv: Some(uv),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{program_matches, try_matches};
+    use ray_tracer::sphere::normal_at;
+    use ray_tracer::stereo::DEFAULT_INTEROCULAR;
+
+    #[test]
+    fn full_turns_are_keyed_so_they_keep_turning() {
+        let matches = program_matches(&["test", "silhouette", "--to-rotate", "0,360,0"]);
+        let track = transform_track(&matches).unwrap();
+        let half = track.sample(0.5).unwrap();
+        assert!((half.rotation.angle() - std::f64::consts::PI).abs() < 1e-9);
+    }
+
+    #[test]
+    fn shade_gets_the_normal_at_the_hit() {
+        let matches = program_matches(&["test", "-w", "9", "-h", "9", "silhouette"]);
+        let wall = wall_from_args(&matches).unwrap();
+        let s = sphere();
+        let layers = cast_at_wall(&wall, &s, (0.0, 0.0), None, |r, hit, normal| {
+            assert_eq!(normal, normal_at(hit.object, position(r, hit.t)));
+            (normal.0, normal.1, normal.2)
+        })
+        .unwrap();
+        // the middle of the sphere faces straight back at the origin
+        assert_eq!(layers.color.get_pixel(4, 4).0, [0.0, 0.0, -1.0]);
+    }
+
+    #[test]
+    fn the_shutter_is_open_for_part_of_a_frame() {
+        let matches = program_matches(&["test", "silhouette"]);
+        let close =
+            |a: (f64, f64), b: (f64, f64)| (a.0 - b.0).abs() < 1e-9 && (a.1 - b.1).abs() < 1e-9;
+        // frames are half the animation apart
+        let times = exposure(&matches, (0.0, 0.5), 1, 3).unwrap();
+        assert!(close(times, (0.5, 0.75)), "{:?}", times);
+        assert_eq!(exposure(&matches, (0.0, 0.0), 2, 3).unwrap(), (1.0, 1.0));
+        // a still spans the whole animation, and nothing moves past the end
+        assert_eq!(exposure(&matches, (0.25, 1.0), 0, 1).unwrap(), (0.25, 1.0));
+        assert_eq!(exposure(&matches, (0.0, 1.0), 2, 3).unwrap(), (1.0, 1.0));
+    }
+
+    #[test]
+    fn the_interocular_default_matches_the_library() {
+        let default: f64 = DEFAULT_INTEROCULAR_ARG.parse().unwrap();
+        assert_eq!(default, DEFAULT_INTEROCULAR);
+    }
+
+    #[test]
+    fn walls_need_a_size() {
+        for size in ["0", "-7"] {
+            let result = try_matches(&["test", "silhouette", &format!("--wall-size={}", size)]);
+            assert!(result.is_err(), "{}", size);
+        }
+    }
+
+    #[test]
+    fn rotations_are_bounded() {
+        assert!(validate_rotation("0,-36000,0").is_ok());
+        assert!(validate_rotation("0,1e15,0").is_err());
+        assert!(validate_rotation("0,1").is_err());
+        let result = try_matches(&["test", "silhouette", "--to-rotate", "0,1e15,0"]);
+        assert!(result.is_err());
+    }
+}
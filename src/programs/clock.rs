@@ -0,0 +1,318 @@
+use crate::commands::{
+    parse_arg, validate_color, validate_positive_f64, validate_positive_u32, width_and_height,
+};
+use crate::error::{Error, Result};
+use crate::programs::MakeImage;
+use clap::{Arg, ArgMatches};
+use image::{ImageBuffer, Rgb};
+use ray_tracer::canvas::{
+    hdr_from_rgb8, layers, neighbors, new_image_buffer, point_is_in_rectangle, Layers,
+};
+use ray_tracer::chart::{rect, viewport};
+use ray_tracer::color::{color_from_hex, color_into_rgb};
+use ray_tracer::matrix::Matrix4x4;
+use ray_tracer::transform::{chain, rotation_y, scaling, transform, translation};
+use ray_tracer::tuple::{point, Tuple};
+use std::f64::consts::PI;
+
+pub struct Clock {}
+
+pub const CLOCK: &str = "clock";
+
+pub const MARKSL: &str = "marks";
+pub const MARKS: char = 'n';
+pub const RADIUSL: &str = "radius";
+pub const RADIUS: char = 'r';
+pub const SIZEL: &str = "size";
+pub const SIZE: char = 's';
+pub const COLORL: &str = "color";
+pub const COLOR: char = 'c';
+pub const TIMEL: &str = "time";
+pub const TIME: char = 't';
+///
+/// The --time that reads the clock the program runs on
+///
+pub const NOW: &str = "now";
+
+///
+/// The largest --radius, as a fraction of half the smaller canvas side
+///
+const MAX_RADIUS: f64 = 10.0;
+///
+/// The largest --size, in pixels
+///
+const MAX_SIZE: u32 = 100;
+
+const HOUR_HAND: f64 = 0.5;
+const MINUTE_HAND: f64 = 0.8;
+
+impl MakeImage for Clock {
+    fn name(&self) -> &'static str {
+        CLOCK
+    }
+
+    fn about(&self) -> &'static str {
+        "Draws the hour marks of a clock face by rotating a point around the Y axis"
+    }
+
+    fn args(&self) -> Vec<Arg<'static>> {
+        vec![
+            Arg::new(MARKSL)
+                .long(MARKSL)
+                .short(MARKS)
+                .help("The number of marks around the face")
+                .required(false)
+                .validator(validate_positive_u32)
+                .default_value("12"),
+            Arg::new(RADIUSL)
+                .long(RADIUSL)
+                .short(RADIUS)
+                .help("The radius of the face, as a fraction of half the smaller canvas side")
+                .required(false)
+                .validator(validate_radius)
+                .default_value("0.75"),
+            Arg::new(SIZEL)
+                .long(SIZEL)
+                .short(SIZE)
+                .help(
+                    "How far each mark's square brush reaches from its center, in pixels; \
+                    a size of s paints a square 2s+1 pixels wide",
+                )
+                .required(false)
+                .validator(validate_size)
+                .default_value("4"),
+            Arg::new(COLORL)
+                .long(COLORL)
+                .short(COLOR)
+                .help("The color of the marks and hands")
+                .required(false)
+                .validator(validate_color)
+                .default_value("#ffffff"),
+            Arg::new(TIMEL)
+                .long(TIMEL)
+                .short(TIME)
+                .help("Draws hour and minute hands showing a time like 10:08, or now for the local time")
+                .required(false)
+                .takes_value(true),
+        ]
+    }
+
+    fn make(&self, matches: &ArgMatches) -> Result<Layers> {
+        let (w, h) = width_and_height(matches)?;
+        let marks: u32 = parse_arg(matches, MARKSL)?;
+        let radius_fraction: f64 = parse_arg(matches, RADIUSL)?;
+        let size: u32 = parse_arg(matches, SIZEL)?;
+        let color = color_into_rgb(color_from_hex(matches.value_of(COLORL).unwrap()).unwrap());
+        let time = match matches.value_of(TIMEL) {
+            Some(t) => Some(parse_time(t)?),
+            None => None,
+        };
+
+        let radius = radius_fraction * w.min(h) as f64 / 2.0;
+        let center = translation(w as f64 / 2.0, 0.0, h as f64 / 2.0);
+        let mut canvas = new_image_buffer(w, h);
+
+        // twelve o'clock lies along +z, and rotating about y turns it clockwise when +z is up
+        let twelve = point(0.0, 0.0, 1.0);
+        for i in 0..marks {
+            let angle = i as f64 * 2.0 * PI / marks as f64;
+            let m = chain(&[rotation_y(angle), scaling(radius, radius, radius), center]);
+            stamp(&mut canvas, &m, twelve, size, color);
+        }
+
+        if let Some((hours, minutes)) = time {
+            let minute_angle = minutes as f64 / 60.0 * 2.0 * PI;
+            let hour_angle = (hours % 12) as f64 / 12.0 * 2.0 * PI + minute_angle / 12.0;
+            let hand_size = (size / 2).max(1);
+            for (angle, length) in [(hour_angle, HOUR_HAND), (minute_angle, MINUTE_HAND)] {
+                let m = chain(&[rotation_y(angle), scaling(radius, radius, radius), center]);
+                let steps = (radius * length).ceil() as u32;
+                for step in 0..=steps {
+                    let along = point(0.0, 0.0, length * step as f64 / steps.max(1) as f64);
+                    stamp(&mut canvas, &m, along, hand_size, color);
+                }
+            }
+        }
+
+        Ok(layers(hdr_from_rgb8(canvas)))
+    }
+}
+
+///
+/// Transforms a point from clock space onto the canvas and paints a square brush there
+///
+fn stamp(
+    canvas: &mut ImageBuffer<Rgb<u8>, Vec<u8>>,
+    m: &Matrix4x4,
+    p: Tuple,
+    size: u32,
+    color: Rgb<u8>,
+) {
+    let (w, h) = (canvas.width() as f64, canvas.height() as f64);
+    // clock space is the canvas in pixels, with z pointing up
+    let view = viewport((0.0, w), (0.0, h), rect(0.0, 0.0, w, h));
+    let p = transform(m, p);
+    let (x, y) = view.to_canvas(p.0, p.2);
+    // a brush further off the canvas than it reaches paints nothing, and its center might not
+    // fit in an i32
+    let reach = size as f64;
+    if x < -reach || y < -reach || x > w + reach || y > h + reach {
+        return;
+    }
+    let (x, y) = (x.round() as i32, y.round() as i32);
+    let (w, h) = (w as i32, h as i32);
+    if point_is_in_rectangle(x, y, w, h) {
+        canvas.put_pixel(x as u32, y as u32, color);
+    }
+    for pt in neighbors(x, y, w, h, size as i32) {
+        canvas.put_pixel(pt.0 as u32, pt.1 as u32, color);
+    }
+}
+
+///
+/// clap validator for --radius: greater than zero and at most `MAX_RADIUS`
+///
+fn validate_radius(v: &str) -> std::result::Result<(), String> {
+    validate_positive_f64(v)?;
+    match v.parse::<f64>() {
+        Ok(r) if r > MAX_RADIUS => Err(format!("must be at most {}", MAX_RADIUS)),
+        _ => Ok(()),
+    }
+}
+
+///
+/// clap validator for --size: greater than zero and at most `MAX_SIZE`
+///
+fn validate_size(v: &str) -> std::result::Result<(), String> {
+    validate_positive_u32(v)?;
+    match v.parse::<u32>() {
+        Ok(s) if s > MAX_SIZE => Err(format!("must be at most {}", MAX_SIZE)),
+        _ => Ok(()),
+    }
+}
+
+///
+/// Parses a time like "10:08", or `NOW`, into (hours, minutes)
+///
+fn parse_time(t: &str) -> Result<(u32, u32)> {
+    if t == NOW {
+        return Ok(local_time());
+    }
+    let invalid = || Error::invalid_argument(TIMEL, t, "expected a time like 10:08 or now");
+    let (hours, minutes) = t.split_once(':').ok_or_else(invalid)?;
+    let hours: u32 = hours.parse().map_err(|_| invalid())?;
+    let minutes: u32 = minutes.parse().map_err(|_| invalid())?;
+    if hours >= 24 || minutes >= 60 {
+        return Err(invalid());
+    }
+    Ok((hours, minutes))
+}
+
+///
+/// The (hours, minutes) on the local clock
+///
+#[cfg(unix)]
+fn local_time() -> (u32, u32) {
+    // SAFETY: localtime_r only writes to the tm it's handed, and a zeroed tm is a valid one
+    let tm = unsafe {
+        let now = libc::time(std::ptr::null_mut());
+        let mut tm: libc::tm = std::mem::zeroed();
+        libc::localtime_r(&now, &mut tm);
+        tm
+    };
+    (tm.tm_hour as u32, tm.tm_min as u32)
+}
+
+///
+/// The (hours, minutes) in UTC, where the local time zone isn't available
+///
+#[cfg(not(unix))]
+fn local_time() -> (u32, u32) {
+    let seconds = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let minutes = seconds / 60 % (24 * 60);
+    ((minutes / 60) as u32, (minutes % 60) as u32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::program_matches;
+    use ray_tracer::canvas::quantize;
+
+    fn render(args: &[&str]) -> ImageBuffer<Rgb<u8>, Vec<u8>> {
+        let matches = program_matches(args);
+        quantize(&Clock {}.make(&matches).unwrap().color)
+    }
+
+    fn lit(canvas: &ImageBuffer<Rgb<u8>, Vec<u8>>, x: u32, y: u32) -> bool {
+        canvas.get_pixel(x, y).0 != [0, 0, 0]
+    }
+
+    #[test]
+    fn marks_land_on_the_hours() {
+        let canvas = render(&[
+            "test", "-w", "100", "-h", "100", "clock", "-r", "0.8", "-s", "1",
+        ]);
+        // twelve, three, six and nine o'clock with a radius of 40 around (50, 50)
+        assert!(lit(&canvas, 50, 10));
+        assert!(lit(&canvas, 90, 50));
+        assert!(lit(&canvas, 50, 90));
+        assert!(lit(&canvas, 10, 50));
+        assert!(!lit(&canvas, 50, 50));
+    }
+
+    #[test]
+    fn hands_point_at_the_time() {
+        let canvas = render(&[
+            "test", "-w", "100", "-h", "100", "clock", "-r", "0.8", "-s", "1", "-t", "3:00",
+        ]);
+        assert!(lit(&canvas, 50, 50));
+        // the minute hand points at twelve, the hour hand at three
+        assert!(lit(&canvas, 50, 25));
+        assert!(lit(&canvas, 65, 50));
+        assert!(!lit(&canvas, 35, 50));
+    }
+
+    #[test]
+    fn radius_and_size_are_bounded() {
+        assert!(validate_radius("0.75").is_ok());
+        assert!(validate_radius("-0.5").is_err());
+        assert!(validate_radius("0").is_err());
+        assert!(validate_radius("1e12").is_err());
+        assert!(validate_size("100").is_ok());
+        assert!(validate_size("101").is_err());
+    }
+
+    #[test]
+    fn marks_off_the_canvas_are_skipped() {
+        // most of the face is off the canvas, but the marks that are on it still draw
+        let canvas = render(&[
+            "test", "-w", "100", "-h", "100", "clock", "-r", "10", "-s", "1", "-t", "3:00",
+        ]);
+        assert!(lit(&canvas, 50, 50));
+    }
+
+    #[test]
+    fn parse_times() {
+        assert_eq!(parse_time("10:08").unwrap(), (10, 8));
+        assert_eq!(parse_time("23:59").unwrap(), (23, 59));
+        assert!(parse_time("24:00").is_err());
+        assert!(parse_time("1008").is_err());
+        assert!(parse_time("a:b").is_err());
+    }
+
+    #[test]
+    fn now_shows_the_local_time() {
+        let args = ["test", "-w", "60", "-h", "60", "clock", "-s", "1", "-t"];
+        let before = local_time();
+        let now = render(&[&args[..], &[NOW]].concat());
+        let after = local_time();
+        assert!(before.0 < 24 && before.1 < 60);
+        // the minute may turn over while the face is drawn
+        let at = |(h, m): (u32, u32)| render(&[&args[..], &[&format!("{}:{:02}", h, m)]].concat());
+        assert!(now == at(before) || now == at(after));
+    }
+}
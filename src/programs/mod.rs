@@ -1,9 +1,114 @@
 pub mod arch;
+pub mod cast;
+pub mod clock;
+pub mod silhouette;
+pub mod sphere;
 
-use clap::{ArgMatches, Command};
-use image::{ImageBuffer, Rgb};
+use crate::error::Result;
+use crate::programs::arch::Arch;
+use crate::programs::clock::Clock;
+use crate::programs::silhouette::Silhouette;
+use crate::programs::sphere::ShadedSphere;
+use clap::{Arg, ArgMatches, Command};
+use ray_tracer::canvas::Layers;
 
-pub trait MakeImage {
-    fn subcommand() -> Command<'static>;
-    fn make(matches: &ArgMatches) -> ImageBuffer<Rgb<u8>, Vec<u8>>;
+///
+/// A program that `rtc` can run as a subcommand to produce an image
+///
+pub trait MakeImage: Sync {
+    ///
+    /// The subcommand name, e.g. `rtc arch`
+    ///
+    fn name(&self) -> &'static str;
+
+    ///
+    /// The one line description shown in `rtc --help` and `rtc list`
+    ///
+    fn about(&self) -> &'static str;
+
+    ///
+    /// The program's own arguments, on top of the global ones
+    ///
+    fn args(&self) -> Vec<Arg<'static>> {
+        vec![]
+    }
+
+    ///
+    /// Renders the image in floating point, so that formats like OpenEXR keep the values the
+    /// program computed; it's only quantized for the 8-bit formats
+    ///
+    fn make(&self, matches: &ArgMatches) -> Result<Layers>;
+
+    ///
+    /// Renders one frame of an animation, `frame` counting from 0 up to `frames - 1`.
+    /// Programs that don't move show the same still in every frame
+    ///
+    fn make_frame(&self, matches: &ArgMatches, _frame: u32, _frames: u32) -> Result<Layers> {
+        self.make(matches)
+    }
+
+    ///
+    /// Renders every frame in order, handing each one to `emit` as soon as it's done, and
+    /// returns the contents of each of `extra_outputs` in the same order. Programs that can
+    /// share work between frames, such as a simulation, override this to do it once
+    ///
+    fn make_frames(
+        &self,
+        matches: &ArgMatches,
+        frames: u32,
+        emit: &mut dyn FnMut(Layers) -> Result<()>,
+    ) -> Result<Vec<String>> {
+        for frame in 0..frames {
+            emit(self.make_frame(matches, frame, frames)?)?;
+        }
+        Ok(vec![])
+    }
+
+    ///
+    /// Files the program writes besides the image, such as the data behind it, as given on the
+    /// command line. They may hold the same placeholders as --output, and are written once the
+    /// frames are done, following the same rules
+    ///
+    fn extra_outputs(&self, _matches: &ArgMatches) -> Result<Vec<String>> {
+        Ok(vec![])
+    }
+
+    fn subcommand(&self) -> Command<'static> {
+        Command::new(self.name())
+            .about(self.about())
+            .args(self.args())
+    }
+}
+
+///
+/// Every program `rtc` knows about; add new programs here
+///
+pub static PROGRAMS: &[&dyn MakeImage] = &[&Arch {}, &Clock {}, &Silhouette {}, &ShadedSphere {}];
+
+///
+/// Looks up a program by its subcommand name
+///
+pub fn find_program(name: &str) -> Option<&'static dyn MakeImage> {
+    PROGRAMS.iter().copied().find(|p| p.name() == name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    #[test]
+    fn program_names_are_unique() {
+        let names: HashSet<_> = PROGRAMS.iter().map(|p| p.name()).collect();
+        assert_eq!(names.len(), PROGRAMS.len());
+        assert!(!names.contains(crate::commands::LIST));
+    }
+
+    #[test]
+    fn programs_are_found_by_name() {
+        for p in PROGRAMS {
+            assert_eq!(find_program(p.name()).unwrap().name(), p.name());
+        }
+        assert!(find_program("nope").is_none());
+    }
 }
@@ -0,0 +1,539 @@
+use crate::commands::validate_color;
+use crate::error::Result;
+use crate::programs::cast::{
+    cast_at_wall, exposure, sphere_args, sphere_from_args, wall_from_args, Wall,
+};
+use crate::programs::MakeImage;
+use clap::{Arg, ArgMatches};
+use ray_tracer::canvas::Layers;
+use ray_tracer::color::color_from_hex;
+
+pub struct Silhouette {}
+
+pub const SILHOUETTE: &str = "silhouette";
+
+pub const COLORL: &str = "color";
+pub const COLOR: char = 'c';
+
+impl MakeImage for Silhouette {
+    fn name(&self) -> &'static str {
+        SILHOUETTE
+    }
+
+    fn about(&self) -> &'static str {
+        "Casts rays at a sphere and paints its flat silhouette on a wall behind it"
+    }
+
+    fn args(&self) -> Vec<Arg<'static>> {
+        let mut args = sphere_args();
+        args.push(
+            Arg::new(COLORL)
+                .long(COLORL)
+                .short(COLOR)
+                .help("The color of the silhouette")
+                .required(false)
+                .validator(validate_color)
+                .default_value("#ff0000"),
+        );
+        args
+    }
+
+    fn make(&self, matches: &ArgMatches) -> Result<Layers> {
+        self.make_frame(matches, 0, 1)
+    }
+
+    fn make_frame(&self, matches: &ArgMatches, frame: u32, frames: u32) -> Result<Layers> {
+        render(matches, &wall_from_args(matches)?, frame, frames)
+    }
+
+    ///
+    /// Loads the wall, and with it any environment map, once for every frame
+    ///
+    fn make_frames(
+        &self,
+        matches: &ArgMatches,
+        frames: u32,
+        emit: &mut dyn FnMut(Layers) -> Result<()>,
+    ) -> Result<Vec<String>> {
+        let wall = wall_from_args(matches)?;
+        for frame in 0..frames {
+            emit(render(matches, &wall, frame, frames)?)?;
+        }
+        Ok(vec![])
+    }
+}
+
+fn render(matches: &ArgMatches, wall: &Wall, frame: u32, frames: u32) -> Result<Layers> {
+    let exposure = exposure(matches, wall.shutter, frame, frames)?;
+    let mut s = sphere_from_args(matches, exposure)?;
+    let color = color_from_hex(matches.value_of(COLORL).unwrap()).unwrap();
+    s.material.color = color;
+    cast_at_wall(wall, &s, exposure, None, |_, _, _| color)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{program_matches, temp_path, try_matches};
+    use image::{ImageBuffer, Rgb};
+    use ray_tracer::canvas::{quantize, HdrImage};
+    use ray_tracer::environment::{load_equirectangular, sample_environment};
+    use ray_tracer::tuple::vector;
+
+    fn render(args: &[&str]) -> Result<ImageBuffer<Rgb<u8>, Vec<u8>>> {
+        let matches = program_matches(args);
+        Ok(quantize(&Silhouette {}.make(&matches)?.color))
+    }
+
+    #[test]
+    fn silhouette_is_centered() {
+        let canvas = render(&["test", "-w", "50", "-h", "50", "silhouette"]).unwrap();
+        assert_eq!(canvas.get_pixel(25, 25).0, [255, 0, 0]);
+        assert_eq!(canvas.get_pixel(0, 0).0, [0, 0, 0]);
+        assert_eq!(canvas.get_pixel(49, 49).0, [0, 0, 0]);
+    }
+
+    #[test]
+    fn translated_sphere_moves_left() {
+        let canvas = render(&[
+            "test",
+            "-w",
+            "50",
+            "-h",
+            "50",
+            "silhouette",
+            "--scale",
+            "0.5,0.5,0.5",
+            "--translate",
+            "-0.6,0,0",
+            "-c",
+            "#00ff00",
+        ])
+        .unwrap();
+        assert_eq!(canvas.get_pixel(12, 25).0, [0, 255, 0]);
+        assert_eq!(canvas.get_pixel(40, 25).0, [0, 0, 0]);
+    }
+
+    #[test]
+    fn frames_move_from_the_start_to_the_to_values() {
+        let matches = program_matches(&[
+            "test",
+            "-w",
+            "50",
+            "-h",
+            "50",
+            "silhouette",
+            "--scale",
+            "0.5,0.5,0.5",
+            "--translate",
+            "-0.6,0,0",
+            "--to-translate",
+            "0.6,0,0",
+            "--easing",
+            "smoothstep",
+        ]);
+        let red = [255, 0, 0];
+        let first = Silhouette {}.make_frame(&matches, 0, 3).unwrap();
+        let last = Silhouette {}.make_frame(&matches, 2, 3).unwrap();
+        assert_eq!(Silhouette {}.make(&matches).unwrap(), first);
+        let (first, last) = (quantize(&first.color), quantize(&last.color));
+        assert_eq!(first.get_pixel(12, 25).0, red);
+        assert_ne!(first.get_pixel(37, 25).0, red);
+        assert_eq!(last.get_pixel(37, 25).0, red);
+        assert_ne!(last.get_pixel(12, 25).0, red);
+    }
+
+    #[test]
+    fn hits_fill_in_the_auxiliary_buffers() {
+        let matches = program_matches(&[
+            "test",
+            "-w",
+            "50",
+            "-h",
+            "50",
+            "silhouette",
+            "-c",
+            "#00ff00",
+        ]);
+        let layers = Silhouette {}.make(&matches).unwrap();
+        let (albedo, normal, depth) = (
+            layers.albedo.unwrap(),
+            layers.normal.unwrap(),
+            layers.depth.unwrap(),
+        );
+        assert_eq!(albedo.get_pixel(25, 25).0, [0.0, 1.0, 0.0]);
+        // the middle of the unit sphere faces straight back at the origin, 4 units away
+        assert!(normal.get_pixel(25, 25).0[2] < -0.99);
+        assert!((depth.get_pixel(25, 25).0[0] - 4.0).abs() < 0.01);
+        assert_eq!(depth.get_pixel(0, 0).0, [0.0; 3]);
+        // the sphere is the only object, and its equator runs across the middle
+        let (object_id, uv) = (layers.object_id.unwrap(), layers.uv.unwrap());
+        assert_eq!(object_id.get_pixel(25, 25).0, [1.0; 3]);
+        assert_eq!(object_id.get_pixel(0, 0).0, [0.0; 3]);
+        assert_eq!(layers.material_id.unwrap().get_pixel(25, 25).0, [1.0; 3]);
+        assert!((uv.get_pixel(25, 25).0[1] - 0.5).abs() < 0.01);
+        // without a light nothing casts a shadow
+        assert!(layers.shadow.is_none());
+    }
+
+    #[test]
+    fn misses_see_the_background() {
+        let canvas = render(&[
+            "test",
+            "-w",
+            "20",
+            "-h",
+            "20",
+            "silhouette",
+            "--background",
+            "#0000ff",
+        ])
+        .unwrap();
+        assert_eq!(canvas.get_pixel(0, 0).0, [0, 0, 255]);
+        assert_eq!(canvas.get_pixel(10, 10).0, [255, 0, 0]);
+    }
+
+    #[test]
+    fn misses_see_the_environment_map() {
+        // the top half of the panorama is the sky, the bottom half the ground
+        let map = temp_path("env.exr");
+        let panorama = HdrImage::from_fn(8, 4, |_, y| {
+            if y < 2 {
+                Rgb([0.0, 0.0, 4.0])
+            } else {
+                Rgb([0.0, 1.0, 0.0])
+            }
+        });
+        image::DynamicImage::ImageRgb32F(panorama)
+            .save(&map)
+            .unwrap();
+        let matches = program_matches(&[
+            "test",
+            "-w",
+            "20",
+            "-h",
+            "20",
+            "silhouette",
+            "--environment",
+            map.to_str().unwrap(),
+        ]);
+        let layers = Silhouette {}.make(&matches).unwrap();
+        std::fs::remove_file(&map).unwrap();
+        assert_eq!(layers.color.get_pixel(0, 0).0, [0.0, 0.0, 4.0]);
+        assert_eq!(layers.color.get_pixel(0, 19).0, [0.0, 1.0, 0.0]);
+    }
+
+    #[test]
+    fn the_environment_is_loaded_once_per_run() {
+        let map = temp_path("env-once.exr");
+        image::DynamicImage::ImageRgb32F(HdrImage::from_pixel(8, 4, Rgb([0.0, 0.0, 4.0])))
+            .save(&map)
+            .unwrap();
+        let matches = program_matches(&[
+            "test",
+            "-w",
+            "10",
+            "-h",
+            "10",
+            "silhouette",
+            "--environment",
+            map.to_str().unwrap(),
+        ]);
+        // the map is gone after the first frame, so later frames can't be loading it again
+        let mut rendered = 0;
+        Silhouette {}
+            .make_frames(&matches, 3, &mut |layers| {
+                if rendered == 0 {
+                    std::fs::remove_file(&map).unwrap();
+                }
+                assert_eq!(layers.color.get_pixel(0, 0).0, [0.0, 0.0, 4.0]);
+                rendered += 1;
+                Ok(())
+            })
+            .unwrap();
+        assert_eq!(rendered, 3);
+    }
+
+    #[test]
+    fn missing_environment_maps_are_rejected() {
+        let result = render(&[
+            "test",
+            "silhouette",
+            "--environment",
+            "/nonexistent/sky.hdr",
+        ]);
+        assert_eq!(result.unwrap_err().exit_code(), crate::error::EXIT_USAGE);
+    }
+
+    #[test]
+    fn zero_scale_is_rejected() {
+        let result = render(&["test", "silhouette", "--scale", "0,1,1"]);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn moving_spheres_smear_while_the_shutter_is_open() {
+        let args = [
+            "test",
+            "-w",
+            "40",
+            "-h",
+            "20",
+            "silhouette",
+            "--scale",
+            "0.5,0.5,0.5",
+            "--translate",
+            "-1,0,0",
+            "--to-translate",
+            "1,0,0",
+            "--samples",
+            "16",
+        ];
+        let red = |extra: &[&str]| {
+            let matches = program_matches(&[&args[..], extra].concat());
+            let color = Silhouette {}.make(&matches).unwrap().color;
+            (0..40)
+                .map(|x| color.get_pixel(x, 10).0[0])
+                .collect::<Vec<_>>()
+        };
+        let partly = |row: &[f32]| row.iter().filter(|r| **r > 0.0 && **r < 1.0).count();
+
+        // with the shutter closed the sphere is caught at the start, with hard edges
+        let sharp = red(&[]);
+        assert_eq!(partly(&sharp), 0);
+        assert_eq!(sharp[20], 0.0);
+
+        // open for the whole move it's a streak, solid only where it stayed the whole time
+        let blurred = red(&["--shutter-close", "1"]);
+        assert!(partly(&blurred) > 10);
+        assert!((0.4..0.6).contains(&blurred[20]), "{}", blurred[20]);
+        assert_eq!(blurred[0], 0.0);
+    }
+
+    #[test]
+    fn spheres_off_the_focus_are_blurred() {
+        let args = [
+            "test",
+            "-w",
+            "40",
+            "-h",
+            "40",
+            "silhouette",
+            "--samples",
+            "16",
+        ];
+        let partly = |extra: &[&str]| {
+            let matches = program_matches(&[&args[..], extra].concat());
+            let color = Silhouette {}.make(&matches).unwrap().color;
+            (0..40)
+                .filter(|x| (0.01..0.99).contains(&color.get_pixel(*x, 20).0[0]))
+                .count()
+        };
+        // a pinhole keeps everything sharp wherever it's focused
+        assert_eq!(partly(&["--focus", "2"]), 0);
+        // the sphere's edge is about 5 units away, where the lens focuses by default
+        let sharp = partly(&["--aperture", "0.3"]);
+        assert!(sharp <= 2, "{}", sharp);
+        let blurred = partly(&["--aperture", "0.3", "--focus", "2"]);
+        assert!(blurred > 6, "{}", blurred);
+        assert!(partly(&["--aperture", "0.3", "--focus", "2", "--blades", "6"]) > 6);
+    }
+
+    #[test]
+    fn the_shutter_closes_after_it_opens() {
+        let result = render(&[
+            "test",
+            "silhouette",
+            "--shutter-open",
+            "0.5",
+            "--shutter-close",
+            "0.2",
+        ]);
+        assert_eq!(result.unwrap_err().exit_code(), crate::error::EXIT_USAGE);
+        assert!(try_matches(&["test", "silhouette", "--shutter-close", "1.5"]).is_err());
+        assert!(try_matches(&["test", "silhouette", "--samples", "0"]).is_err());
+        assert!(try_matches(&["test", "silhouette", "--blades", "2"]).is_err());
+        assert!(try_matches(&["test", "silhouette", "--focus", "0"]).is_err());
+    }
+
+    ///
+    /// How many pixels across the middle row of a square canvas `size` pixels wide are red
+    ///
+    fn width_of_sphere(size: u32, extra: &[&str]) -> usize {
+        let side = size.to_string();
+        let args = [&["test", "-w", &side, "-h", &side, "silhouette"], extra].concat();
+        let canvas = render(&args).unwrap();
+        (0..size)
+            .filter(|x| canvas.get_pixel(*x, size / 2).0 == [255, 0, 0])
+            .count()
+    }
+
+    #[test]
+    fn perspective_is_the_default_projection() {
+        let args = ["test", "-w", "30", "-h", "20", "silhouette"];
+        let explicit = render(&[&args[..], &["--projection", "perspective"]].concat()).unwrap();
+        assert_eq!(render(&args).unwrap(), explicit);
+    }
+
+    #[test]
+    fn wider_views_make_the_sphere_smaller() {
+        let wall = width_of_sphere(40, &[]);
+        let wide = width_of_sphere(40, &["--field-of-view", "60"]);
+        assert!(wide < wall, "{} {}", wide, wall);
+    }
+
+    #[test]
+    fn orthographic_views_cover_the_wall_size() {
+        // the unit sphere is 2 across, half of the 4 units the 40 pixels cover
+        let width = width_of_sphere(40, &["--projection", "orthographic", "--wall-size", "4"]);
+        assert_eq!(width, 20);
+        // moving back doesn't make it any smaller
+        let args = [
+            "--projection",
+            "orthographic",
+            "--wall-size",
+            "4",
+            "--origin",
+            "0,0,-50",
+        ];
+        assert_eq!(width_of_sphere(40, &args), 20);
+    }
+
+    #[test]
+    fn fisheyes_leave_outside_the_image_circle_black() {
+        let canvas = render(&[
+            "test",
+            "-w",
+            "40",
+            "-h",
+            "40",
+            "silhouette",
+            "--projection",
+            "fisheye",
+            "--background",
+            "#0000ff",
+        ])
+        .unwrap();
+        assert_eq!(canvas.get_pixel(0, 0).0, [0, 0, 0]);
+        assert_eq!(canvas.get_pixel(20, 0).0, [0, 0, 255]);
+        assert_eq!(canvas.get_pixel(20, 20).0, [255, 0, 0]);
+    }
+
+    #[test]
+    fn panoramas_load_back_as_environments() {
+        let matches = program_matches(&[
+            "test",
+            "-w",
+            "64",
+            "-h",
+            "32",
+            "silhouette",
+            "--projection",
+            "equirectangular",
+            "--background",
+            "#0000ff",
+        ]);
+        let panorama = temp_path("panorama.exr");
+        let color = Silhouette {}.make(&matches).unwrap().color;
+        image::DynamicImage::ImageRgb32F(color)
+            .save(&panorama)
+            .unwrap();
+        let environment = load_equirectangular(&panorama).unwrap();
+        std::fs::remove_file(&panorama).unwrap();
+        // the sphere is straight ahead of the origin, along +z
+        let ahead = sample_environment(&environment, vector(0.0, 0.0, 1.0));
+        assert_eq!(ahead, (1.0, 0.0, 0.0));
+        let behind = sample_environment(&environment, vector(0.0, 0.0, -1.0));
+        assert_eq!(behind, (0.0, 0.0, 1.0));
+    }
+
+    #[test]
+    fn cube_faces_see_all_around_the_origin() {
+        let face = |name: &str| {
+            render(&[
+                "test",
+                "-w",
+                "20",
+                "-h",
+                "20",
+                "silhouette",
+                "--projection",
+                "cube-face",
+                "--face",
+                name,
+                "--background",
+                "#0000ff",
+            ])
+            .unwrap()
+            .get_pixel(10, 10)
+            .0
+        };
+        assert_eq!(face("+z"), [255, 0, 0]);
+        for name in ["-z", "+x", "-y"] {
+            assert_eq!(face(name), [0, 0, 255], "{}", name);
+        }
+    }
+
+    #[test]
+    fn views_are_checked() {
+        assert!(try_matches(&["test", "silhouette", "--projection", "pinhole"]).is_err());
+        assert!(try_matches(&["test", "silhouette", "--face", "+w"]).is_err());
+        assert!(try_matches(&["test", "silhouette", "--field-of-view", "0"]).is_err());
+        // a perspective view can't see as far as its sides, a fisheye can see all around
+        let result = render(&["test", "silhouette", "--field-of-view", "180"]);
+        assert_eq!(result.unwrap_err().exit_code(), crate::error::EXIT_USAGE);
+        let all_around = ["--projection", "fisheye", "--field-of-view", "360"];
+        assert!(render(&[&["test", "silhouette"], &all_around[..]].concat()).is_ok());
+        let beyond = ["--projection", "fisheye", "--field-of-view", "361"];
+        assert!(render(&[&["test", "silhouette"], &beyond[..]].concat()).is_err());
+    }
+
+    #[test]
+    fn stereo_pairs_are_packed_into_one_image() {
+        let args = ["test", "-w", "20", "-h", "16", "silhouette", "--stereo"];
+        let make = |layout: &str| {
+            let matches = program_matches(&[&args[..], &[layout, "--interocular", "1"]].concat());
+            Silhouette {}.make(&matches).unwrap()
+        };
+        let side_by_side = make("side-by-side");
+        assert_eq!(side_by_side.color.dimensions(), (40, 16));
+        assert_eq!(side_by_side.depth.unwrap().dimensions(), (40, 16));
+        // the eyes converge on the wall, so the sphere in front of it shifts toward the nose
+        let middle = |image: &HdrImage, from: u32| {
+            let xs: Vec<u32> = (from..from + 20)
+                .filter(|x| image.get_pixel(*x, 8).0[0] > 0.5)
+                .collect();
+            (xs[0] + xs[xs.len() - 1]) as f64 / 2.0 - from as f64
+        };
+        let color = &side_by_side.color;
+        assert!(middle(color, 0) > middle(color, 20) + 1.0);
+        assert_eq!(make("top-bottom").color.dimensions(), (20, 32));
+    }
+
+    #[test]
+    fn anaglyphs_take_red_from_the_left_eye() {
+        let matches = program_matches(&[
+            "test",
+            "-w",
+            "20",
+            "-h",
+            "16",
+            "silhouette",
+            "-c",
+            "#ffffff",
+            "--stereo",
+            "anaglyph",
+            "--interocular",
+            "1",
+        ]);
+        let layers = Silhouette {}.make(&matches).unwrap();
+        assert_eq!(layers.color.dimensions(), (20, 16));
+        assert!(layers.depth.is_none());
+        let row: Vec<[f32; 3]> = (0..20).map(|x| layers.color.get_pixel(x, 8).0).collect();
+        assert!(row.contains(&[1.0, 0.0, 0.0]), "{:?}", row);
+        assert!(row.contains(&[0.0, 1.0, 1.0]), "{:?}", row);
+        assert!(row.contains(&[1.0, 1.0, 1.0]), "{:?}", row);
+        assert!(try_matches(&["test", "silhouette", "--stereo", "interlaced"]).is_err());
+        assert!(try_matches(&["test", "silhouette", "--interocular", "0"]).is_err());
+    }
+}
@@ -0,0 +1,380 @@
+use crate::commands::{
+    parse_arg, validate_color, validate_f64, validate_non_negative_f64, validate_triple,
+};
+use crate::error::{Error, Result};
+use crate::programs::cast::{
+    cast_at_wall, exposure, parse_animated_triple, sphere_args, sphere_from_args, to_arg,
+    wall_from_args, Wall,
+};
+use crate::programs::MakeImage;
+use clap::{Arg, ArgMatches};
+use ray_tracer::canvas::Layers;
+use ray_tracer::color::color_from_hex;
+use ray_tracer::keyframe::{keyframe, track, Easing};
+use ray_tracer::light::{lighting, point_light};
+use ray_tracer::material::Perturbation;
+use ray_tracer::ray::position;
+use ray_tracer::tuple::{neg_tup, point};
+use std::sync::Arc;
+
+pub struct ShadedSphere {}
+
+pub const SPHERE: &str = "sphere";
+
+pub const COLORL: &str = "color";
+pub const COLOR: char = 'c';
+pub const LIGHTL: &str = "light";
+pub const TO_LIGHTL: &str = "to-light";
+pub const LIGHT_COLORL: &str = "light-color";
+pub const AMBIENTL: &str = "ambient";
+pub const DIFFUSEL: &str = "diffuse";
+pub const SPECULARL: &str = "specular";
+pub const SHININESSL: &str = "shininess";
+pub const NORMAL_MAPL: &str = "normal-map";
+pub const BUMPL: &str = "bump";
+pub const BUMP_STRENGTHL: &str = "bump-strength";
+
+// the material's defaults as clap needs them, which tests hold to the library's values
+const DEFAULT_AMBIENT_ARG: &str = "0.1";
+const DEFAULT_DIFFUSE_ARG: &str = "0.9";
+const DEFAULT_SPECULAR_ARG: &str = "0.9";
+const DEFAULT_SHININESS_ARG: &str = "200";
+
+impl MakeImage for ShadedSphere {
+    fn name(&self) -> &'static str {
+        SPHERE
+    }
+
+    fn about(&self) -> &'static str {
+        "Casts rays at a sphere and shades it with a point light using the Phong model"
+    }
+
+    fn args(&self) -> Vec<Arg<'static>> {
+        let mut args = sphere_args();
+        args.extend([
+            Arg::new(COLORL)
+                .long(COLORL)
+                .short(COLOR)
+                .help("The color of the sphere's material")
+                .required(false)
+                .validator(validate_color)
+                .default_value("#ff33ff"),
+            Arg::new(LIGHTL)
+                .long(LIGHTL)
+                .help("The position of the light, as x,y,z")
+                .required(false)
+                .validator(validate_triple)
+                .allow_hyphen_values(true)
+                .default_value("-10,10,-10"),
+            to_arg(TO_LIGHTL, "The light position to animate to over --frames"),
+            Arg::new(LIGHT_COLORL)
+                .long(LIGHT_COLORL)
+                .help("The color of the light")
+                .required(false)
+                .validator(validate_color)
+                .default_value("#ffffff"),
+            material_arg(
+                AMBIENTL,
+                "The ambient reflection of the material",
+                DEFAULT_AMBIENT_ARG,
+            ),
+            material_arg(
+                DIFFUSEL,
+                "The diffuse reflection of the material",
+                DEFAULT_DIFFUSE_ARG,
+            ),
+            material_arg(
+                SPECULARL,
+                "The specular reflection of the material",
+                DEFAULT_SPECULAR_ARG,
+            ),
+            material_arg(
+                SHININESSL,
+                "How tight the specular highlight is",
+                DEFAULT_SHININESS_ARG,
+            ),
+            Arg::new(NORMAL_MAPL)
+                .long(NORMAL_MAPL)
+                .help("A tangent-space normal map image, wrapped around the sphere")
+                .required(false)
+                .takes_value(true)
+                .conflicts_with(BUMPL),
+            Arg::new(BUMPL)
+                .long(BUMPL)
+                .help("A grayscale height map image, wrapped around the sphere, brighter is higher")
+                .required(false)
+                .takes_value(true),
+            Arg::new(BUMP_STRENGTHL)
+                .long(BUMP_STRENGTHL)
+                .help("How strongly the slope of the --bump map tilts the surface")
+                .required(false)
+                .validator(validate_f64)
+                .allow_hyphen_values(true)
+                .default_value("0.01"),
+        ]);
+        args
+    }
+
+    fn make(&self, matches: &ArgMatches) -> Result<Layers> {
+        self.make_frame(matches, 0, 1)
+    }
+
+    fn make_frame(&self, matches: &ArgMatches, frame: u32, frames: u32) -> Result<Layers> {
+        let perturbation = perturbation_from_args(matches)?;
+        render(
+            matches,
+            &wall_from_args(matches)?,
+            &perturbation,
+            frame,
+            frames,
+        )
+    }
+
+    ///
+    /// Loads the wall, and with it any environment map, and the normal or bump map once for
+    /// every frame
+    ///
+    fn make_frames(
+        &self,
+        matches: &ArgMatches,
+        frames: u32,
+        emit: &mut dyn FnMut(Layers) -> Result<()>,
+    ) -> Result<Vec<String>> {
+        let wall = wall_from_args(matches)?;
+        let perturbation = perturbation_from_args(matches)?;
+        for frame in 0..frames {
+            emit(render(matches, &wall, &perturbation, frame, frames)?)?;
+        }
+        Ok(vec![])
+    }
+}
+
+fn render(
+    matches: &ArgMatches,
+    wall: &Wall,
+    perturbation: &Option<Perturbation>,
+    frame: u32,
+    frames: u32,
+) -> Result<Layers> {
+    let exposure = exposure(matches, wall.shutter, frame, frames)?;
+    let mut s = sphere_from_args(matches, exposure)?;
+    s.material.color = color_from_hex(matches.value_of(COLORL).unwrap()).unwrap();
+    s.material.ambient = parse_arg(matches, AMBIENTL)?;
+    s.material.diffuse = parse_arg(matches, DIFFUSEL)?;
+    s.material.specular = parse_arg(matches, SPECULARL)?;
+    s.material.shininess = parse_arg(matches, SHININESSL)?;
+    s.material.perturbation = perturbation.clone();
+
+    let (from, to) = parse_animated_triple(matches, LIGHTL, TO_LIGHTL)?;
+    let light_track = track(vec![
+        keyframe(0.0, point(from.0, from.1, from.2), Easing::Linear),
+        keyframe(1.0, point(to.0, to.1, to.2), Easing::Linear),
+    ]);
+    let light_color = color_from_hex(matches.value_of(LIGHT_COLORL).unwrap()).unwrap();
+    // the light holds still while the shutter is open
+    let light = point_light(light_track.sample(exposure.0).unwrap(), light_color);
+
+    cast_at_wall(
+        wall,
+        &s,
+        exposure,
+        Some(light.position),
+        |r, hit, normal| {
+            let p = position(r, hit.t);
+            let eye = neg_tup(r.direction);
+            lighting(&hit.object.material, &light, p, eye, normal)
+        },
+    )
+}
+
+///
+/// The --normal-map or --bump map, if either was given
+///
+fn perturbation_from_args(matches: &ArgMatches) -> Result<Option<Perturbation>> {
+    let load = |name: &str, path: &str| {
+        image::open(path).map_err(|e| Error::invalid_argument(name, path, &e.to_string()))
+    };
+    if let Some(path) = matches.value_of(NORMAL_MAPL) {
+        let map = load(NORMAL_MAPL, path)?.to_rgb8();
+        return Ok(Some(Perturbation::NormalMap(Arc::new(map))));
+    }
+    if let Some(path) = matches.value_of(BUMPL) {
+        let heights = load(BUMPL, path)?.to_luma32f();
+        let strength = parse_arg(matches, BUMP_STRENGTHL)?;
+        return Ok(Some(Perturbation::Bump(Arc::new(heights), strength)));
+    }
+    Ok(None)
+}
+
+fn material_arg(name: &'static str, help: &'static str, default: &'static str) -> Arg<'static> {
+    Arg::new(name)
+        .long(name)
+        .help(help)
+        .required(false)
+        .validator(validate_non_negative_f64)
+        .default_value(default)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{program_matches, temp_path, try_matches};
+    use image::{ImageBuffer, Rgb};
+    use ray_tracer::canvas::quantize;
+
+    fn render(args: &[&str]) -> ImageBuffer<Rgb<u8>, Vec<u8>> {
+        let matches = program_matches(args);
+        quantize(&ShadedSphere {}.make(&matches).unwrap().color)
+    }
+
+    fn brightness(canvas: &ImageBuffer<Rgb<u8>, Vec<u8>>, x: u32, y: u32) -> u32 {
+        canvas.get_pixel(x, y).0.iter().map(|c| *c as u32).sum()
+    }
+
+    #[test]
+    fn lit_side_is_brighter() {
+        let canvas = render(&["test", "-w", "60", "-h", "60", "sphere"]);
+        // the default light is up and to the left
+        assert!(brightness(&canvas, 22, 22) > brightness(&canvas, 38, 38));
+        assert_eq!(brightness(&canvas, 0, 0), 0);
+    }
+
+    #[test]
+    fn bright_lights_go_past_one() {
+        let matches = program_matches(&[
+            "test",
+            "-w",
+            "60",
+            "-h",
+            "60",
+            "sphere",
+            "-c",
+            "#ffffff",
+            "--diffuse",
+            "3",
+        ]);
+        let layers = ShadedSphere {}.make(&matches).unwrap();
+        assert!(layers.color.get_pixel(22, 22).0[0] > 1.0);
+    }
+
+    fn save_temp(name: &str, image: image::DynamicImage) -> std::path::PathBuf {
+        let path = temp_path(&format!("{}.png", name));
+        image.save(&path).unwrap();
+        path
+    }
+
+    #[test]
+    fn normal_maps_change_the_shading() {
+        let plain = render(&["test", "-w", "40", "-h", "40", "sphere"]);
+        // every normal leans fully toward +u, around the sphere
+        let map = save_temp(
+            "normals",
+            image::DynamicImage::ImageRgb8(ImageBuffer::from_pixel(4, 4, Rgb([255, 128, 128]))),
+        );
+        let path = map.to_str().unwrap();
+        let mapped = render(&[
+            "test",
+            "-w",
+            "40",
+            "-h",
+            "40",
+            "sphere",
+            "--normal-map",
+            path,
+        ]);
+        std::fs::remove_file(&map).unwrap();
+        assert_ne!(plain, mapped);
+    }
+
+    #[test]
+    fn flat_bump_maps_change_nothing() {
+        let plain = render(&["test", "-w", "40", "-h", "40", "sphere"]);
+        let map = save_temp(
+            "heights",
+            image::DynamicImage::ImageLuma8(ImageBuffer::from_pixel(4, 4, image::Luma([90]))),
+        );
+        let path = map.to_str().unwrap();
+        let bumped = render(&["test", "-w", "40", "-h", "40", "sphere", "--bump", path]);
+        std::fs::remove_file(&map).unwrap();
+        assert_eq!(plain, bumped);
+    }
+
+    #[test]
+    fn maps_are_loaded_once_per_run() {
+        let map = save_temp(
+            "once",
+            image::DynamicImage::ImageRgb8(ImageBuffer::from_pixel(4, 4, Rgb([255, 128, 128]))),
+        );
+        let matches = program_matches(&[
+            "test",
+            "-w",
+            "20",
+            "-h",
+            "20",
+            "sphere",
+            "--normal-map",
+            map.to_str().unwrap(),
+        ]);
+        let still = ShadedSphere {}.make(&matches).unwrap();
+        // the map is gone after the first frame, so later frames can't be loading it again
+        let mut rendered = 0;
+        ShadedSphere {}
+            .make_frames(&matches, 3, &mut |layers| {
+                if rendered == 0 {
+                    std::fs::remove_file(&map).unwrap();
+                }
+                assert_eq!(layers, still);
+                rendered += 1;
+                Ok(())
+            })
+            .unwrap();
+        assert_eq!(rendered, 3);
+    }
+
+    #[test]
+    fn normal_and_bump_maps_conflict() {
+        let result = try_matches(&["test", "sphere", "--normal-map", "a.png", "--bump", "b.png"]);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn material_defaults_match_the_library() {
+        use ray_tracer::material::material;
+
+        let m = material();
+        let defaults = [
+            (DEFAULT_AMBIENT_ARG, m.ambient),
+            (DEFAULT_DIFFUSE_ARG, m.diffuse),
+            (DEFAULT_SPECULAR_ARG, m.specular),
+            (DEFAULT_SHININESS_ARG, m.shininess),
+        ];
+        for (arg, value) in defaults {
+            assert_eq!(arg.parse::<f64>().unwrap(), value);
+        }
+    }
+
+    #[test]
+    fn negative_material_values_are_rejected() {
+        for name in [AMBIENTL, DIFFUSEL, SPECULARL, SHININESSL] {
+            let negative = format!("--{}=-0.5", name);
+            assert!(try_matches(&["test", "sphere", &negative]).is_err());
+            let zero = format!("--{}=0", name);
+            assert!(try_matches(&["test", "sphere", &zero]).is_ok());
+        }
+    }
+
+    #[test]
+    fn moving_the_light_moves_the_highlight() {
+        let canvas = render(&[
+            "test",
+            "-w",
+            "60",
+            "-h",
+            "60",
+            "sphere",
+            "--light",
+            "10,-10,-10",
+        ]);
+        assert!(brightness(&canvas, 38, 38) > brightness(&canvas, 22, 22));
+    }
+}
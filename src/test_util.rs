@@ -0,0 +1,38 @@
+//!
+//! Fixtures the command line tests share: parsing arguments the way `rtc` does, and naming
+//! scratch files that don't collide with other test runs.
+//!
+use crate::commands::{get_subcommands, global_args};
+use clap::{ArgMatches, Command};
+use std::path::PathBuf;
+
+///
+/// The `rtc` command line, with the global options and every program
+///
+pub fn cli() -> Command<'static> {
+    Command::new("test")
+        .args(global_args())
+        .subcommands(get_subcommands())
+}
+
+///
+/// Parses a full command line, e.g. `["test", "-w", "20", "arch", "--fit"]`
+///
+pub fn try_matches(args: &[&str]) -> clap::Result<ArgMatches> {
+    cli().try_get_matches_from(args)
+}
+
+///
+/// The matches a program is handed for a full command line, which must parse
+///
+pub fn program_matches(args: &[&str]) -> ArgMatches {
+    let matches = try_matches(args).unwrap();
+    matches.subcommand().unwrap().1.clone()
+}
+
+///
+/// A path in the temporary directory, unique to this test run
+///
+pub fn temp_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("rtc-{}-{}", std::process::id(), name))
+}